@@ -0,0 +1,77 @@
+use baby_parallel_vector_graphics::abstract_segment::{AbstractLineSegment, SegType};
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::path::AbstractPath;
+use baby_parallel_vector_graphics::quad_tree::{CapacityPolicy, QuadTree};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use usvg::tiny_skia_path::Point;
+use usvg::FillRule;
+
+const CANVAS: f32 = 1024.0;
+
+/// Many small, overlapping triangles scattered over the canvas with a fixed LCG seed.
+fn dense_scene(num_triangles: u32) -> (Vec<AbstractLineSegment>, Vec<AbstractPath>) {
+    let mut state = 0x2545_f491u32;
+    let mut next = || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1u32 << 24) as f32
+    };
+    let mut segments = Vec::with_capacity(num_triangles as usize * 3);
+    let mut paths = Vec::with_capacity(num_triangles as usize);
+    for path_idx in 0..num_triangles {
+        let cx = next() * CANVAS;
+        let cy = next() * CANVAS;
+        let pts: Vec<Point> = (0..3)
+            .map(|_| Point {
+                x: (cx + (next() - 0.5) * 64.0).clamp(0.0, CANVAS),
+                y: (cy + (next() - 0.5) * 64.0).clamp(0.0, CANVAS),
+            })
+            .collect();
+        for i in 0..3 {
+            segments.push(AbstractLineSegment::new(
+                pts[i],
+                pts[(i + 1) % 3],
+                SegType::Linear,
+                path_idx,
+            ));
+        }
+        let xs = pts.iter().map(|p| p.x);
+        let ys = pts.iter().map(|p| p.y);
+        paths.push(AbstractPath {
+            seg_start_idx: segments.len() - 3,
+            seg_end_idx: segments.len(),
+            fill_rule: FillRule::EvenOdd,
+            paint_id: 0,
+            bounding_box: Rect::from_ltrb(
+                xs.clone().fold(f32::MAX, f32::min),
+                ys.clone().fold(f32::MAX, f32::min),
+                xs.fold(f32::MIN, f32::max),
+                ys.fold(f32::MIN, f32::max),
+            )
+            .unwrap(),
+            stroke_width: None,
+        });
+    }
+    (segments, paths)
+}
+
+fn capacity_policy(c: &mut Criterion) {
+    let (segments, paths) = dense_scene(2000);
+    let root = Rect::from_ltrb(0.0, 0.0, CANVAS, CANVAS).unwrap();
+    let mut group = c.benchmark_group("cpu_build_capacity_policy");
+    for (name, policy) in [
+        ("on_demand", CapacityPolicy::OnDemand),
+        ("reserve_frontier", CapacityPolicy::ReserveFrontier),
+    ] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &policy, |b, &policy| {
+            b.iter(|| {
+                QuadTree::with_capacity_policy(black_box(&segments), &paths, root, 6, 4, policy)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, capacity_policy);
+criterion_main!(benches);
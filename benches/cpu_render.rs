@@ -0,0 +1,130 @@
+use baby_parallel_vector_graphics::abstract_segment::{AbstractLineSegment, SegType};
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::path::{AbstractPath, Paint};
+use baby_parallel_vector_graphics::quad_tree::QuadTree;
+use baby_parallel_vector_graphics::render::{render, supersampled_pixel_count, RenderOptions};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use usvg::tiny_skia_path::Point;
+use usvg::FillRule;
+
+const SIZE: u32 = 256;
+
+/// `layers` opaque rectangles stacked over almost the whole canvas, each slightly
+/// inset from the one below, so every pixel is covered by many paths.
+fn overlapping_rects(layers: u32) -> (Vec<AbstractLineSegment>, Vec<AbstractPath>, Vec<Paint>) {
+    let mut segments = vec![];
+    let mut paths = vec![];
+    let mut paints = vec![];
+    for i in 0..layers {
+        let inset = i as f32 * 0.5;
+        let (l, t, r, b) = (inset, inset, SIZE as f32 - inset, SIZE as f32 - inset);
+        let corners = [(l, t), (r, t), (r, b), (l, b)];
+        let seg_start_idx = segments.len();
+        for k in 0..4 {
+            let (x0, y0) = corners[k];
+            let (x1, y1) = corners[(k + 1) % 4];
+            segments.push(AbstractLineSegment::new(
+                Point { x: x0, y: y0 },
+                Point { x: x1, y: y1 },
+                SegType::Linear,
+                i,
+            ));
+        }
+        paths.push(AbstractPath {
+            seg_start_idx,
+            seg_end_idx: segments.len(),
+            fill_rule: FillRule::EvenOdd,
+            paint_id: paints.len(),
+            bounding_box: Rect::from_ltrb(l, t, r, b).unwrap(),
+            stroke_width: None,
+        });
+        let shade = (i * 7 % 256) as u8;
+        paints.push(Paint::SolidColor {
+            rgba: [shade, 255 - shade, 128, 255],
+        });
+    }
+    (segments, paths, paints)
+}
+
+fn overlapping_opaque(c: &mut Criterion) {
+    let (segments, paths, paints) = overlapping_rects(64);
+    let root = Rect::from_ltrb(0.0, 0.0, SIZE as f32, SIZE as f32).unwrap();
+    let tree = QuadTree::new(&segments, &paths, root, 4, 1).unwrap();
+    let mut pixels = vec![0u8; (SIZE * SIZE * 4) as usize];
+    c.bench_function("cpu_render_overlapping_opaque", |b| {
+        b.iter(|| {
+            render(
+                black_box(&tree),
+                &segments,
+                &paths,
+                &paints,
+                &mut pixels,
+                SIZE,
+                SIZE,
+                &RenderOptions::default(),
+            )
+        })
+    });
+}
+
+/// One opaque circle filling most of the canvas, flattened into `sides` segments.
+fn big_circle(sides: u32) -> (Vec<AbstractLineSegment>, Vec<AbstractPath>, Vec<Paint>) {
+    let (c, r) = (SIZE as f32 / 2.0, SIZE as f32 * 0.45);
+    let at = |i: u32| {
+        let a = i as f32 / sides as f32 * std::f32::consts::TAU;
+        Point {
+            x: c + r * a.cos(),
+            y: c + r * a.sin(),
+        }
+    };
+    let segments: Vec<_> = (0..sides)
+        .map(|i| AbstractLineSegment::new(at(i), at(i + 1), SegType::Linear, 0))
+        .collect();
+    let paths = vec![AbstractPath {
+        seg_start_idx: 0,
+        seg_end_idx: segments.len(),
+        fill_rule: FillRule::NonZero,
+        paint_id: 0,
+        bounding_box: Rect::from_ltrb(c - r, c - r, c + r, c + r).unwrap(),
+        stroke_width: None,
+    }];
+    let paints = vec![Paint::SolidColor {
+        rgba: [40, 90, 200, 255],
+    }];
+    (segments, paths, paints)
+}
+
+fn big_circle_anti_aliased(c: &mut Criterion) {
+    let (segments, paths, paints) = big_circle(256);
+    let root = Rect::from_ltrb(0.0, 0.0, SIZE as f32, SIZE as f32).unwrap();
+    // min_seg 0 stops cells as soon as no edge passes through them.
+    let tree = QuadTree::new(&segments, &paths, root, 6, 0).unwrap();
+    let supersampled = supersampled_pixel_count(&tree, SIZE, SIZE);
+    println!(
+        "{supersampled} of {} pixels supersampled; the rest take one sample or are empty",
+        SIZE * SIZE
+    );
+    let options = RenderOptions {
+        samples_per_axis: 4,
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; (SIZE * SIZE * 4) as usize];
+    c.bench_function("cpu_render_big_circle_4x4", |b| {
+        b.iter(|| {
+            render(
+                black_box(&tree),
+                &segments,
+                &paths,
+                &paints,
+                &mut pixels,
+                SIZE,
+                SIZE,
+                &options,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, overlapping_opaque, big_circle_anti_aliased);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::quad_tree::QuadTree;
+use baby_parallel_vector_graphics::render::{
+    render, segment_test_work, segment_test_work_culled, RenderOptions,
+};
+use baby_parallel_vector_graphics::svg_parser::{parse_svg_str, FlattenOptions};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fmt::Write;
+use std::hint::black_box;
+
+const SIZE: u32 = 512;
+
+/// A `per_axis` x `per_axis` grid of small circles, so a shallow tree leaves many
+/// paths in each cell, each covering only a small part of it.
+fn many_circles_svg(per_axis: u32) -> String {
+    let step = SIZE as f32 / per_axis as f32;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SIZE}\" height=\"{SIZE}\">"
+    );
+    for j in 0..per_axis {
+        for i in 0..per_axis {
+            let _ = write!(
+                svg,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#{:02x}{:02x}80\"/>",
+                (i as f32 + 0.5) * step,
+                (j as f32 + 0.5) * step,
+                step * 0.35,
+                i * 255 / per_axis,
+                j * 255 / per_axis
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn path_culling(c: &mut Criterion) {
+    let parsed = parse_svg_str(&many_circles_svg(24), &FlattenOptions::default()).unwrap();
+    let root = Rect::from_ltrb(0.0, 0.0, SIZE as f32, SIZE as f32).unwrap();
+    let tree = QuadTree::new(&parsed.abs_segments, &parsed.abs_paths, root, 3, 1).unwrap();
+    let all = segment_test_work(&tree, SIZE, SIZE);
+    let culled =
+        segment_test_work_culled(&tree, &parsed.abs_segments, &parsed.abs_paths, SIZE, SIZE);
+    println!(
+        "{} paths: {all} segment tests without path culling, {culled} with ({:.1}%)",
+        parsed.abs_paths.len(),
+        culled as f64 * 100.0 / all as f64
+    );
+
+    let mut pixels = vec![0u8; (SIZE * SIZE * 4) as usize];
+    c.bench_function("cpu_render_many_small_paths", |b| {
+        b.iter(|| {
+            render(
+                black_box(&tree),
+                &parsed.abs_segments,
+                &parsed.abs_paths,
+                &parsed.paints,
+                &mut pixels,
+                SIZE,
+                SIZE,
+                &RenderOptions::default(),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, path_culling);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+use baby_parallel_vector_graphics::abstract_segment::{AbstractLineSegment, SegType};
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::seg_entry::{
+    init_root_seg_entries, subdivide_seg_entry, subdivide_seg_entry_with_scratch, SegEntry,
+    SubdivideScratch,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use usvg::tiny_skia_path::Point;
+
+const CANVAS: f32 = 1024.0;
+/// Cells of one 16x16 grid level, each subdivided once per iteration.
+const GRID: u32 = 16;
+
+/// Counts heap allocations so the two variants can be compared directly.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Many small triangles scattered over the canvas with a fixed LCG seed.
+fn dense_segments(num_triangles: u32) -> Vec<AbstractLineSegment> {
+    let mut state = 0x2545_f491u32;
+    let mut next = || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1u32 << 24) as f32
+    };
+    let mut segments = Vec::with_capacity(num_triangles as usize * 3);
+    for path_idx in 0..num_triangles {
+        let cx = next() * CANVAS;
+        let cy = next() * CANVAS;
+        let pts: Vec<Point> = (0..3)
+            .map(|_| Point {
+                x: (cx + (next() - 0.5) * 64.0).clamp(0.0, CANVAS),
+                y: (cy + (next() - 0.5) * 64.0).clamp(0.0, CANVAS),
+            })
+            .collect();
+        for i in 0..3 {
+            segments.push(AbstractLineSegment::new(
+                pts[i],
+                pts[(i + 1) % 3],
+                SegType::Linear,
+                path_idx,
+            ));
+        }
+    }
+    segments
+}
+
+/// The cells of a `GRID` x `GRID` level with the root entries of the segments whose
+/// bbox overlaps each cell; close enough to a real frontier for allocation counts.
+fn grid_cells(segments: &[AbstractLineSegment]) -> Vec<(Rect, Vec<SegEntry>)> {
+    let root_entries = init_root_seg_entries(segments);
+    let size = CANVAS / GRID as f32;
+    let mut cells = vec![];
+    for gy in 0..GRID {
+        for gx in 0..GRID {
+            let (l, t) = (gx as f32 * size, gy as f32 * size);
+            let bbox = Rect::from_ltrb(l, t, l + size, t + size).unwrap();
+            let entries: Vec<SegEntry> = root_entries
+                .iter()
+                .copied()
+                .filter(|e| {
+                    let [sl, st, sr, sb] = segments[e.seg_idx as usize].bbox_ltrb;
+                    sl < bbox.right() && sr > bbox.left() && st < bbox.bottom() && sb > bbox.top()
+                })
+                .collect();
+            if !entries.is_empty() {
+                cells.push((bbox, entries));
+            }
+        }
+    }
+    cells
+}
+
+fn subdivide_fresh(cells: &mut [(Rect, Vec<SegEntry>)], segments: &[AbstractLineSegment]) -> usize {
+    let mut total = 0;
+    for (bbox, entries) in cells.iter_mut() {
+        let [mx, my] = bbox.mid_point();
+        let mid = Point { x: mx, y: my };
+        total += subdivide_seg_entry(entries, bbox, &mid, segments)
+            .unwrap()
+            .len();
+    }
+    total
+}
+
+fn subdivide_reused(
+    cells: &mut [(Rect, Vec<SegEntry>)],
+    segments: &[AbstractLineSegment],
+    scratch: &mut SubdivideScratch,
+) -> usize {
+    let mut total = 0;
+    for (bbox, entries) in cells.iter_mut() {
+        let [mx, my] = bbox.mid_point();
+        let mid = Point { x: mx, y: my };
+        total += subdivide_seg_entry_with_scratch(entries, bbox, &mid, segments, scratch)
+            .unwrap()
+            .len();
+    }
+    total
+}
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn subdivide_scratch(c: &mut Criterion) {
+    let segments = dense_segments(2000);
+    let mut cells = grid_cells(&segments);
+    let mut scratch = SubdivideScratch::default();
+    // Warm the scratch up to the largest cell before counting.
+    subdivide_reused(&mut cells, &segments, &mut scratch);
+    let fresh = allocations_during(|| {
+        black_box(subdivide_fresh(&mut cells, &segments));
+    });
+    let reused = allocations_during(|| {
+        black_box(subdivide_reused(&mut cells, &segments, &mut scratch));
+    });
+    println!(
+        "subdivide {} cells: {fresh} allocations with fresh buffers, {reused} with scratch",
+        cells.len()
+    );
+
+    let mut group = c.benchmark_group("subdivide_scratch");
+    group.bench_function("fresh", |b| {
+        b.iter(|| subdivide_fresh(black_box(&mut cells), &segments))
+    });
+    group.bench_function("scratch", |b| {
+        b.iter(|| subdivide_reused(black_box(&mut cells), &segments, &mut scratch))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, subdivide_scratch);
+criterion_main!(benches);
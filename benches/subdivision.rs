@@ -0,0 +1,62 @@
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::gpu::init::adapter_available;
+use baby_parallel_vector_graphics::gpu::quad_tree::{build_quadtree, build_quadtree_cancellable};
+use baby_parallel_vector_graphics::quad_tree::QuadTree;
+use baby_parallel_vector_graphics::seg_entry::init_root_seg_entries;
+use baby_parallel_vector_graphics::svg_parser::{
+    parse_svg, FlattenOptions, SAMPLE_SVG_PATH,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+const DEPTHS: [u8; 4] = [2, 4, 6, 8];
+
+/// CPU vs GPU quadtree subdivision of the sample SVG at several depths.
+/// The GPU arm is skipped when no adapter is available.
+fn subdivision(c: &mut Criterion) {
+    let parsed = parse_svg(Path::new(SAMPLE_SVG_PATH), &FlattenOptions::default()).unwrap();
+    let root = Rect::from_ltrb(0.0, 0.0, parsed.width as f32, parsed.height as f32).unwrap();
+    let has_gpu = pollster::block_on(adapter_available());
+    if !has_gpu {
+        eprintln!("no GPU adapter found; skipping GPU subdivision benchmarks");
+    }
+
+    let mut group = c.benchmark_group("subdivision");
+    for depth in DEPTHS {
+        group.bench_with_input(BenchmarkId::new("cpu", depth), &depth, |b, &depth| {
+            b.iter(|| QuadTree::new(black_box(&parsed.abs_segments), &parsed.abs_paths, root, depth, 1).unwrap())
+        });
+        if has_gpu {
+            // Includes device setup and readback, as the renderer pays both per build.
+            group.bench_with_input(BenchmarkId::new("gpu", depth), &depth, |b, &depth| {
+                b.iter(|| {
+                    let root_entries = init_root_seg_entries(&parsed.abs_segments);
+                    build_quadtree(root, root_entries, depth, 1, black_box(&parsed.abs_segments))
+                        .unwrap()
+                })
+            });
+            // Same build with a submission and entry-count readback per level.
+            group.bench_with_input(BenchmarkId::new("gpu-readback", depth), &depth, |b, &depth| {
+                b.iter(|| {
+                    let root_entries = init_root_seg_entries(&parsed.abs_segments);
+                    build_quadtree_cancellable(
+                        root,
+                        root_entries,
+                        depth,
+                        1,
+                        black_box(&parsed.abs_segments),
+                        &AtomicBool::new(false),
+                        |_| {},
+                    )
+                    .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, subdivision);
+criterion_main!(benches);
@@ -4,6 +4,15 @@ use usvg::tiny_skia_path::Point;
 
 const EPS: f32 = 1e-6;
 
+/// `AbstractLineSegment::hull` values: which bbox diagonal the segment runs along.
+/// Axis-aligned and degenerate segments have no hull and always use the implicit
+/// equation.
+const HULL_NONE: u32 = 0;
+/// Top-left to bottom-right.
+const HULL_MAIN_DIAGONAL: u32 = 1;
+/// Top-right to bottom-left.
+const HULL_ANTI_DIAGONAL: u32 = 2;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Direction {
     NW,
@@ -11,6 +20,8 @@ pub enum Direction {
     SW,
     SE,
     Horizontal,
+    /// Zero-length or non-finite segment; it never contributes winding.
+    None,
 }
 
 impl Direction {
@@ -21,6 +32,7 @@ impl Direction {
             2 => Direction::SW,
             3 => Direction::SE,
             4 => Direction::Horizontal,
+            5 => Direction::None,
             _ => {
                 panic!("Invalid integer passed")
             }
@@ -33,6 +45,7 @@ impl Direction {
             Direction::SW => 2,
             Direction::SE => 3,
             Direction::Horizontal => 4,
+            Direction::None => 5,
         }
     }
 }
@@ -72,6 +85,27 @@ impl SegType {
             SegType::LastStack => 12,
         }
     }
+
+    pub fn from_u32(int: u32) -> Self {
+        match int {
+            0 => SegType::Point,
+            1 => SegType::Linear,
+            2 => SegType::Quadratic,
+            3 => SegType::Cubic,
+            4 => SegType::Arc,
+            5 => SegType::Path,
+            6 => SegType::LastGeom,
+            7 => SegType::FirstStack,
+            8 => SegType::Push,
+            9 => SegType::PopFill,
+            10 => SegType::PopClip,
+            11 => SegType::Commit,
+            12 => SegType::LastStack,
+            _ => {
+                panic!("Invalid integer passed")
+            }
+        }
+    }
 }
 
 impl Direction {
@@ -102,17 +136,29 @@ pub struct AbstractLineSegment {
     pub y0: f32,
     pub x1: f32,
     pub y1: f32,
+    /// `dx/dy`, the x step per unit of y when walking scanlines; 0 for horizontal segments.
+    pub inv_slope: f32,
+    /// Cached `HULL_*` classification used by `hit_chull`.
+    hull: u32,
+    pub _pad1: [u32; 2],
 }
 
 impl AbstractLineSegment {
+    /// A zero-length or non-finite segment is tagged `SegType::Point`, whatever
+    /// `seg_type` says: its line equation is zeroed so it is never left of a sample,
+    /// and it never contributes winding.
     pub fn new(p0: Point, p1: Point, seg_type: SegType, path_id: u32) -> Self {
         let dir = Self::direction_svg(p1.x - p0.x, p1.y - p0.y);
         let bounding_box = Self::line_bbox(&p0, &p1);
+        let is_point = dir == Direction::None;
+        let seg_type = if is_point { SegType::Point } else { seg_type };
         let mut a = p0.y - p1.y;
         let mut b = p1.x - p0.x;
         let mut c = p0.x * p1.y - p1.x * p0.y;
 
-        if a < 0.0 || (a == 0.0 && b < 0.0) {
+        if is_point {
+            (a, b, c) = (0.0, 0.0, 0.0);
+        } else if a < 0.0 || (a == 0.0 && b < 0.0) {
             a = -a;
             b = -b;
             c = -c;
@@ -131,6 +177,9 @@ impl AbstractLineSegment {
             y0: p0.y,
             x1: p1.x,
             y1: p1.y,
+            inv_slope: Self::inv_slope(&p0, &p1),
+            hull: Self::hull(&p0, &p1),
+            _pad1: [0; 2],
         }
     }
 
@@ -144,6 +193,18 @@ impl AbstractLineSegment {
         self.eval(x, y) < 0.
     }
 
+    /// Whether the segment was degenerate, see [`Self::new`].
+    pub fn is_point(&self) -> bool {
+        self.seg_type == SegType::Point.to_u32()
+    }
+
+    /// Whether a horizontal ray can cross the segment. Horizontal and degenerate
+    /// segments are left out of the crossing tests rather than relying on their
+    /// empty or near-empty `[top, bottom)` range.
+    pub fn crosses_rows(&self) -> bool {
+        Direction::from_u32(self.direction).to_winding_inc() != 0
+    }
+
     pub fn going_right(&self) -> bool {
         let dir = Direction::from_u32(self.direction);
         match dir {
@@ -152,6 +213,7 @@ impl AbstractLineSegment {
             Direction::SW => false,
             Direction::SE => true,
             Direction::Horizontal => true,
+            Direction::None => false,
         }
     }
 
@@ -163,19 +225,62 @@ impl AbstractLineSegment {
             Direction::SW => false,
             Direction::SE => false,
             Direction::Horizontal => false,
+            Direction::None => false,
         }
     }
 
+    /// Classify a sample inside the bbox without the implicit equation where the
+    /// bbox alone decides: the segment is the bbox diagonal, so of the four quadrants
+    /// around the bbox centre, the two it does not pass through lie wholly on one
+    /// side. Returns 1 left of the segment (where `eval` is negative), 0 right of
+    /// it, and -1 in the other two quadrants, which need `eval`.
     pub fn hit_chull(&self, pt: &Point) -> i32 {
-        -1
+        let [left, top, right, bottom] = self.bbox_ltrb;
+        let cx = (left + right) * 0.5;
+        let cy = (top + bottom) * 0.5;
+        match self.hull {
+            HULL_MAIN_DIAGONAL if pt.x < cx && pt.y > cy => 1,
+            HULL_MAIN_DIAGONAL if pt.x > cx && pt.y < cy => 0,
+            HULL_ANTI_DIAGONAL if pt.x < cx && pt.y < cy => 1,
+            HULL_ANTI_DIAGONAL if pt.x > cx && pt.y > cy => 0,
+            _ => -1,
+        }
+    }
+
+    fn hull(p0: &Point, p1: &Point) -> u32 {
+        let (dx, dy) = (p1.x - p0.x, p1.y - p0.y);
+        if dx.abs() < EPS || dy.abs() < EPS || !(dx.is_finite() && dy.is_finite()) {
+            HULL_NONE
+        } else if (dx > 0.0) == (dy > 0.0) {
+            HULL_MAIN_DIAGONAL
+        } else {
+            HULL_ANTI_DIAGONAL
+        }
     }
 
     /// Returns the x coordinate on the line at the given y.
-    fn x_at_y(&self, y0: f32) -> Option<f32> {
+    pub fn x_at_scanline(&self, y: f32) -> Option<f32> {
         if self.a.abs() < EPS {
             return None;
         }
-        Some(-(self.b * y0 + self.c) / self.a)
+        Some(-(self.b * y + self.c) / self.a)
+    }
+
+    /// x on the line at `y_start`, `y_start + 1`, ... stepped incrementally with the
+    /// cached `inv_slope`; empty for horizontal segments.
+    pub fn scanline_xs(&self, y_start: f32) -> impl Iterator<Item = f32> + use<> {
+        let step = self.inv_slope;
+        self.x_at_scanline(y_start).into_iter().flat_map(move |x0| {
+            std::iter::successors(Some(x0), move |x| Some(x + step))
+        })
+    }
+
+    fn inv_slope(p0: &Point, p1: &Point) -> f32 {
+        let dy = p1.y - p0.y;
+        if dy.abs() < EPS {
+            return 0.0;
+        }
+        (p1.x - p0.x) / dy
     }
 
     /// Returns true if the segment crosses any edge of `bb`.
@@ -208,6 +313,10 @@ impl AbstractLineSegment {
     }
 
     fn direction_svg(dx: f32, dy: f32) -> Direction {
+        // Also catches NaN, for which every comparison below would be false.
+        if !(dx.is_finite() && dy.is_finite()) || (dx.abs() < EPS && dy.abs() < EPS) {
+            return Direction::None;
+        }
         if dy.abs() < EPS {
             return Direction::Horizontal;
         }
@@ -220,7 +329,11 @@ impl AbstractLineSegment {
         }
     }
 
+    /// Empty box at the origin when an endpoint is not finite.
     fn line_bbox(a: &Point, b: &Point) -> Rect {
+        if ![a.x, a.y, b.x, b.y].iter().all(|v| v.is_finite()) {
+            return Rect::from_ltrb(0.0, 0.0, 0.0, 0.0).unwrap();
+        }
         let left = a.x.min(b.x);
         let right = a.x.max(b.x);
         let top = a.y.min(b.y);
@@ -257,6 +370,80 @@ mod tests {
     use super::*;
     static PATH_ID: u32 = 0;
 
+    #[test]
+    fn hull_classification_agrees_with_implicit_eval() {
+        let ends = [(0.0, 0.0), (10.0, 3.0), (2.5, 9.0), (-4.0, 7.0), (6.0, -5.0)];
+        let mut classified = 0;
+        for &(x0, y0) in &ends {
+            for &(x1, y1) in &ends {
+                let seg = AbstractLineSegment::new(
+                    Point { x: x0, y: y0 },
+                    Point { x: x1, y: y1 },
+                    SegType::Linear,
+                    PATH_ID,
+                );
+                let [l, t, r, b] = seg.bbox_ltrb;
+                for i in 0..=20 {
+                    for j in 0..=20 {
+                        let pt = Point {
+                            x: l + (r - l) * i as f32 / 20.0,
+                            y: t + (b - t) * j as f32 / 20.0,
+                        };
+                        let hull = seg.hit_chull(&pt);
+                        if hull == -1 {
+                            continue;
+                        }
+                        classified += 1;
+                        assert_eq!(hull == 1, seg.is_left(pt.x, pt.y), "{seg:?} at {pt:?}");
+                    }
+                }
+            }
+        }
+        assert!(classified > 0);
+    }
+
+    #[test]
+    fn hit_chull_left_right_and_boundary() {
+        // (0, 0) -> (10, 10): bbox centre (5, 5), hull along the main diagonal.
+        let seg = AbstractLineSegment::new(
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 10.0, y: 10.0 },
+            SegType::Linear,
+            PATH_ID,
+        );
+        let at = |x, y| seg.hit_chull(&Point { x, y });
+        assert_eq!(at(1.0, 9.0), 1);
+        assert_eq!(at(9.0, 1.0), 0);
+        // On the segment, in a quadrant it passes through, or on a quadrant boundary.
+        assert_eq!(at(5.0, 5.0), -1);
+        assert_eq!(at(2.0, 3.0), -1);
+        assert_eq!(at(5.0, 9.0), -1);
+        assert_eq!(at(1.0, 5.0), -1);
+
+        // The same points against the reversed anti-diagonal (10, 0) -> (0, 10).
+        let seg = AbstractLineSegment::new(
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 0.0, y: 10.0 },
+            SegType::Linear,
+            PATH_ID,
+        );
+        let at = |x, y| seg.hit_chull(&Point { x, y });
+        assert_eq!(at(1.0, 1.0), 1);
+        assert!(seg.is_left(1.0, 1.0));
+        assert_eq!(at(9.0, 9.0), 0);
+        assert!(!seg.is_left(9.0, 9.0));
+        assert_eq!(at(5.0, 5.0), -1);
+
+        // Axis-aligned segments have no hull to test against.
+        let seg = AbstractLineSegment::new(
+            Point { x: 3.0, y: 0.0 },
+            Point { x: 3.0, y: 10.0 },
+            SegType::Linear,
+            PATH_ID,
+        );
+        assert_eq!(seg.hit_chull(&Point { x: 3.0, y: 2.0 }), -1);
+    }
+
     #[test]
     fn direction_sw() {
         let a = Point { x: 1., y: 0. };
@@ -293,6 +480,103 @@ mod tests {
         assert_eq!(dir, Direction::NE);
     }
 
+    #[test]
+    fn direction_degenerate() {
+        let a = Point { x: 3., y: 4. };
+        let abs_seg = AbstractLineSegment::new(a, a, SegType::Linear, PATH_ID);
+        let dir = Direction::from_u32(abs_seg.direction);
+        assert_eq!(dir, Direction::None);
+        assert_eq!(dir.to_winding_inc(), 0);
+
+        let nan = Point { x: f32::NAN, y: 1. };
+        let abs_seg = AbstractLineSegment::new(a, nan, SegType::Linear, PATH_ID);
+        assert_eq!(Direction::from_u32(abs_seg.direction), Direction::None);
+    }
+
+    #[test]
+    fn degenerate_segments_are_points_with_no_crossings() {
+        let p = Point { x: 10.0, y: 10.0 };
+        let near = Point {
+            x: 10.0 + EPS / 4.0,
+            y: 10.0 - EPS / 4.0,
+        };
+        let nan = Point {
+            x: f32::NAN,
+            y: f32::NAN,
+        };
+        let inf = Point {
+            x: f32::INFINITY,
+            y: 10.0,
+        };
+        for (p0, p1) in [(p, p), (p, near), (nan, nan), (p, inf)] {
+            let seg = AbstractLineSegment::new(p0, p1, SegType::Cubic, PATH_ID);
+            assert!(seg.is_point(), "{p0:?} -> {p1:?}");
+            assert!(!seg.crosses_rows());
+            assert!(seg.bbox_ltrb.iter().all(|v| v.is_finite()));
+            for y in [9.0, 10.0 - EPS, 10.0, 10.5, 11.0] {
+                for x in [-5.0, 9.0, 10.0, 10.5, 20.0] {
+                    assert!(!seg.is_left(x, y), "({x}, {y}) against {p0:?} -> {p1:?}");
+                    let cell = Rect::from_ltrb(0.0, 0.0, 32.0, 32.0).unwrap();
+                    assert!(!seg.hit_shortcut(&cell, x, y));
+                }
+            }
+        }
+
+        let horizontal = AbstractLineSegment::new(
+            Point { x: 0.0, y: 5.0 },
+            Point { x: 8.0, y: 5.0 },
+            SegType::Linear,
+            PATH_ID,
+        );
+        assert!(!horizontal.is_point());
+        assert!(!horizontal.crosses_rows());
+    }
+
+    #[test]
+    fn zero_length_segment_adds_no_winding() {
+        use crate::geometry::rect::Rect;
+        use crate::quad_tree::QuadTree;
+        use crate::seg_entry::WINDING_INCREMENT;
+        use crate::test_utils::{TestScene, solid};
+
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let square = [(8.0, 8.0), (40.0, 8.0), (40.0, 40.0), (8.0, 40.0)];
+        let mut plain = TestScene::new();
+        plain.push_polygon(&square, solid([255, 0, 0, 255]));
+        // Same square with a repeated corner, i.e. one zero-length edge.
+        let mut degenerate = TestScene::new();
+        degenerate.push_polygon(
+            &[square[0], square[1], square[1], square[2], square[3]],
+            solid([255, 0, 0, 255]),
+        );
+        let zero_idx = degenerate
+            .abs_segments
+            .iter()
+            .position(|s| Direction::from_u32(s.direction) == Direction::None)
+            .unwrap();
+
+        let winding_of = |scene: &TestScene| {
+            let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+            let mut winding: Vec<(u32, i32)> = tree
+                .entries
+                .iter()
+                .filter(|e| e.entry_type & WINDING_INCREMENT != 0)
+                .map(|e| (e.cell_id, e.data))
+                .collect();
+            winding.sort();
+            (tree, winding)
+        };
+        let (_, plain_winding) = winding_of(&plain);
+        let (tree, degenerate_winding) = winding_of(&degenerate);
+        assert_eq!(degenerate_winding, plain_winding);
+        assert!(
+            tree.entries
+                .iter()
+                .filter(|e| e.seg_idx as usize == zero_idx && e.entry_type & WINDING_INCREMENT == 0)
+                .all(|e| e.data == 0)
+        );
+    }
+
     #[test]
     fn bounding_box() {
         let a = Point { x: 2., y: 0. };
@@ -375,4 +659,26 @@ mod tests {
         let bb = Rect::from_ltrb(50.0, 50.0, 100.0, 100.0).unwrap();
         assert!(!abs_seg.intersect_with_bb(&bb));
     }
+
+    #[test]
+    fn cached_slope_steps_match_x_at_scanline() {
+        let a = Point { x: 3.0, y: 2.0 };
+        let b = Point { x: 40.0, y: 90.0 };
+        let abs_seg = AbstractLineSegment::new(a, b, SegType::Linear, PATH_ID);
+        assert_eq!(abs_seg.inv_slope, 37.0 / 88.0);
+        for (i, x) in abs_seg.scanline_xs(2.5).take(88).enumerate() {
+            let y = 2.5 + i as f32;
+            let expected = abs_seg.x_at_scanline(y).unwrap();
+            assert!((x - expected).abs() < 1e-3, "y {y}: {x} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn horizontal_segment_has_no_scanline_steps() {
+        let a = Point { x: 0.0, y: 5.0 };
+        let b = Point { x: 10.0, y: 5.0 };
+        let abs_seg = AbstractLineSegment::new(a, b, SegType::Linear, PATH_ID);
+        assert_eq!(abs_seg.inv_slope, 0.0);
+        assert_eq!(abs_seg.scanline_xs(5.0).count(), 0);
+    }
 }
@@ -10,6 +10,11 @@ fn checked_f32_sub(a: f32, b: f32) -> Option<f32> {
     }
 }
 
+/// `v` as an `i32` if it is a whole number in range.
+pub(crate) fn exact_i32(v: f32) -> Option<i32> {
+    (v.fract() == 0.0 && v >= i32::MIN as f32 && v < i32::MAX as f32).then_some(v as i32)
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Rect {
     left: f32,
@@ -22,6 +27,22 @@ impl Rect {
     pub(crate) fn from_ltrb_slice(s: &[f32; 4]) -> Option<Self> {
         Rect::from_ltrb(s[0], s[1], s[2], s[3])
     }
+
+    /// Same edges as a `usvg::Rect`; `None` only if `from_ltrb` rejects them.
+    pub fn from_usvg(r: usvg::Rect) -> Option<Self> {
+        Rect::from_ltrb(r.left(), r.top(), r.right(), r.bottom())
+    }
+
+    pub fn to_usvg(&self) -> usvg::Rect {
+        usvg::Rect::from_ltrb(self.left, self.top, self.right, self.bottom)
+            .expect("Rect is validated the same way as usvg::Rect")
+    }
+}
+
+impl From<Rect> for usvg::Rect {
+    fn from(r: Rect) -> Self {
+        r.to_usvg()
+    }
 }
 
 impl Rect {
@@ -83,6 +104,23 @@ impl Rect {
         ]
     }
 
+    /// Edges as integers when all four lie on whole numbers representable as `i32`.
+    pub fn to_integer_ltrb(&self) -> Option<[i32; 4]> {
+        Some([
+            exact_i32(self.left)?,
+            exact_i32(self.top)?,
+            exact_i32(self.right)?,
+            exact_i32(self.bottom)?,
+        ])
+    }
+
+    /// Overlap of the two rects, boundaries included: rects that only touch along an
+    /// edge or at a corner give a zero-width and/or zero-height rect.
+    ///
+    /// Use this where the rects themselves may have no area, e.g. the bbox of an
+    /// axis-aligned segment in `AbstractLineSegment::intersect_with_bb`, or to test
+    /// containment as `a.intersect(&b) == Some(a)`. To skip things that merely
+    /// graze a cell, use [`Self::intersect_strict`].
     pub fn intersect(&self, other: &Self) -> Option<Self> {
         let left = self.x().max(other.x());
         let top = self.y().max(other.y());
@@ -92,4 +130,123 @@ impl Rect {
 
         Rect::from_ltrb(left, top, right, bottom)
     }
+
+    /// Like [`Self::intersect`], but `None` unless the overlap has a positive area, so
+    /// rects touching only along an edge or at a corner do not intersect. Also `None`
+    /// whenever either rect has no area itself.
+    pub fn intersect_strict(&self, other: &Self) -> Option<Self> {
+        self.intersect(other)
+            .filter(|r| r.left < r.right && r.top < r.bottom)
+    }
+
+    /// Whether `(x, y)` is inside, half-open like pixel sampling: the left and top
+    /// edges are included, the right and bottom edges are not.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.left <= x && x < self.right && self.top <= y && y < self.bottom
+    }
+
+    /// Smallest rect covering both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Rect {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// `self` grown by `pad` on every side; `None` if that no longer fits in f32
+    /// or a negative `pad` shrinks it past empty.
+    pub fn outset(&self, pad: f32) -> Option<Self> {
+        Rect::from_ltrb(
+            self.left - pad,
+            self.top - pad,
+            self.right + pad,
+            self.bottom + pad,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains_includes_top_left_edges_only() {
+        let rect = Rect::from_ltrb(2.0, 4.0, 10.0, 8.0).unwrap();
+        assert!(rect.contains(2.0, 4.0));
+        assert!(rect.contains(9.5, 7.5));
+        assert!(rect.contains(2.0, 7.9));
+        assert!(!rect.contains(10.0, 5.0));
+        assert!(!rect.contains(5.0, 8.0));
+        assert!(!rect.contains(1.9, 5.0));
+        assert!(!rect.contains(5.0, 3.9));
+    }
+
+    #[test]
+    fn edge_touching_rects_intersect_only_non_strictly() {
+        let a = Rect::from_ltrb(0.0, 0.0, 4.0, 4.0).unwrap();
+        let right = Rect::from_ltrb(4.0, 1.0, 8.0, 3.0).unwrap();
+        let below = Rect::from_ltrb(-2.0, 4.0, 2.0, 6.0).unwrap();
+        assert_eq!(a.intersect(&right).unwrap().to_ltrb(), [4.0, 1.0, 4.0, 3.0]);
+        assert_eq!(a.intersect(&below).unwrap().to_ltrb(), [0.0, 4.0, 2.0, 4.0]);
+        assert_eq!(a.intersect_strict(&right), None);
+        assert_eq!(a.intersect_strict(&below), None);
+        assert_eq!(right.intersect_strict(&a), None);
+    }
+
+    #[test]
+    fn corner_touching_rects_intersect_only_non_strictly() {
+        let a = Rect::from_ltrb(0.0, 0.0, 4.0, 4.0).unwrap();
+        let corner = Rect::from_ltrb(4.0, 4.0, 6.0, 6.0).unwrap();
+        assert_eq!(
+            a.intersect(&corner).unwrap().to_ltrb(),
+            [4.0, 4.0, 4.0, 4.0]
+        );
+        assert_eq!(a.intersect_strict(&corner), None);
+
+        let overlapping = Rect::from_ltrb(3.0, 3.0, 6.0, 6.0).unwrap();
+        assert_eq!(
+            a.intersect_strict(&overlapping),
+            Some(Rect::from_ltrb(3.0, 3.0, 4.0, 4.0).unwrap())
+        );
+        let apart = Rect::from_ltrb(5.0, 0.0, 6.0, 4.0).unwrap();
+        assert_eq!(a.intersect(&apart), None);
+        assert_eq!(a.intersect_strict(&apart), None);
+        // A zero-width rect inside `a`, like a vertical segment's bbox.
+        let line = Rect::from_ltrb(2.0, 1.0, 2.0, 3.0).unwrap();
+        assert_eq!(a.intersect(&line), Some(line));
+        assert_eq!(a.intersect_strict(&line), None);
+    }
+
+    #[test]
+    fn usvg_conversions_round_trip() {
+        let rect = Rect::from_ltrb(-3.5, 2.0, 10.25, 8.0).unwrap();
+        let usvg_rect: usvg::Rect = rect.into();
+        assert_eq!(
+            [usvg_rect.left(), usvg_rect.top(), usvg_rect.right(), usvg_rect.bottom()],
+            rect.to_ltrb()
+        );
+        assert_eq!(Rect::from_usvg(usvg_rect), Some(rect));
+
+        let usvg_rect = usvg::Rect::from_xywh(1.0, 2.0, 0.0, 4.0).unwrap();
+        assert_eq!(Rect::from_usvg(usvg_rect).unwrap().to_usvg(), usvg_rect);
+    }
+
+    #[test]
+    fn union_of_disjoint_rects_spans_the_gap() {
+        let a = Rect::from_ltrb(0.0, 0.0, 2.0, 3.0).unwrap();
+        let b = Rect::from_ltrb(10.0, -5.0, 12.0, -1.0).unwrap();
+        assert_eq!(a.union(&b).to_ltrb(), [0.0, -5.0, 12.0, 3.0]);
+        assert_eq!(b.union(&a), a.union(&b));
+        assert_eq!(a.union(&a), a);
+    }
+
+    #[test]
+    fn outset_grows_every_side() {
+        let rect = Rect::from_ltrb(2.0, 4.0, 10.0, 8.0).unwrap();
+        assert_eq!(rect.outset(1.5).unwrap().to_ltrb(), [0.5, 2.5, 11.5, 9.5]);
+        assert_eq!(rect.outset(-2.0).unwrap().to_ltrb(), [4.0, 6.0, 8.0, 6.0]);
+        assert!(rect.outset(-3.0).is_none());
+    }
 }
@@ -1,6 +1,7 @@
 // Depends on: common.wgsl, split_helpers.wgsl
 
-const WG_SIZE: u32 = 2u;
+// Must match WG_SIZE in subdivide_seg_entry.rs.
+const WG_SIZE: u32 = 256u;
 
 // Grid layout for edge-crossing classification used in subdivision.
 //
@@ -47,6 +48,7 @@ fn going_right(direction: u32) -> bool {
         case 2: { return false; } // SW
         case 3: { return true; }  // SE
         case 4: { return true; }  // Horizontal
+        case 5: { return false; } // None (degenerate)
         default: { return false; }
     }
 }
@@ -58,6 +60,7 @@ fn going_up(direction: u32) -> bool {
         case 2: { return false; } // SW
         case 3: { return false; } // SE
         case 4: { return false; } // Horizontal
+        case 5: { return false; } // None (degenerate)
         default: { return false; }
     }
 }
@@ -83,8 +86,30 @@ fn classify_child(x: f32, y: f32, mid_x: f32, mid_y: f32) -> u32 {
     return BOTTOM_RIGHT;
 }
 
-// Convex hull test — not yet implemented for line segments; always falls through.
-fn hit_chull() -> i32 {
+// AbstractLineSegment.hull values; mirror HULL_* in abstract_segment.rs.
+const HULL_MAIN_DIAGONAL: u32 = 1u;
+const HULL_ANTI_DIAGONAL: u32 = 2u;
+
+// Mirrors AbstractLineSegment::hit_chull: 1 left of the segment, 0 right of it, -1
+// in the bbox quadrants the segment passes through.
+fn hit_chull(seg: AbstractLineSegment, x: f32, y: f32) -> i32 {
+    let cx = (seg.bbox_ltrb[0] + seg.bbox_ltrb[2]) * 0.5;
+    let cy = (seg.bbox_ltrb[1] + seg.bbox_ltrb[3]) * 0.5;
+    if seg.hull == HULL_MAIN_DIAGONAL {
+        if x < cx && y > cy {
+            return 1;
+        }
+        if x > cx && y < cy {
+            return 0;
+        }
+    } else if seg.hull == HULL_ANTI_DIAGONAL {
+        if x < cx && y < cy {
+            return 1;
+        }
+        if x > cx && y > cy {
+            return 0;
+        }
+    }
     return -1;
 }
 
@@ -93,6 +118,10 @@ fn eval(a: f32, b: f32, c: f32, x: f32, y: f32) -> f32 {
 }
 
 fn half_open_eval(seg: AbstractLineSegment, sample_x: f32, sample_y: f32) -> i32 {
+    // A degenerate (direction 5) segment has no side, so no cell edge can cross it.
+    if seg.direction == 5u {
+        return 0;
+    }
     let left = seg.bbox_ltrb[0];
     let top = seg.bbox_ltrb[1];
     let right = seg.bbox_ltrb[2];
@@ -121,7 +150,7 @@ fn half_open_eval(seg: AbstractLineSegment, sample_x: f32, sample_y: f32) -> i32
     }
 
     // Inside bbox: try convex hull, then fall back to implicit evaluation.
-    let check = hit_chull();
+    let check = hit_chull(seg, sample_x, sample_y);
     if check != -1 {
         return select(1, -1, check == 1);
     }
@@ -188,7 +217,8 @@ fn build_split_data(
     var split_info = 0u;
     var winding = vec4(0, 0, 0, 0);
 
-    // Endpoints inside the parent quad mark their child cell as occupied.
+    // Endpoints inside the parent quad mark their child cell as occupied; one on a
+    // midline counts for the top/left child, as in `SplitData::new`.
     if (contains_point_in_bbox(seg.x0, seg.y0, bbox_ltrb)) {
         split_info |= fill(classify_child(seg.x0, seg.y0, mid_x, mid_y));
     }
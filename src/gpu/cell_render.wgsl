@@ -2,15 +2,37 @@
 
 const EPS: f32 = 1e-6;
 
+const PAINT_SOLID: u32 = 0u;
+const PAINT_LINEAR_GRADIENT: u32 = 1u;
+const PAINT_RADIAL_GRADIENT: u32 = 2u;
+
+const FILL_RULE_NON_ZERO: u32 = 0u;
+const FILL_RULE_EVEN_ODD: u32 = 1u;
+
 struct PathPaintGpu {
     rgba: vec4<f32>,
+    kind: u32,
+    stop_start: u32,
+    stop_count: u32,
+    fill_rule: u32,
+    // Linear: (x1, y1, x2, y2). Radial: (cx, cy, r, unused).
+    geometry: vec4<f32>,
+    // Rows of the inverse gradient transform: (sx, kx, tx, _), (ky, sy, ty, _).
+    inv_row0: vec4<f32>,
+    inv_row1: vec4<f32>,
+}
+
+struct GradientStopGpu {
+    rgba: vec4<f32>,
+    offset: f32,
+    _pad: array<f32, 3>,
 }
 
 struct RenderParams {
     width: u32,
     height: u32,
     entries_len: u32,
-    _pad: u32,
+    premultiply: u32,
 }
 
 @group(0) @binding(0) var<storage, read> cell_metadata: array<CellMetadata>;
@@ -19,6 +41,7 @@ struct RenderParams {
 @group(0) @binding(3) var<storage, read> path_paints: array<PathPaintGpu>;
 @group(0) @binding(4) var<uniform> params: RenderParams;
 @group(0) @binding(5) var output_tex: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(6) var<storage, read> gradient_stops: array<GradientStopGpu>;
 
 fn contains_point(cell_meta: CellMetadata, px: u32, py: u32) -> bool {
     // Match CPU rasterization range conversion:
@@ -38,6 +61,11 @@ fn seg_is_left(seg: AbstractLineSegment, x: f32, y: f32) -> bool {
     return seg_eval(seg, x, y) < 0.0;
 }
 
+// Horizontal (4) and degenerate (5) segments never cross a sample row.
+fn seg_crosses_rows(seg: AbstractLineSegment) -> bool {
+    return seg.direction < 4u;
+}
+
 fn hit_shortcut(seg: AbstractLineSegment, cell_bbox_ltrb: vec4<f32>, sample_x: f32, sample_y: f32) -> bool {
     if (abs(seg.b) < EPS) {
         return false;
@@ -50,8 +78,73 @@ fn hit_shortcut(seg: AbstractLineSegment, cell_bbox_ltrb: vec4<f32>, sample_x: f
     return sample_x < x0;
 }
 
+// Mirrors `AbstractPath::fills` in path.rs.
+fn path_fills(fill_rule: u32, winding: i32) -> bool {
+    if (fill_rule == FILL_RULE_NON_ZERO) {
+        return winding != 0;
+    }
+    return (winding & 1) != 0;
+}
+
+// Mirrors `sample_stops` in path.rs.
+fn sample_stops(start: u32, count: u32, t: f32) -> vec4<f32> {
+    if (count == 0u) {
+        return vec4<f32>(0.0);
+    }
+    let tc = clamp(t, 0.0, 1.0);
+    var prev = gradient_stops[start];
+    if (tc <= prev.offset) {
+        return prev.rgba;
+    }
+    for (var i = 1u; i < count; i++) {
+        let next = gradient_stops[start + i];
+        if (tc <= next.offset) {
+            let f = (tc - prev.offset) / (next.offset - prev.offset);
+            return mix(prev.rgba, next.rgba, f);
+        }
+        prev = next;
+    }
+    return prev.rgba;
+}
+
+fn sample_paint(paint: PathPaintGpu, x: f32, y: f32) -> vec4<f32> {
+    if (paint.kind == PAINT_SOLID) {
+        return paint.rgba;
+    }
+    let p = vec3<f32>(x, y, 1.0);
+    let g = vec2<f32>(dot(paint.inv_row0.xyz, p), dot(paint.inv_row1.xyz, p));
+    var t = 0.0;
+    if (paint.kind == PAINT_LINEAR_GRADIENT) {
+        let start = paint.geometry.xy;
+        let d = paint.geometry.zw - start;
+        let len_sq = dot(d, d);
+        if (len_sq > 0.0) {
+            t = dot(g - start, d) / len_sq;
+        }
+    } else if (paint.kind == PAINT_RADIAL_GRADIENT) {
+        let radius = paint.geometry.z;
+        if (radius > 0.0) {
+            t = length(g - paint.geometry.xy) / radius;
+        }
+    }
+    return sample_stops(paint.stop_start, paint.stop_count, t);
+}
+
+fn store_output(px: u32, py: u32, rgba: vec4<f32>) {
+    var color = rgba;
+    if (params.premultiply != 0u) {
+        color = vec4<f32>(color.rgb * color.a, color.a);
+    }
+    textureStore(output_tex, vec2<i32>(i32(px), i32(py)), color);
+}
+
+// Tile of pixels per workgroup in `main` and `clear_output`; `ComputeRenderer`
+// sets them for the device and dispatches `ceil(size / tile)` workgroups.
+override RENDER_WG_SIZE_X: u32 = 8u;
+override RENDER_WG_SIZE_Y: u32 = 8u;
+
 @compute
-@workgroup_size(8, 8, 1)
+@workgroup_size(RENDER_WG_SIZE_X, RENDER_WG_SIZE_Y, 1)
 fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (gid.x >= params.width || gid.y >= params.height) {
         return;
@@ -100,8 +193,8 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
                 let seg = segments[entry.seg_idx];
                 let top = seg.bbox_ltrb[1];
                 let bottom = seg.bbox_ltrb[3];
-                if (seg_is_left(seg, x, y) && y >= top && y < bottom) {
-                    count += 1;
+                if (seg_crosses_rows(seg) && seg_is_left(seg, x, y) && y >= top && y < bottom) {
+                    count += select(-1, 1, seg.y0 > seg.y1);
                 }
                 if (entry.data != 0 && hit_shortcut(seg, cell_meta.bbox_ltrb, x, y)) {
                     count += entry.data;
@@ -117,9 +210,11 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
                 last_entry_in_path = seg_entries[entry_idx + 1u].path_idx != entry.path_idx;
             }
             if (last_entry_in_path) {
-                if ((count & 1) != 0 && path_paint_len > 0u) {
-                    let paint_idx = min(entry.path_idx, path_paint_len - 1u);
-                    cell_color = path_paints[paint_idx].rgba;
+                if (path_paint_len > 0u) {
+                    let paint = path_paints[min(entry.path_idx, path_paint_len - 1u)];
+                    if (path_fills(paint.fill_rule, count)) {
+                        cell_color = sample_paint(paint, x, y);
+                    }
                 }
                 count = 0;
             }
@@ -131,5 +226,129 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
         i = i + 1u;
     }
 
-    textureStore(output_tex, vec2<i32>(i32(gid.x), i32(gid.y)), color);
+    store_output(gid.x, gid.y, color);
+}
+
+// Alternate pipeline: one workgroup per leaf cell, covering the cell's pixels in
+// 8x8 tiles while the cell's entries are staged through workgroup memory.
+const CELL_TILE: u32 = 8u;
+const CELL_CHUNK: u32 = 64u;
+
+var<workgroup> chunk_entries: array<SegEntry, CELL_CHUNK>;
+var<workgroup> chunk_segments: array<AbstractLineSegment, CELL_CHUNK>;
+
+// Fill the whole output with the background `main` leaves outside the leaf cells;
+// `render_cells` only writes the pixels of cells with entries.
+@compute
+@workgroup_size(RENDER_WG_SIZE_X, RENDER_WG_SIZE_Y, 1)
+fn clear_output(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= params.width || gid.y >= params.height) {
+        return;
+    }
+    store_output(gid.x, gid.y, vec4<f32>(0.0, 0.0, 0.0, 1.0));
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn render_cells(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) num_wg: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let cell_idx = linearize_workgroup_id(wid, num_wg);
+    if (cell_idx >= arrayLength(&cell_metadata)) {
+        return;
+    }
+    let cell_meta = cell_metadata[cell_idx];
+    // Same range checks as `main`: a cell failing them is not drawn.
+    if (cell_meta.entry_count == 0u || cell_meta.entry_start >= params.entries_len) {
+        return;
+    }
+    if (cell_meta.entry_count > params.entries_len - cell_meta.entry_start) {
+        return;
+    }
+
+    // Pixel range as in `contains_point`, clipped to the output.
+    let l = u32(cell_meta.bbox_ltrb[0]);
+    let t = u32(cell_meta.bbox_ltrb[1]);
+    let r = min(u32(cell_meta.bbox_ltrb[2]), params.width);
+    let b = min(u32(cell_meta.bbox_ltrb[3]), params.height);
+    if (l >= r || t >= b) {
+        return;
+    }
+    let tiles_x = (r - l + CELL_TILE - 1u) / CELL_TILE;
+    let tiles_y = (b - t + CELL_TILE - 1u) / CELL_TILE;
+    let start = cell_meta.entry_start;
+    let end = start + cell_meta.entry_count;
+    let path_paint_len = arrayLength(&path_paints);
+
+    for (var tile = 0u; tile < tiles_x * tiles_y; tile++) {
+        let px = l + (tile % tiles_x) * CELL_TILE + lid % CELL_TILE;
+        let py = t + (tile / tiles_x) * CELL_TILE + lid / CELL_TILE;
+        let in_cell = px < r && py < b;
+        let x = f32(px);
+        let y = f32(py);
+        var count = 0;
+        var cell_color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+
+        for (var chunk_start = start; chunk_start < end; chunk_start += CELL_CHUNK) {
+            let chunk_len = min(CELL_CHUNK, end - chunk_start);
+            // Everyone is done with the previous chunk before it is overwritten.
+            workgroupBarrier();
+            if (lid < chunk_len) {
+                let entry = seg_entries[chunk_start + lid];
+                chunk_entries[lid] = entry;
+                if ((entry.entry_type & ABSTRACT) != 0u) {
+                    chunk_segments[lid] = segments[entry.seg_idx];
+                }
+            }
+            workgroupBarrier();
+            if (!in_cell) {
+                continue;
+            }
+
+            for (var k = 0u; k < chunk_len; k++) {
+                let entry = chunk_entries[k];
+                if ((entry.entry_type & ABSTRACT) != 0u) {
+                    let seg = chunk_segments[k];
+                    let top = seg.bbox_ltrb[1];
+                    let bottom = seg.bbox_ltrb[3];
+                    if (seg_crosses_rows(seg) && seg_is_left(seg, x, y) && y >= top && y < bottom) {
+                        count += select(-1, 1, seg.y0 > seg.y1);
+                    }
+                    if (entry.data != 0 && hit_shortcut(seg, cell_meta.bbox_ltrb, x, y)) {
+                        count += entry.data;
+                    }
+                }
+                if ((entry.entry_type & WINDING_INCREMENT) != 0u) {
+                    count += entry.data;
+                }
+
+                let entry_idx = chunk_start + k;
+                var last_entry_in_path = entry_idx + 1u >= end;
+                if (!last_entry_in_path) {
+                    var next_path_idx: u32;
+                    if (k + 1u < chunk_len) {
+                        next_path_idx = chunk_entries[k + 1u].path_idx;
+                    } else {
+                        next_path_idx = seg_entries[entry_idx + 1u].path_idx;
+                    }
+                    last_entry_in_path = next_path_idx != entry.path_idx;
+                }
+                if (last_entry_in_path) {
+                    if (path_paint_len > 0u) {
+                        let paint = path_paints[min(entry.path_idx, path_paint_len - 1u)];
+                        if (path_fills(paint.fill_rule, count)) {
+                            cell_color = sample_paint(paint, x, y);
+                        }
+                    }
+                    count = 0;
+                }
+            }
+        }
+
+        if (in_cell) {
+            store_output(px, py, cell_color);
+        }
+    }
 }
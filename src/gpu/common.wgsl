@@ -29,6 +29,9 @@ struct AbstractLineSegment {
     y0: f32,
     x1: f32,
     y1: f32,
+    inv_slope: f32, // dx/dy; 0 for horizontal segments
+    hull: u32,      // HULL_* classification for hit_chull
+    _pad1: array<u32, 2>,
 }
 
 struct SegEntry {
@@ -67,7 +70,8 @@ struct WindingBlockInfo {
 struct SplitResultInfo {
     seg_entries_length: u32,
     min_seg: u32,
-    _pad: vec2<u32>,
+    emitted_entries_length: u32,
+    _pad: u32,
 }
 
 struct ScanParams {
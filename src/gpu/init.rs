@@ -1,13 +1,29 @@
-pub async fn init_wgpu() -> (wgpu::Device, wgpu::Queue) {
+const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::SUBGROUP;
+
+/// Requested as well when the adapter has them.
+const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY;
+
+async fn request_compute_adapter() -> Result<wgpu::Adapter, wgpu::RequestAdapterError> {
     let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
-    let adapter = instance
+    instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::HighPerformance,
             compatible_surface: None,
             force_fallback_adapter: false,
         })
         .await
-        .expect("No adapter found");
+}
+
+/// Whether `init_wgpu` can find an adapter with the features it needs; lets callers
+/// skip GPU work instead of panicking.
+pub async fn adapter_available() -> bool {
+    request_compute_adapter()
+        .await
+        .is_ok_and(|adapter| adapter.features().contains(REQUIRED_FEATURES))
+}
+
+pub async fn init_wgpu() -> (wgpu::Device, wgpu::Queue) {
+    let adapter = request_compute_adapter().await.expect("No adapter found");
 
     let mut limits = wgpu::Limits::default();
     limits.max_buffer_size = adapter.limits().max_buffer_size;
@@ -16,7 +32,7 @@ pub async fn init_wgpu() -> (wgpu::Device, wgpu::Queue) {
     let (device, queue) = adapter
         .request_device(&wgpu::DeviceDescriptor {
             label: Some("device"),
-            required_features: wgpu::Features::SUBGROUP,
+            required_features: REQUIRED_FEATURES | (adapter.features() & OPTIONAL_FEATURES),
             required_limits: limits,
             experimental_features: Default::default(),
             memory_hints: Default::default(),
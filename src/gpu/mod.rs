@@ -1,4 +1,6 @@
 pub mod init;
+#[cfg(feature = "gpu-tests")]
+pub mod parity;
 pub mod quad_tree;
 pub mod render;
 pub mod shader_loader;
@@ -0,0 +1,193 @@
+use crate::abstract_segment::AbstractLineSegment;
+use crate::geometry::rect::Rect;
+use crate::gpu::quad_tree::build_quadtree;
+use crate::quad_tree::{CapacityPolicy, QuadCell};
+use crate::seg_entry::{init_root_seg_entries, SegEntry, ABSTRACT};
+use std::collections::BTreeSet;
+
+/// Largest difference allowed between CPU and GPU cell bounds; both halve the same
+/// f32 bounds, so anything above rounding noise is a real divergence.
+const BBOX_TOLERANCE: f32 = 1e-4;
+
+/// Build the quad tree with both the CPU and the GPU builder and check that they
+/// subdivide alike.
+///
+/// The GPU builder always returns the full `4^max_depth` cells of the last level,
+/// while the CPU builder stops at cells with at most `min_seg` segments and prunes
+/// empty quadrants. Each GPU cell is therefore followed down the CPU tree:
+///
+/// - in a pruned quadrant it must be empty;
+/// - in a CPU leaf at `max_depth` its bounds must match within tolerance and its
+///   entries, compared as sets, must be the same;
+/// - in a CPU leaf that stopped early, its segments must be among the leaf's.
+///
+/// Entry order within a cell is ignored, as are `cell_pos` and `cell_id`, which
+/// the two builders number differently.
+pub fn validate_parity(
+    abs_segments: &[AbstractLineSegment],
+    root_bbox: Rect,
+    max_depth: u8,
+    min_seg: usize,
+) -> anyhow::Result<()> {
+    let root_entries = init_root_seg_entries(abs_segments);
+    let (cpu_nodes, cpu_entries) = crate::quad_tree::build_quadtree(
+        root_bbox,
+        root_entries.clone(),
+        max_depth,
+        min_seg,
+        abs_segments,
+        CapacityPolicy::default(),
+    )?;
+    let (gpu_cells, gpu_entries) =
+        build_quadtree(root_bbox, root_entries, max_depth, min_seg, abs_segments)?;
+    anyhow::ensure!(
+        gpu_cells.len() == 4usize.pow(max_depth as u32),
+        "GPU returned {} cells for depth {max_depth}",
+        gpu_cells.len()
+    );
+    let covered: usize = gpu_cells.iter().map(|c| c.entry_count() as usize).sum();
+    anyhow::ensure!(
+        covered == gpu_entries.len(),
+        "GPU cells cover {covered} of {} entries",
+        gpu_entries.len()
+    );
+
+    for (index, cell) in gpu_cells.iter().enumerate() {
+        let start = cell.entry_start() as usize;
+        let range = start..start + cell.entry_count() as usize;
+        let gpu_cell_entries = gpu_entries
+            .get(range.clone())
+            .ok_or_else(|| anyhow::anyhow!("GPU cell {index} entries {range:?} out of bounds"))?;
+        let gpu_keys = entry_keys(gpu_cell_entries);
+
+        let Some(leaf) = cpu_leaf_above(&cpu_nodes, index, max_depth) else {
+            anyhow::ensure!(
+                gpu_keys.is_empty(),
+                "GPU cell {index} has {} entries in a quadrant the CPU pruned",
+                gpu_keys.len()
+            );
+            continue;
+        };
+        let leaf_range = leaf.leaf_entry_range.clone().unwrap_or_default();
+        let cpu_keys = entry_keys(&cpu_entries[leaf_range]);
+
+        if leaf.depth == max_depth {
+            let gpu_bbox = cell.bbox_ltrb();
+            let cpu_bbox = leaf.bbox.to_ltrb();
+            anyhow::ensure!(
+                gpu_bbox
+                    .iter()
+                    .zip(&cpu_bbox)
+                    .all(|(g, c)| (g - c).abs() <= BBOX_TOLERANCE),
+                "cell {index} bounds differ: GPU {gpu_bbox:?}, CPU {cpu_bbox:?}"
+            );
+            anyhow::ensure!(
+                gpu_keys == cpu_keys,
+                "cell {index} entries differ: GPU {gpu_keys:?}, CPU {cpu_keys:?}"
+            );
+        } else {
+            let segments = |keys: &BTreeSet<EntryKey>| -> BTreeSet<u32> {
+                keys.iter()
+                    .filter(|k| k.0 & ABSTRACT != 0)
+                    .map(|k| k.1)
+                    .collect()
+            };
+            let (gpu_segments, cpu_segments) = (segments(&gpu_keys), segments(&cpu_keys));
+            anyhow::ensure!(
+                gpu_segments.is_subset(&cpu_segments),
+                "cell {index} has segments {gpu_segments:?} outside its depth {} CPU leaf {:?}",
+                leaf.depth,
+                cpu_segments
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `(entry_type, seg_idx, path_idx, data)`: what an entry means, without where
+/// it was placed.
+type EntryKey = (u32, u32, u32, i32);
+
+/// Entry keys of a cell; duplicates would be a bug in either builder, so a set
+/// is enough.
+fn entry_keys(entries: &[SegEntry]) -> BTreeSet<EntryKey> {
+    entries
+        .iter()
+        .map(|e| (e.entry_type, e.seg_idx, e.path_idx, e.data))
+        .collect()
+}
+
+/// CPU leaf containing GPU cell `index` of the last level, or `None` if a quadrant
+/// on the way was pruned. Cell `i` is child `i % 4` of cell `i / 4` one level up,
+/// so the base-4 digits of `index` give the quadrants from the root down.
+fn cpu_leaf_above(nodes: &[QuadCell], index: usize, max_depth: u8) -> Option<&QuadCell> {
+    let mut node = nodes.first()?;
+    for level in (0..max_depth as u32).rev() {
+        let Some(children) = node.children else {
+            break;
+        };
+        let pos = (index / 4usize.pow(level)) % 4;
+        node = &nodes[children[pos]? as usize];
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg_parser::{FlattenOptions, parse_svg};
+    use crate::test_utils::{TestScene, solid};
+    use std::path::Path;
+
+    fn validate_sample(name: &str, max_depth: u8, min_seg: usize) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("sample_svg")
+            .join(name);
+        let parsed = parse_svg(&path, &FlattenOptions::default()).unwrap();
+        let root = Rect::from_ltrb(0.0, 0.0, parsed.width as f32, parsed.height as f32).unwrap();
+        validate_parity(&parsed.abs_segments, root, max_depth, min_seg)
+            .unwrap_or_else(|e| panic!("{name} at depth {max_depth}, min_seg {min_seg}: {e}"));
+    }
+
+    #[test]
+    fn simple_polygons_match() {
+        for (max_depth, min_seg) in [(1, 0), (4, 0), (5, 2)] {
+            validate_sample("simple_polygons.svg", max_depth, min_seg);
+        }
+    }
+
+    #[test]
+    fn degenerate_segments_match() {
+        // Repeated vertices give zero-length segments on the root and quadrant
+        // midlines, where the CPU and GPU builders would otherwise classify them
+        // against their zeroed line equations.
+        let mut scene = TestScene::new();
+        scene.push_polygon(
+            &[
+                (8.0, 8.0),
+                (32.0, 32.0),
+                (32.0, 32.0),
+                (56.0, 8.0),
+                (56.0, 16.0),
+                (56.0, 16.0),
+                (48.0, 56.0),
+                (16.0, 48.0),
+                (16.0, 48.0),
+            ],
+            solid([255, 0, 0, 255]),
+        );
+        assert!(scene.abs_segments.iter().filter(|s| s.is_point()).count() >= 3);
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        for (max_depth, min_seg) in [(1, 0), (3, 0), (4, 1)] {
+            validate_parity(&scene.abs_segments, root, max_depth, min_seg)
+                .unwrap_or_else(|e| panic!("depth {max_depth}, min_seg {min_seg}: {e}"));
+        }
+    }
+
+    #[test]
+    fn curves_and_holes_match() {
+        for (max_depth, min_seg) in [(3, 0), (5, 1)] {
+            validate_sample("curves_and_holes.svg", max_depth, min_seg);
+        }
+    }
+}
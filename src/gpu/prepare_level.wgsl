@@ -0,0 +1,77 @@
+// Depends on: common.wgsl
+
+// Must match WG_SIZE in subdivide_seg_entry.rs.
+const WG_SIZE: u32 = 256u;
+
+struct LevelLayout {
+    max_workgroups_per_dim: u32,
+    num_winding_levels: u32,
+    num_offset_levels: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> result_info: array<SplitResultInfo>;
+// Winding scan levels first, then offset scan levels; copied into each level's
+// own ScanParams buffer after this kernel.
+@group(0) @binding(1) var<storage, read_write> level_scan_params: array<ScanParams>;
+// (x, y, z) workgroup counts, see dispatch slots in subdivide_seg_entry.rs.
+@group(0) @binding(2) var<storage, read_write> dispatch_args: array<u32>;
+@group(0) @binding(3) var<storage, read> level_layout: array<LevelLayout>;
+
+fn div_ceil(a: u32, b: u32) -> u32 {
+    return (a + b - 1u) / b;
+}
+
+// Same split as split_dispatch_3d; zero workgroups dispatch nothing.
+fn write_dispatch(slot: u32, workgroups: u32) {
+    let max_dim = level_layout[0].max_workgroups_per_dim;
+    let x = min(workgroups, max_dim);
+    var y = 1u;
+    var z = 1u;
+    if (x > 0u) {
+        y = min(div_ceil(workgroups, x), max_dim);
+        z = div_ceil(workgroups, x * y);
+    }
+    dispatch_args[slot * 3u] = x;
+    dispatch_args[slot * 3u + 1u] = y;
+    dispatch_args[slot * 3u + 2u] = z;
+}
+
+// Mirrors hierarchical_level_counts: levels past the one that reaches a single
+// element get a length of zero.
+fn write_scan_levels(first: u32, num_levels: u32, initial: u32) {
+    var level_len = initial;
+    for (var i = 0u; i < num_levels; i++) {
+        var params = ScanParams();
+        params.level_len = level_len;
+        params.carry_len = div_ceil(level_len, WG_SIZE);
+        level_scan_params[first + i] = params;
+        write_dispatch(1u + first + i, div_ceil(level_len, WG_SIZE));
+        if (level_len <= 1u) {
+            level_len = 0u;
+        } else {
+            level_len = div_ceil(level_len, WG_SIZE);
+        }
+    }
+}
+
+/// Turn the entry count emitted by the previous level into this level's input
+/// count, scan parameters and indirect dispatch sizes, so that no readback is
+/// needed between levels.
+@compute
+@workgroup_size(1)
+fn main() {
+    let num_entries = result_info[0].emitted_entries_length;
+    result_info[0].seg_entries_length = num_entries;
+    result_info[0].emitted_entries_length = 0u;
+
+    // Saturate like the CPU side's `saturating_mul(4)` rather than wrapping.
+    let num_offsets = select(num_entries * 4u, 0xffffffffu, num_entries > 0x3fffffffu);
+    let num_winding_levels = level_layout[0].num_winding_levels;
+    let num_offset_levels = level_layout[0].num_offset_levels;
+    write_dispatch(0u, div_ceil(num_entries, WG_SIZE));
+    write_scan_levels(0u, num_winding_levels, num_entries);
+    write_scan_levels(num_winding_levels, num_offset_levels, num_offsets);
+    // update_metadata runs one entry per single-invocation workgroup.
+    write_dispatch(1u + num_winding_levels + num_offset_levels, num_offsets);
+}
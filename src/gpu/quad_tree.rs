@@ -1,8 +1,12 @@
 use crate::abstract_segment::AbstractLineSegment;
-use crate::seg_entry::SegEntry;
+use crate::path::AbstractPath;
+use crate::quad_tree::{get_child_bounds, validate_path_indices, QuadCell, QuadTree, Subdivider};
+use crate::seg_entry::{init_root_seg_entries, CellId, SegEntry, ABSTRACT};
 use crate::geometry::rect::Rect;
 use crate::gpu::subdivide_seg_entry::QuadTreeGpuContext;
 use bytemuck::{Pod, Zeroable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use usvg::tiny_skia_path::Point;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
@@ -49,7 +53,48 @@ impl CellMetadata {
         )
         .expect("CellMetadata bbox_ltrb should always be valid")
     }
+
+    /// `nx` x `ny` empty cells tiling `root`, row by row from the top-left.
+    ///
+    /// Neighbouring cells share their edge coordinates exactly and the outer edges
+    /// are the root's own, so no point of the root falls between cells. Entry ranges
+    /// are all empty; fill them in once entries are assigned to the cells.
+    pub fn grid(root: &Rect, nx: u32, ny: u32) -> Vec<CellMetadata> {
+        let xs = grid_edges(root.left(), root.right(), nx);
+        let ys = grid_edges(root.top(), root.bottom(), ny);
+        ys.windows(2)
+            .flat_map(|y| {
+                xs.windows(2).map(move |x| {
+                    let rect = Rect::from_ltrb(x[0], y[0], x[1], y[1])
+                        .expect("grid edges should be increasing");
+                    CellMetadata::new(&rect, 0, 0)
+                })
+            })
+            .collect()
+    }
 }
+
+/// `n + 1` evenly spaced edges from `start` to exactly `end`.
+fn grid_edges(start: f32, end: f32, n: u32) -> Vec<f32> {
+    (0..=n)
+        .map(|i| {
+            if i == n {
+                end
+            } else {
+                start + (end - start) * (i as f32 / n as f32)
+            }
+        })
+        .collect()
+}
+
+/// Debug switch: make [`build_quadtree`] read the entry count back after every
+/// level, as [`build_quadtree_cancellable`] does, instead of building all levels
+/// in one submission. Use it to check the indirect path against the readbacks.
+const READBACK_EACH_LEVEL: bool = false;
+
+/// Subdivide on the GPU down to `max_depth`, returning the last level's cells and
+/// their entries. All levels run in a single submission with indirect dispatches,
+/// so the CPU only waits for the final readback.
 pub fn build_quadtree(
     root_bbox: Rect,
     root_entries: Vec<SegEntry>,
@@ -57,33 +102,891 @@ pub fn build_quadtree(
     min_seg: usize,
     abs_segments: &[AbstractLineSegment],
 ) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
-    let gpu_ctx = pollster::block_on(QuadTreeGpuContext::new(
-        &root_entries,
+    if max_depth == 0 {
+        return Ok(root_only(&root_bbox, root_entries));
+    }
+    if READBACK_EACH_LEVEL {
+        return build_quadtree_cancellable(
+            root_bbox,
+            root_entries,
+            max_depth,
+            min_seg,
+            abs_segments,
+            &AtomicBool::new(false),
+            |_| {},
+        );
+    }
+    let gpu_ctx = create_context(&root_bbox, &root_entries, max_depth, min_seg, abs_segments)?;
+    build_indirect(&gpu_ctx, max_depth)
+}
+
+/// Same as [`build_quadtree`], but on an existing context: `gpu_ctx` is reloaded
+/// with this drawing, so repeated builds (e.g. on every window resize) reuse its
+/// device and pipelines instead of creating new ones. The context's `min_seg` is
+/// used.
+pub fn build_quadtree_in(
+    gpu_ctx: &mut QuadTreeGpuContext,
+    root_bbox: Rect,
+    root_entries: Vec<SegEntry>,
+    max_depth: u8,
+    abs_segments: &[AbstractLineSegment],
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+    if max_depth == 0 {
+        return Ok(root_only(&root_bbox, root_entries));
+    }
+    gpu_ctx.reload(&root_entries, abs_segments, &root_bbox, max_depth);
+    build_indirect(gpu_ctx, max_depth)
+}
+
+/// Same as [`build_quadtree`], but checks `cancel` between levels and stops early
+/// when it is set, returning the tree built so far (the root alone if cancelled
+/// before the first level). Share the flag through an `Arc<AtomicBool>` to cancel
+/// from another thread.
+///
+/// `on_level_done(depth)` runs after each level is subdivided, e.g. for progress.
+/// This needs the CPU between levels, so each level is its own submission followed
+/// by a readback of its entry count.
+pub fn build_quadtree_cancellable(
+    root_bbox: Rect,
+    root_entries: Vec<SegEntry>,
+    max_depth: u8,
+    min_seg: usize,
+    abs_segments: &[AbstractLineSegment],
+    cancel: &AtomicBool,
+    mut on_level_done: impl FnMut(u8),
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+    if max_depth == 0 {
+        return Ok(root_only(&root_bbox, root_entries));
+    }
+    let gpu_ctx = create_context(&root_bbox, &root_entries, max_depth, min_seg, abs_segments)?;
+    build_with_readback(
+        &gpu_ctx,
+        root_bbox,
+        root_entries,
+        max_depth,
+        cancel,
+        |level| {
+            on_level_done(level.depth);
+            Ok(())
+        },
+    )
+}
+
+/// Same as [`build_quadtree`], reading every level back as soon as it is built for
+/// a coarse-to-fine preview: `on_level(depth, cells, entries)` gets the `4^depth`
+/// cells at `depth` (from 1 to `max_depth`) and their entries, as `build_quadtree`
+/// would return them for that `max_depth`.
+///
+/// Each level is its own submission followed by a full readback, so this is the
+/// slowest way to build; use it only when the intermediate levels are shown.
+pub fn build_quadtree_progressive(
+    root_bbox: Rect,
+    root_entries: Vec<SegEntry>,
+    max_depth: u8,
+    min_seg: usize,
+    abs_segments: &[AbstractLineSegment],
+    mut on_level: impl FnMut(u8, &[CellMetadata], &[SegEntry]),
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+    if max_depth == 0 {
+        return Ok(root_only(&root_bbox, root_entries));
+    }
+    let gpu_ctx = create_context(&root_bbox, &root_entries, max_depth, min_seg, abs_segments)?;
+    build_with_readback(
+        &gpu_ctx,
+        root_bbox,
+        root_entries,
+        max_depth,
+        &AtomicBool::new(false),
+        |level| {
+            let (cells, entries) = level.read()?;
+            on_level(level.depth + 1, &cells, &entries);
+            Ok(())
+        },
+    )
+}
+
+/// The tree for `max_depth` 0: the root as a single cell holding every entry.
+/// Needs no GPU, so the builders return it before creating a context.
+fn root_only(root_bbox: &Rect, root_entries: Vec<SegEntry>) -> (Vec<CellMetadata>, Vec<SegEntry>) {
+    let root = CellMetadata::new(root_bbox, 0, root_entries.len() as u32);
+    (vec![root], root_entries)
+}
+
+fn create_context(
+    root_bbox: &Rect,
+    root_entries: &[SegEntry],
+    max_depth: u8,
+    min_seg: usize,
+    abs_segments: &[AbstractLineSegment],
+) -> anyhow::Result<QuadTreeGpuContext> {
+    pollster::block_on(QuadTreeGpuContext::new(
+        root_entries,
         abs_segments,
-        &root_bbox,
+        root_bbox,
         max_depth,
         min_seg as u32,
-    ))?;
+        None,
+    ))
+}
+
+/// `max_depth` must be at least 1; [`build_quadtree`] handles 0 with [`root_only`].
+fn build_indirect(
+    gpu_ctx: &QuadTreeGpuContext,
+    max_depth: u8,
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+    gpu_ctx.process_levels_indirect(max_depth)?;
+    let num_entries = gpu_ctx.read_result_info()?.emitted_entries_length;
+    let num_cells = 4u32.pow(max_depth as u32);
+    read_last_level(gpu_ctx, max_depth - 1, num_cells, num_entries)
+}
 
+/// A level `build_with_readback` has just subdivided.
+struct BuiltLevel<'a> {
+    gpu_ctx: &'a QuadTreeGpuContext,
+    depth: u8,
+    num_cells: u32,
+    num_entries: u32,
+}
+
+impl BuiltLevel<'_> {
+    /// Read back the cells and entries this level produced.
+    fn read(&self) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+        read_last_level(self.gpu_ctx, self.depth, self.num_cells, self.num_entries)
+    }
+}
+
+fn build_with_readback(
+    gpu_ctx: &QuadTreeGpuContext,
+    root_bbox: Rect,
+    root_entries: Vec<SegEntry>,
+    max_depth: u8,
+    cancel: &AtomicBool,
+    mut on_level_done: impl FnMut(&BuiltLevel) -> anyhow::Result<()>,
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
     let mut num_cells = 1u32;
     let mut num_entries = root_entries.len() as u32;
+    let mut levels_done = 0u8;
 
     for depth in 0..max_depth {
-        gpu_ctx.process_level(depth, num_cells, num_entries);
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        gpu_ctx.process_level(depth, num_cells, num_entries)?;
 
         // Read back the actual output entry count; needed because the GPU emits a
         // variable number of entries and the next dispatch must use the correct size.
         let result_info = gpu_ctx.read_result_info()?;
-        num_entries = result_info.seg_entries_length;
+        num_entries = result_info.emitted_entries_length;
         num_cells *= 4;
+        levels_done = depth + 1;
+        on_level_done(&BuiltLevel {
+            gpu_ctx,
+            depth,
+            num_cells,
+            num_entries,
+        })?;
     }
 
-    let mut result_seg_entries = gpu_ctx.read_seg_entry()?;
-    // Last depth processed is max_depth - 1; pass it to select the correct ping-pong buffer.
-    let last_depth = max_depth - 1;
-    let cell_metadata = gpu_ctx.read_cell_metadata(last_depth)?;
+    if levels_done == 0 {
+        return Ok(root_only(&root_bbox, root_entries));
+    }
+    // Last depth processed is levels_done - 1; pass it to select the correct ping-pong buffer.
+    read_last_level(gpu_ctx, levels_done - 1, num_cells, num_entries)
+}
 
-    // num_entries was updated to the final level's output count after the last readback.
+/// Read back the cells and entries `last_depth` produced.
+fn read_last_level(
+    gpu_ctx: &QuadTreeGpuContext,
+    last_depth: u8,
+    num_cells: u32,
+    num_entries: u32,
+) -> anyhow::Result<(Vec<CellMetadata>, Vec<SegEntry>)> {
+    let mut result_seg_entries = gpu_ctx.read_seg_entry()?;
+    let mut cell_metadata = gpu_ctx.read_cell_metadata(last_depth)?;
+    // The metadata buffer is sized for max_depth; only the first num_cells are this level's.
+    cell_metadata.truncate(num_cells as usize);
     result_seg_entries.truncate(num_entries as usize);
     Ok((cell_metadata, result_seg_entries))
 }
+
+/// Builds the tree with the GPU kernels and converts the result to a `QuadTree`.
+///
+/// The kernels split every non-empty cell down to `max_depth`, so the tree can be
+/// deeper than the CPU one for the same `min_seg`; it renders the same.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct GpuSubdivider;
+
+impl Subdivider for GpuSubdivider {
+    fn subdivide(
+        &self,
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        max_depth: u8,
+        min_seg: usize,
+    ) -> anyhow::Result<QuadTree> {
+        validate_path_indices(abs_segments, abs_paths)?;
+        let root_entries = init_root_seg_entries(abs_segments);
+        let (metadata, entries) =
+            build_quadtree(root_bbox, root_entries, max_depth, min_seg, abs_segments)?;
+        level_cells_to_quad_tree(root_bbox, &metadata, &entries)
+    }
+}
+
+/// Rebuild the node hierarchy above the last level returned by `build_quadtree`.
+///
+/// `metadata` holds all `4^depth` cells of that level, cell `i` being child `i % 4`
+/// of cell `i / 4` one level up. As in the CPU builder, cells with no entries get
+/// no node, and the entries are copied out in leaf order with `cell_id` set to
+/// their leaf's node id.
+pub fn level_cells_to_quad_tree(
+    root_bbox: Rect,
+    metadata: &[CellMetadata],
+    entries: &[SegEntry],
+) -> anyhow::Result<QuadTree> {
+    let mut depth = 0u32;
+    while 4usize.pow(depth) < metadata.len() {
+        depth += 1;
+    }
+    if 4usize.pow(depth) != metadata.len() {
+        anyhow::bail!("{} cells is not a complete quad tree level", metadata.len());
+    }
+
+    // occupied[d][i]: whether cell i at depth d has any entries below it.
+    let mut occupied = vec![metadata.iter().map(|c| c.entry_count() > 0).collect::<Vec<_>>()];
+    for _ in 0..depth {
+        let below = occupied.last().unwrap();
+        let level = below.chunks(4).map(|c| c.iter().any(|&o| o)).collect();
+        occupied.push(level);
+    }
+    occupied.reverse();
+
+    let mut nodes = vec![QuadCell {
+        id: 0,
+        depth: 0,
+        bbox: root_bbox,
+        children: None,
+        leaf_entry_range: None,
+    }];
+    let mut leaf_entries = Vec::with_capacity(entries.len());
+    let mut frontier: Vec<(CellId, usize)> = vec![(0, 0)];
+    for (d, level_occupied) in occupied.iter().enumerate().skip(1) {
+        let mut next_frontier = Vec::new();
+        for (parent_id, index) in frontier {
+            let bbox = nodes[parent_id as usize].bbox;
+            let [mid_x, mid_y] = bbox.mid_point();
+            let child_bounds = get_child_bounds(bbox, Point { x: mid_x, y: mid_y })
+                .ok_or_else(|| anyhow::anyhow!("cell {parent_id} cannot be split"))?;
+            let mut child_ids: [Option<CellId>; 4] = [None; 4];
+            for pos in 0..4 {
+                let child_index = index * 4 + pos;
+                if !level_occupied[child_index] {
+                    continue;
+                }
+                let id = nodes.len() as CellId;
+                nodes.push(QuadCell {
+                    id,
+                    depth: d as u8,
+                    bbox: child_bounds[pos],
+                    children: None,
+                    leaf_entry_range: None,
+                });
+                child_ids[pos] = Some(id);
+                next_frontier.push((id, child_index));
+            }
+            nodes[parent_id as usize].children = Some(child_ids);
+        }
+        frontier = next_frontier;
+    }
+
+    for (id, index) in frontier {
+        let range = if depth == 0 {
+            0..entries.len()
+        } else {
+            let cell = &metadata[index];
+            let start = cell.entry_start() as usize;
+            start..start + cell.entry_count() as usize
+        };
+        let cell_entries = entries
+            .get(range.clone())
+            .ok_or_else(|| anyhow::anyhow!("cell {index} entries {range:?} out of bounds"))?;
+        let start = leaf_entries.len();
+        leaf_entries.extend(cell_entries.iter().map(|e| SegEntry { cell_id: id, ..*e }));
+        nodes[id as usize].leaf_entry_range = Some(start..leaf_entries.len());
+    }
+
+    let tree = QuadTree {
+        nodes,
+        entries: leaf_entries,
+    };
+    #[cfg(debug_assertions)]
+    tree.validate_leaf_ranges()?;
+    Ok(tree)
+}
+
+/// Lay a CPU-built tree out the way `ComputeRenderer` reads it: one cell per leaf
+/// with entries, in node order, its `entry_start`/`entry_count` indexing the
+/// returned flat entries.
+///
+/// The CPU builder already keeps each leaf's entries contiguous, so the entries are
+/// `tree.entries` as they are.
+pub fn quad_tree_to_cells(tree: &QuadTree) -> (Vec<CellMetadata>, Vec<SegEntry>) {
+    let metadata = tree
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            let range = node.leaf_entry_range.clone().filter(|r| !r.is_empty())?;
+            let abstract_count = tree.entries[range.clone()]
+                .iter()
+                .filter(|e| e.entry_type & ABSTRACT != 0)
+                .count();
+            Some(CellMetadata {
+                abstract_count: abstract_count as u32,
+                ..CellMetadata::new(&node.bbox, range.start as u32, range.len() as u32)
+            })
+        })
+        .collect();
+    (metadata, tree.entries.clone())
+}
+
+#[cfg(test)]
+mod conversion_tests {
+    use super::*;
+    use crate::test_utils::{TestScene, solid};
+
+    #[test]
+    fn level_cells_rebuild_cpu_tree() {
+        let mut scene = TestScene::new();
+        // Leaves the bottom-right quadrant empty.
+        scene.push_polygon(
+            &[(4.0, 6.0), (58.0, 4.0), (6.0, 58.0)],
+            solid([255, 0, 0, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let cpu = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 1, 0).unwrap();
+
+        // Lay the CPU leaves out the way build_quadtree returns its last level.
+        let mut metadata = Vec::new();
+        let mut entries = Vec::new();
+        for (pos, child) in cpu.nodes[0].children.unwrap().iter().enumerate() {
+            let cell_entries = match child {
+                Some(id) => {
+                    let range = cpu.nodes[*id as usize].leaf_entry_range.clone().unwrap();
+                    &cpu.entries[range]
+                }
+                None => &[][..],
+            };
+            let bbox = get_child_bounds(root, Point { x: 32.0, y: 32.0 }).unwrap()[pos];
+            metadata.push(CellMetadata::new(
+                &bbox,
+                entries.len() as u32,
+                cell_entries.len() as u32,
+            ));
+            entries.extend_from_slice(cell_entries);
+        }
+
+        let tree = level_cells_to_quad_tree(root, &metadata, &entries).unwrap();
+        assert_eq!(tree.nodes[0].children, cpu.nodes[0].children);
+        assert_eq!(tree.nodes[0].children.unwrap()[3], None);
+        for (node, cpu_node) in tree.nodes.iter().zip(&cpu.nodes) {
+            assert_eq!(node.bbox, cpu_node.bbox);
+            assert_eq!(node.leaf_entry_range, cpu_node.leaf_entry_range);
+        }
+        assert!(level_cells_to_quad_tree(root, &metadata[..3], &entries).is_err());
+    }
+
+    #[test]
+    fn cpu_tree_flattens_to_one_cell_per_leaf() {
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (6.0, 58.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(40.0, 40.0, 60.0, 60.0, solid([0, 0, 255, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+
+        let (metadata, entries) = quad_tree_to_cells(&tree);
+        let leaves: Vec<_> = tree
+            .nodes
+            .iter()
+            .filter(|n| n.leaf_entry_range.as_ref().is_some_and(|r| !r.is_empty()))
+            .collect();
+        assert_eq!(metadata.len(), leaves.len());
+        let covered: u32 = metadata.iter().map(|c| c.entry_count()).sum();
+        assert_eq!(covered as usize, entries.len());
+        for (cell, leaf) in metadata.iter().zip(leaves) {
+            assert_eq!(cell.bbox_rect(), leaf.bbox);
+            let start = cell.entry_start() as usize;
+            let cell_entries = &entries[start..start + cell.entry_count() as usize];
+            assert!(cell_entries.iter().all(|e| e.cell_id == leaf.id));
+            let abstract_count = cell_entries
+                .iter()
+                .filter(|e| e.entry_type & ABSTRACT != 0)
+                .count();
+            assert_eq!(cell.abstract_count as usize, abstract_count);
+        }
+    }
+
+    #[test]
+    fn grid_tiles_root_exactly() {
+        let root = Rect::from_ltrb(-3.5, 2.0, 97.1, 50.3).unwrap();
+        let (nx, ny) = (3, 5);
+        let cells = CellMetadata::grid(&root, nx, ny);
+        assert_eq!(cells.len(), (nx * ny) as usize);
+
+        for (i, cell) in cells.iter().enumerate() {
+            let (col, row) = (i as u32 % nx, i as u32 / nx);
+            let [l, t, r, b] = cell.bbox_ltrb();
+            assert!(l < r && t < b, "cell {i}: {:?}", cell.bbox_ltrb());
+            // Left and top edges continue the previous cell in the row and column.
+            let prev_r = col.checked_sub(1).map(|_| cells[i - 1].bbox_ltrb()[2]);
+            let prev_b = row
+                .checked_sub(1)
+                .map(|_| cells[i - nx as usize].bbox_ltrb()[3]);
+            assert_eq!(l, prev_r.unwrap_or(root.left()), "cell {i}");
+            assert_eq!(t, prev_b.unwrap_or(root.top()), "cell {i}");
+            if col == nx - 1 {
+                assert_eq!(r, root.right());
+            }
+            if row == ny - 1 {
+                assert_eq!(b, root.bottom());
+            }
+            assert_eq!(cell.entry_count(), 0);
+            let [mx, my] = cell.mid;
+            assert!((mx - (l + r) / 2.0).abs() < 1e-4 && (my - (t + b) / 2.0).abs() < 1e-4);
+        }
+        assert!(CellMetadata::grid(&root, 0, 4).is_empty());
+    }
+
+    #[test]
+    fn max_depth_zero_returns_the_root_without_a_gpu() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 60.0, 40.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let n = root_entries.len();
+        let (metadata, entries) =
+            build_quadtree(root, root_entries, 0, 1, &scene.abs_segments).unwrap();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(
+            (metadata[0].entry_start(), metadata[0].entry_count()),
+            (0, n as u32)
+        );
+        assert_eq!(entries.len(), n);
+
+        let tree = GpuSubdivider
+            .subdivide(&scene.abs_segments, &scene.abs_paths, root, 0, 1)
+            .unwrap();
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.nodes[0].leaf_entry_range, Some(0..n));
+    }
+}
+
+#[cfg(all(test, feature = "gpu-tests"))]
+mod tests {
+    use super::*;
+    use crate::quad_tree::CpuSubdivider;
+    use crate::render::{RenderOptions, render};
+    use crate::test_utils::{TestScene, solid};
+
+    #[test]
+    fn cancel_after_first_level_returns_depth_one_tree() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 60.0, 40.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let cancel = AtomicBool::new(false);
+        let (metadata, entries) = build_quadtree_cancellable(
+            root,
+            init_root_seg_entries(&scene.abs_segments),
+            4,
+            0,
+            &scene.abs_segments,
+            &cancel,
+            |depth| {
+                if depth == 0 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(metadata.len(), 4);
+        for cell in &metadata {
+            let [l, t, r, b] = cell.bbox_ltrb();
+            assert_eq!((r - l, b - t), (32.0, 32.0));
+        }
+        let covered: u32 = metadata.iter().map(|c| c.entry_count()).sum();
+        assert_eq!(covered as usize, entries.len());
+    }
+
+    #[test]
+    fn progressive_levels_refine_towards_final_tree() {
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(30.0, 30.0, 50.0, 50.0, solid([0, 0, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let max_depth = 3;
+
+        let mut levels = Vec::new();
+        let (cells, entries) = build_quadtree_progressive(
+            root,
+            root_entries.clone(),
+            max_depth,
+            1,
+            &scene.abs_segments,
+            |depth, cells, entries| levels.push((depth, cells.to_vec(), entries.to_vec())),
+        )
+        .unwrap();
+
+        let depths: Vec<_> = levels.iter().map(|(depth, ..)| *depth).collect();
+        assert_eq!(depths, [1, 2, 3]);
+        let occupied =
+            |cells: &[CellMetadata]| cells.iter().filter(|c| c.entry_count() > 0).count();
+        let (_, level_1, _) = &levels[0];
+        let (_, level_3, level_3_entries) = &levels[2];
+        assert_eq!((level_1.len(), level_3.len()), (4, 64));
+        assert!(occupied(level_3) > occupied(level_1));
+        for (_, cells, entries) in &levels {
+            let covered: u32 = cells.iter().map(|c| c.entry_count()).sum();
+            assert_eq!(covered as usize, entries.len());
+        }
+
+        // The last preview is the finished tree, as build_quadtree returns it.
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(level_3),
+            bytemuck::cast_slice::<_, u8>(&cells)
+        );
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(level_3_entries),
+            bytemuck::cast_slice::<_, u8>(&entries)
+        );
+        let (direct_cells, _) =
+            build_quadtree(root, root_entries, max_depth, 1, &scene.abs_segments).unwrap();
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&direct_cells),
+            bytemuck::cast_slice::<_, u8>(&cells)
+        );
+    }
+
+    #[test]
+    fn min_seg_zero_stops_at_max_depth() {
+        let mut scene = TestScene::new();
+        scene.push_polygon(
+            &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+            solid([255, 0, 0, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let max_depth = 4;
+        let (metadata, entries) = build_quadtree(
+            root,
+            init_root_seg_entries(&scene.abs_segments),
+            max_depth,
+            0,
+            &scene.abs_segments,
+        )
+        .unwrap();
+
+        let covered: u32 = metadata.iter().map(|c| c.entry_count()).sum();
+        assert_eq!(covered as usize, entries.len());
+        let finest = 64.0 / (1 << max_depth) as f32;
+        for cell in &metadata {
+            let [l, t, r, _] = cell.bbox_ltrb();
+            assert!(r - l >= finest);
+            let start = cell.entry_start() as usize;
+            let end = start + cell.entry_count() as usize;
+            if entries[start..end].iter().any(|e| e.entry_type & ABSTRACT != 0) {
+                assert_eq!(r - l, finest, "cell at ({l}, {t})");
+            }
+        }
+    }
+
+    #[test]
+    fn reloaded_context_builds_like_a_fresh_one() {
+        let mut small = TestScene::new();
+        small.push_rect(4.0, 4.0, 20.0, 28.0, solid([255, 0, 0, 255]));
+        let mut large = TestScene::new();
+        large
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(30.0, 30.0, 50.0, 50.0, solid([0, 0, 255, 128]));
+        let small_root = Rect::from_ltrb(0.0, 0.0, 32.0, 32.0).unwrap();
+        let large_root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let small_entries = init_root_seg_entries(&small.abs_segments);
+        let large_entries = init_root_seg_entries(&large.abs_segments);
+
+        let mut ctx =
+            create_context(&small_root, &small_entries, 2, 1, &small.abs_segments).unwrap();
+        build_quadtree_in(&mut ctx, small_root, small_entries, 2, &small.abs_segments).unwrap();
+        let (cells, entries) = build_quadtree_in(
+            &mut ctx,
+            large_root,
+            large_entries.clone(),
+            4,
+            &large.abs_segments,
+        )
+        .unwrap();
+        let (fresh_cells, fresh_entries) =
+            build_quadtree(large_root, large_entries, 4, 1, &large.abs_segments).unwrap();
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&cells),
+            bytemuck::cast_slice::<_, u8>(&fresh_cells)
+        );
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&entries),
+            bytemuck::cast_slice::<_, u8>(&fresh_entries)
+        );
+    }
+
+    #[test]
+    fn cpu_and_gpu_subdividers_render_alike() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(30.0, 30.0, 50.0, 50.0, solid([0, 0, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let subdividers: [&dyn Subdivider; 2] = [&CpuSubdivider::default(), &GpuSubdivider];
+        let [cpu, gpu] = subdividers.map(|subdivider| {
+            let tree = subdivider
+                .subdivide(&scene.abs_segments, &scene.abs_paths, root, 3, 1)
+                .unwrap();
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &RenderOptions::default(),
+            );
+            (tree, pixels)
+        });
+
+        assert_same_tree(&cpu.0, &gpu.0, 3);
+        assert_eq!(cpu.1, gpu.1);
+    }
+
+    /// Walk `cpu` and `gpu` from the root and check that they subdivide alike, as
+    /// `gpu::parity` does for the raw builders: matching nodes have the same bounds
+    /// and children, and matching leaves the same number of entries with the same
+    /// sorted keys. Below a CPU leaf that stopped before `max_depth` the GPU tree may
+    /// split further, but only among that leaf's segments.
+    fn assert_same_tree(cpu: &QuadTree, gpu: &QuadTree, max_depth: u8) {
+        let key = |e: &SegEntry| (e.entry_type, e.seg_idx, e.path_idx, e.data);
+        let keys = |tree: &QuadTree, node: &QuadCell| {
+            let mut keys: Vec<_> = tree.entries[node.leaf_entry_range.clone().unwrap()]
+                .iter()
+                .map(key)
+                .collect();
+            keys.sort_unstable();
+            keys
+        };
+        let mut stack = vec![(0usize, 0usize)];
+        while let Some((cpu_id, gpu_id)) = stack.pop() {
+            let (c, g) = (&cpu.nodes[cpu_id], &gpu.nodes[gpu_id]);
+            assert_eq!((c.depth, c.bbox), (g.depth, g.bbox), "node {cpu_id}");
+            match (c.children, g.children) {
+                (Some(cpu_children), Some(gpu_children)) => {
+                    for (pos, pair) in cpu_children.iter().zip(&gpu_children).enumerate() {
+                        match pair {
+                            (Some(ci), Some(gi)) => stack.push((*ci as usize, *gi as usize)),
+                            (None, None) => {}
+                            _ => panic!("node {cpu_id} child {pos} pruned in only one tree"),
+                        }
+                    }
+                }
+                (None, None) => {
+                    let len = |node: &QuadCell| node.leaf_entry_range.as_ref().map(|r| r.len());
+                    assert_eq!(len(c), len(g), "leaf {cpu_id}");
+                    assert_eq!(keys(cpu, c), keys(gpu, g), "leaf {cpu_id}");
+                }
+                (None, Some(_)) => {
+                    assert!(c.depth < max_depth, "leaf {cpu_id} split on the GPU only");
+                    let segments: Vec<u32> = keys(cpu, c)
+                        .iter()
+                        .filter(|k| k.0 & ABSTRACT != 0)
+                        .map(|k| k.1)
+                        .collect();
+                    let mut below = vec![gpu_id];
+                    while let Some(id) = below.pop() {
+                        let node = &gpu.nodes[id];
+                        if let Some(children) = node.children {
+                            below.extend(children.into_iter().flatten().map(|i| i as usize));
+                            continue;
+                        }
+                        for k in keys(gpu, node).iter().filter(|k| k.0 & ABSTRACT != 0) {
+                            assert!(segments.contains(&k.1), "{k:?} below leaf {cpu_id}");
+                        }
+                    }
+                }
+                (Some(_), None) => panic!("node {cpu_id} split on the CPU only"),
+            }
+        }
+    }
+
+    #[test]
+    fn cpu_built_tree_renders_on_gpu_like_cpu() {
+        use crate::gpu::render::{ComputeRenderer, build_path_paints};
+        use crate::test_utils::pixel;
+
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        // Both opaque: the GPU kernel keeps the last filled path's paint instead of
+        // compositing, so a translucent top path would differ where they overlap.
+        scene
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(30.0, 30.0, 50.0, 50.0, solid([0, 0, 255, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+
+        let mut cpu_pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut cpu_pixels,
+            w,
+            h,
+            &RenderOptions {
+                background: Some([0, 0, 0, 255]),
+                ..RenderOptions::default()
+            },
+        );
+
+        let (metadata, entries) = quad_tree_to_cells(&tree);
+        let (path_paints, gradient_stops) = build_path_paints(&scene.abs_paths, &scene.paints);
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let gpu_pixels = renderer
+            .render_to_rgba_headless(
+                &metadata,
+                &entries,
+                &scene.abs_segments,
+                &path_paints,
+                &gradient_stops,
+            )
+            .unwrap();
+
+        // Away from the edges, where sample placement cannot differ.
+        for (x, y) in [(1, 1), (10, 10), (40, 10), (20, 40), (40, 40), (55, 55), (62, 62)] {
+            assert_eq!(
+                pixel(&gpu_pixels, w, x, y),
+                pixel(&cpu_pixels, w, x, y),
+                "({x}, {y})"
+            );
+        }
+    }
+
+    #[test]
+    fn child_quadrants_match_cpu_order() {
+        let mut scene = TestScene::new();
+        // Touches all four quadrants, with different edges in each.
+        scene.push_polygon(
+            &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+            solid([255, 0, 0, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let (metadata, gpu_entries) = build_quadtree(
+            root,
+            init_root_seg_entries(&scene.abs_segments),
+            1,
+            0,
+            &scene.abs_segments,
+        )
+        .unwrap();
+        let cpu = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 1, 0).unwrap();
+
+        let key = |e: &SegEntry| (e.entry_type, e.seg_idx, e.path_idx, e.data);
+        let cpu_children = cpu.nodes[0].children.unwrap();
+        assert_eq!(metadata.len(), 4);
+        for (pos, child_id) in cpu_children.iter().enumerate() {
+            let cpu_child = &cpu.nodes[child_id.unwrap() as usize];
+            let gpu_child = &metadata[pos];
+            assert_eq!(gpu_child.bbox_ltrb(), cpu_child.bbox.to_ltrb(), "quadrant {pos}");
+
+            let start = gpu_child.entry_start() as usize;
+            let end = start + gpu_child.entry_count() as usize;
+            let mut gpu_keys: Vec<_> = gpu_entries[start..end].iter().map(key).collect();
+            let range = cpu_child.leaf_entry_range.clone().unwrap();
+            let mut cpu_keys: Vec<_> = cpu.entries[range].iter().map(key).collect();
+            gpu_keys.sort();
+            cpu_keys.sort();
+            assert_eq!(gpu_keys, cpu_keys, "quadrant {pos}");
+            assert!(
+                gpu_entries[start..end]
+                    .iter()
+                    .all(|e| e.cell_pos == pos as u32)
+            );
+        }
+    }
+
+    #[test]
+    fn indirect_build_matches_readback_in_fewer_submissions() {
+        let mut scene = TestScene::new();
+        // Enough segments that the deeper levels need a second scan level.
+        let star: Vec<(f32, f32)> = (0..200)
+            .map(|k| {
+                let r = if k % 2 == 0 { 28.0 } else { 14.0 };
+                let angle = k as f32 * std::f32::consts::TAU / 200.0;
+                (32.0 + r * angle.cos(), 32.0 + r * angle.sin())
+            })
+            .collect();
+        scene
+            .push_polygon(&star, solid([255, 0, 0, 255]))
+            .push_rect(10.0, 10.0, 30.0, 50.0, solid([0, 0, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let max_depth = 5;
+
+        let readback_ctx =
+            create_context(&root, &root_entries, max_depth, 1, &scene.abs_segments).unwrap();
+        let (readback_cells, readback_entries) = build_with_readback(
+            &readback_ctx,
+            root,
+            root_entries.clone(),
+            max_depth,
+            &AtomicBool::new(false),
+            |_| Ok(()),
+        )
+        .unwrap();
+        let indirect_ctx =
+            create_context(&root, &root_entries, max_depth, 1, &scene.abs_segments).unwrap();
+        let (indirect_cells, indirect_entries) = build_indirect(&indirect_ctx, max_depth).unwrap();
+
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&indirect_cells),
+            bytemuck::cast_slice::<_, u8>(&readback_cells)
+        );
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&indirect_entries),
+            bytemuck::cast_slice::<_, u8>(&readback_entries)
+        );
+
+        // Readback: a level and a count readback per depth, then cells and entries.
+        // Indirect: all levels, the final count, then cells and entries.
+        let (readback, indirect) = (readback_ctx.submissions(), indirect_ctx.submissions());
+        assert_eq!(
+            readback,
+            2 * max_depth as u32 + 2,
+            "readback submissions at depth {max_depth}"
+        );
+        assert_eq!(indirect, 4, "indirect submissions at depth {max_depth}");
+    }
+}
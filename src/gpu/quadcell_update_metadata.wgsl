@@ -13,7 +13,7 @@ fn main(
     @builtin(num_workgroups) num_wg: vec3<u32>,
 ) {
     let entry_idx = linearize_workgroup_id(wid, num_wg);
-    let result_len = result_info[0].seg_entries_length;
+    let result_len = result_info[0].emitted_entries_length;
 
     if (entry_idx >= result_len) {
         return;
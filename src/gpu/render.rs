@@ -1,12 +1,14 @@
 use crate::abstract_segment::AbstractLineSegment;
 use crate::seg_entry::SegEntry;
 use crate::gpu::quad_tree::CellMetadata;
-use crate::path::{AbstractPath, Paint};
+use crate::path::{resolve_paint, AbstractPath, Affine, GradientStop, Paint};
 use anyhow::Context;
 use bytemuck::{bytes_of, Pod, Zeroable};
 use std::sync::mpsc::channel;
+use usvg::FillRule;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use crate::gpu::shader_loader::load_with_common;
+use crate::gpu::subdivide_seg_entry::split_dispatch_3d;
 use wgpu::{
     BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferDescriptor, BufferUsages,
     CommandEncoderDescriptor, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor,
@@ -16,13 +18,58 @@ use wgpu::{
     TextureView, TextureViewDescriptor,
 };
 
-const RENDER_WG_SIZE_X: u32 = 8;
-const RENDER_WG_SIZE_Y: u32 = 8;
+/// Edge of the largest pixel tile `default_workgroup_size` picks.
+const MAX_RENDER_WG_SIZE: u32 = 16;
+
+/// How the cell render pass spreads its work over the GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderDispatch {
+    /// One invocation per output pixel, scanning every leaf cell for the one
+    /// containing it.
+    #[default]
+    PerPixel,
+    /// One workgroup per leaf cell, shading the cell's pixels in tiles with its
+    /// entries staged in workgroup memory. Better locality for cells with many
+    /// entries; the output is the same.
+    PerCell,
+}
+
+const PAINT_SOLID: u32 = 0;
+const PAINT_LINEAR_GRADIENT: u32 = 1;
+const PAINT_RADIAL_GRADIENT: u32 = 2;
+
+const FILL_RULE_NON_ZERO: u32 = 0;
+const FILL_RULE_EVEN_ODD: u32 = 1;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct PathPaintGpu {
     rgba: [f32; 4],
+    kind: u32,
+    stop_start: u32,
+    stop_count: u32,
+    fill_rule: u32,
+    // Linear: (x1, y1, x2, y2). Radial: (cx, cy, r, unused).
+    geometry: [f32; 4],
+    // Rows of the inverse gradient transform: (sx, kx, tx, _), (ky, sy, ty, _).
+    inv_transform: [[f32; 4]; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct GradientStopGpu {
+    rgba: [f32; 4],
+    offset: f32,
+    _pad: [f32; 3],
+}
+
+/// Per-frame inputs of the cell render pass.
+struct RenderInputs<'a> {
+    cell_metadata: &'a [CellMetadata],
+    seg_entries: &'a [SegEntry],
+    segments: &'a [AbstractLineSegment],
+    path_paints: &'a [PathPaintGpu],
+    gradient_stops: &'a [GradientStopGpu],
 }
 
 #[repr(C)]
@@ -31,43 +78,113 @@ struct RenderParams {
     width: u32,
     height: u32,
     entries_len: u32,
-    _pad: u32,
+    premultiply: u32,
 }
 
-pub fn build_path_paints(abs_paths: &[AbstractPath], paints: &[Paint]) -> Vec<PathPaintGpu> {
+/// Flatten per-path paints for the GPU; gradient stops of all paths share one buffer.
+pub fn build_path_paints(
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> (Vec<PathPaintGpu>, Vec<GradientStopGpu>) {
     let mut out = Vec::with_capacity(abs_paths.len().max(1));
+    let mut stops_out: Vec<GradientStopGpu> = Vec::new();
+    let mut push_stops = |stops: &[GradientStop]| -> (u32, u32) {
+        let start = stops_out.len() as u32;
+        stops_out.extend(stops.iter().map(|s| GradientStopGpu {
+            rgba: s.rgba.map(|c| c as f32 / 255.0),
+            offset: s.offset,
+            _pad: [0.0; 3],
+        }));
+        (start, stops.len() as u32)
+    };
+    let inv_rows = |m: &Affine| [[m[0], m[1], m[2], 0.0], [m[3], m[4], m[5], 0.0]];
+
     for path in abs_paths {
-        let rgba = paints
-            .get(path.paint_id)
-            .map(|paint| match paint {
-                Paint::SolidColor { rgba } => *rgba,
-            })
-            .unwrap_or([0, 0, 0, 255]);
+        let gpu_paint = match resolve_paint(paints, path.paint_id) {
+            Paint::SolidColor { rgba } => solid_paint_gpu(*rgba),
+            Paint::LinearGradient {
+                start,
+                end,
+                stops,
+                inv_transform,
+            } => {
+                let (stop_start, stop_count) = push_stops(stops);
+                PathPaintGpu {
+                    kind: PAINT_LINEAR_GRADIENT,
+                    stop_start,
+                    stop_count,
+                    geometry: [start[0], start[1], end[0], end[1]],
+                    inv_transform: inv_rows(inv_transform),
+                    ..solid_paint_gpu([0, 0, 0, 255])
+                }
+            }
+            Paint::RadialGradient {
+                center,
+                radius,
+                stops,
+                inv_transform,
+            } => {
+                let (stop_start, stop_count) = push_stops(stops);
+                PathPaintGpu {
+                    kind: PAINT_RADIAL_GRADIENT,
+                    stop_start,
+                    stop_count,
+                    geometry: [center[0], center[1], *radius, 0.0],
+                    inv_transform: inv_rows(inv_transform),
+                    ..solid_paint_gpu([0, 0, 0, 255])
+                }
+            }
+        };
         out.push(PathPaintGpu {
-            rgba: [
-                rgba[0] as f32 / 255.0,
-                rgba[1] as f32 / 255.0,
-                rgba[2] as f32 / 255.0,
-                rgba[3] as f32 / 255.0,
-            ],
+            fill_rule: match path.fill_rule {
+                FillRule::NonZero => FILL_RULE_NON_ZERO,
+                FillRule::EvenOdd => FILL_RULE_EVEN_ODD,
+            },
+            ..gpu_paint
         });
     }
     if out.is_empty() {
-        out.push(PathPaintGpu {
-            rgba: [0.0, 0.0, 0.0, 1.0],
-        });
+        out.push(solid_paint_gpu([0, 0, 0, 255]));
+    }
+    // Storage bindings cannot be empty; the dummy stop is never referenced.
+    if stops_out.is_empty() {
+        stops_out.push(GradientStopGpu::zeroed());
+    }
+    (out, stops_out)
+}
+
+fn solid_paint_gpu(rgba: [u8; 4]) -> PathPaintGpu {
+    PathPaintGpu {
+        rgba: [
+            rgba[0] as f32 / 255.0,
+            rgba[1] as f32 / 255.0,
+            rgba[2] as f32 / 255.0,
+            rgba[3] as f32 / 255.0,
+        ],
+        kind: PAINT_SOLID,
+        stop_start: 0,
+        stop_count: 0,
+        fill_rule: FILL_RULE_EVEN_ODD,
+        geometry: [0.0; 4],
+        inv_transform: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]],
     }
-    out
 }
 
 pub struct ComputeRenderer {
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
+    dispatch: RenderDispatch,
+    /// Pixels per workgroup of the per-pixel passes, `[x, y]`; the pipelines are
+    /// specialized to it.
+    workgroup_size: [u32; 2],
     pipeline: ComputePipeline,
+    /// Clears the output before `pipeline` when it only writes the leaf cells.
+    clear_pipeline: Option<ComputePipeline>,
     output_texture: Texture,
     output_view: TextureView,
     blitter: wgpu::util::TextureBlitter,
+    premultiply: bool,
 }
 
 impl ComputeRenderer {
@@ -85,19 +202,7 @@ impl ComputeRenderer {
             })
             .await
             .context("No surface-compatible adapter found")?;
-
-        let limits = adapter.limits();
-        let (device, queue) = adapter
-            .request_device(&DeviceDescriptor {
-                label: Some("gpu renderer device"),
-                required_features: Features::empty(),
-                required_limits: limits,
-                experimental_features: Default::default(),
-                memory_hints: Default::default(),
-                trace: Default::default(),
-            })
-            .await
-            .context("Failed to create renderer device")?;
+        let (device, queue) = request_renderer_device(&adapter).await?;
 
         let caps = surface.get_capabilities(&adapter);
         let surface_format = caps
@@ -125,32 +230,105 @@ impl ComputeRenderer {
         };
         surface.configure(&device, &config);
 
-        let shader = load_with_common(
-            &device, "cell render compute shader", include_str!("cell_render.wgsl"),
-        );
+        Ok(Self::from_device(device, queue, config))
+    }
 
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("cell render pipeline"),
-            layout: None,
-            module: &shader,
-            entry_point: Some("main"),
-            compilation_options: PipelineCompilationOptions::default(),
-            cache: None,
-        });
+    /// Create a renderer without a window: the adapter need not support any surface,
+    /// and output is read back with `render_to_rgba_headless`.
+    pub async fn new_headless(width: u32, height: u32) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .context("No adapter found")?;
+        let (device, queue) = request_renderer_device(&adapter).await?;
+
+        // Never used to configure a surface; only the size and blit format are read.
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8Unorm,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        Ok(Self::from_device(device, queue, config))
+    }
 
+    /// Build the pipeline and output texture for an already-configured device.
+    ///
+    /// Use this to share the caller's device, e.g. together with `render_into_view`;
+    /// `config` only supplies the render size and the blit target format.
+    pub fn from_device(device: Device, queue: Queue, config: SurfaceConfiguration) -> Self {
+        let dispatch = RenderDispatch::default();
+        let workgroup_size = default_workgroup_size(&device.limits());
+        let (pipeline, clear_pipeline) = create_render_pipelines(&device, dispatch, workgroup_size);
         let (output_texture, output_view) =
             create_output_texture(&device, config.width, config.height);
         let blitter = wgpu::util::TextureBlitter::new(&device, config.format);
 
-        Ok(Self {
+        Self {
             device,
             queue,
             config,
+            dispatch,
+            workgroup_size,
             pipeline,
+            clear_pipeline,
             output_texture,
             output_view,
             blitter,
-        })
+            premultiply: false,
+        }
+    }
+
+    /// Switch the cell render pass to `dispatch`, rebuilding its pipelines.
+    pub fn with_dispatch(mut self, dispatch: RenderDispatch) -> Self {
+        if dispatch != self.dispatch {
+            (self.pipeline, self.clear_pipeline) =
+                create_render_pipelines(&self.device, dispatch, self.workgroup_size);
+            self.dispatch = dispatch;
+        }
+        self
+    }
+
+    /// Use `[x, y]`-pixel workgroups for the per-pixel passes instead of the size
+    /// `default_workgroup_size` picked for the device, rebuilding the pipelines.
+    /// Fails if the device limits do not allow that workgroup size.
+    pub fn with_workgroup_size(mut self, size: [u32; 2]) -> anyhow::Result<Self> {
+        let limits = self.device.limits();
+        anyhow::ensure!(
+            workgroup_size_fits(size, &limits),
+            "render workgroup size {size:?} exceeds the device limits ({} invocations, \
+             {}x{})",
+            limits.max_compute_invocations_per_workgroup,
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_workgroup_size_y
+        );
+        if size != self.workgroup_size {
+            (self.pipeline, self.clear_pipeline) =
+                create_render_pipelines(&self.device, self.dispatch, size);
+            self.workgroup_size = size;
+        }
+        Ok(self)
+    }
+
+    /// Pixels per workgroup of the per-pixel passes, `[x, y]`.
+    pub fn workgroup_size(&self) -> [u32; 2] {
+        self.workgroup_size
+    }
+
+    /// Write premultiplied RGBA (`rgb * a`) instead of straight alpha, matching
+    /// `RenderOptions::premultiply` on the CPU. Off by default; leave it off for
+    /// output passed to `save_png_rgba8`.
+    pub fn set_premultiply(&mut self, premultiply: bool) {
+        self.premultiply = premultiply;
     }
 
     pub fn render_to_rgba(
@@ -160,7 +338,189 @@ impl ComputeRenderer {
         seg_entries: &[SegEntry],
         segments: &[AbstractLineSegment],
         path_paints: &[PathPaintGpu],
+        gradient_stops: &[GradientStopGpu],
     ) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("renderer command encoder"),
+            });
+        self.encode_render_pass(
+            &mut encoder,
+            &self.output_view,
+            &RenderInputs {
+                cell_metadata,
+                seg_entries,
+                segments,
+                path_paints,
+                gradient_stops,
+            },
+        )?;
+
+        let frame_to_present = self.encode_blit_to_surface(&mut encoder, surface)?;
+
+        let readback = self.encode_output_readback(&mut encoder, &self.output_texture);
+        self.queue.submit([encoder.finish()]);
+        if let Some(frame) = frame_to_present {
+            frame.present();
+        }
+        self.read_output(readback)
+    }
+
+    /// Like `render_to_rgba`, without the readback: render, blit to `surface` and
+    /// present, e.g. once per frame in a window. A lost or outdated surface is
+    /// reconfigured and the frame skipped.
+    pub fn render_to_surface(
+        &self,
+        surface: &Surface<'_>,
+        cell_metadata: &[CellMetadata],
+        seg_entries: &[SegEntry],
+        segments: &[AbstractLineSegment],
+        path_paints: &[PathPaintGpu],
+        gradient_stops: &[GradientStopGpu],
+    ) -> anyhow::Result<()> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("renderer command encoder"),
+            });
+        self.encode_render_pass(
+            &mut encoder,
+            &self.output_view,
+            &RenderInputs {
+                cell_metadata,
+                seg_entries,
+                segments,
+                path_paints,
+                gradient_stops,
+            },
+        )?;
+        let frame_to_present = self.encode_blit_to_surface(&mut encoder, surface)?;
+        self.queue.submit([encoder.finish()]);
+        if let Some(frame) = frame_to_present {
+            frame.present();
+        }
+        Ok(())
+    }
+
+    /// Change the render size: reconfigure `surface` and recreate the output texture.
+    /// Zero sizes are clamped to 1, as a minimised window reports.
+    pub fn resize(&mut self, surface: &Surface<'_>, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        surface.configure(&self.device, &self.config);
+        (self.output_texture, self.output_view) =
+            create_output_texture(&self.device, self.config.width, self.config.height);
+    }
+
+    /// Render size, which `resize` changes.
+    pub fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    /// Record a blit of the output texture into the surface's next frame, returned
+    /// for presenting after submission; `None` when no frame is available.
+    fn encode_blit_to_surface(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &Surface<'_>,
+    ) -> anyhow::Result<Option<SurfaceTexture>> {
+        match surface.get_current_texture() {
+            Ok(frame) => {
+                let view = frame.texture.create_view(&TextureViewDescriptor::default());
+                self.blitter
+                    .copy(&self.device, encoder, &self.output_view, &view);
+                Ok(Some(frame))
+            }
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                surface.configure(&self.device, &self.config);
+                Ok(None)
+            }
+            Err(SurfaceError::Timeout | SurfaceError::Other) => Ok(None),
+            Err(SurfaceError::OutOfMemory) => anyhow::bail!("surface out of memory"),
+        }
+    }
+
+    /// Like `render_to_rgba`, without a surface to blit to and present: only the
+    /// compute pass and the readback of the output texture.
+    pub fn render_to_rgba_headless(
+        &self,
+        cell_metadata: &[CellMetadata],
+        seg_entries: &[SegEntry],
+        segments: &[AbstractLineSegment],
+        path_paints: &[PathPaintGpu],
+        gradient_stops: &[GradientStopGpu],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("renderer command encoder"),
+            });
+        self.encode_render_pass(
+            &mut encoder,
+            &self.output_view,
+            &RenderInputs {
+                cell_metadata,
+                seg_entries,
+                segments,
+                path_paints,
+                gradient_stops,
+            },
+        )?;
+        let readback = self.encode_output_readback(&mut encoder, &self.output_texture);
+        self.queue.submit([encoder.finish()]);
+        self.read_output(readback)
+    }
+
+    /// Render straight into `target` instead of the internal output texture, skipping
+    /// the blit and readback.
+    ///
+    /// `target` must be a view of an `Rgba8Unorm` texture with `STORAGE_BINDING` usage,
+    /// created on this renderer's device and at least the configured size. The work is
+    /// submitted on the renderer's queue before returning.
+    pub fn render_into_view(
+        &self,
+        target: &TextureView,
+        cell_metadata: &[CellMetadata],
+        seg_entries: &[SegEntry],
+        segments: &[AbstractLineSegment],
+        path_paints: &[PathPaintGpu],
+        gradient_stops: &[GradientStopGpu],
+    ) -> anyhow::Result<()> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("renderer command encoder"),
+            });
+        self.encode_render_pass(
+            &mut encoder,
+            target,
+            &RenderInputs {
+                cell_metadata,
+                seg_entries,
+                segments,
+                path_paints,
+                gradient_stops,
+            },
+        )?;
+        self.queue.submit([encoder.finish()]);
+        Ok(())
+    }
+
+    /// Record the cell render compute pass writing into `target`.
+    fn encode_render_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &TextureView,
+        inputs: &RenderInputs,
+    ) -> anyhow::Result<()> {
+        let RenderInputs {
+            cell_metadata,
+            seg_entries,
+            segments,
+            path_paints,
+            gradient_stops,
+        } = *inputs;
         let metadata_buffer =
             create_storage_buffer_or_dummy(&self.device, "renderer metadata buffer", cell_metadata);
         let entries_buffer = create_storage_buffer_or_dummy(
@@ -175,12 +535,17 @@ impl ComputeRenderer {
             "renderer path paints buffer",
             path_paints,
         );
+        let gradient_stops_buffer = create_storage_buffer_or_dummy(
+            &self.device,
+            "renderer gradient stops buffer",
+            gradient_stops,
+        );
 
         let params = RenderParams {
             width: self.config.width,
             height: self.config.height,
             entries_len: seg_entries.len() as u32,
-            _pad: 0,
+            premultiply: self.premultiply as u32,
         };
         let params_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
             label: Some("renderer params buffer"),
@@ -214,69 +579,92 @@ impl ComputeRenderer {
                 },
                 BindGroupEntry {
                     binding: 5,
-                    resource: BindingResource::TextureView(&self.output_view),
+                    resource: BindingResource::TextureView(target),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: gradient_stops_buffer.as_entire_binding(),
                 },
             ],
         });
 
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("cell render pass"),
+            timestamp_writes: None,
+        });
+        let [wg_x, wg_y] = self.workgroup_size;
+        let x = self.config.width.div_ceil(wg_x);
+        let y = self.config.height.div_ceil(wg_y);
+        match self.dispatch {
+            RenderDispatch::PerPixel => {
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bg, &[]);
+                pass.dispatch_workgroups(x, y, 1);
+            }
+            RenderDispatch::PerCell => {
+                let clear_pipeline = self
+                    .clear_pipeline
+                    .as_ref()
+                    .expect("per-cell dispatch has a clear pipeline");
+                let clear_bg = self.device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("cell render clear bind group"),
+                    layout: &clear_pipeline.get_bind_group_layout(0),
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 4,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 5,
+                            resource: BindingResource::TextureView(target),
+                        },
+                    ],
+                });
+                pass.set_pipeline(clear_pipeline);
+                pass.set_bind_group(0, &clear_bg, &[]);
+                pass.dispatch_workgroups(x, y, 1);
+
+                if !cell_metadata.is_empty() {
+                    let [cx, cy, cz] = split_dispatch_3d(
+                        cell_metadata.len() as u32,
+                        self.device.limits().max_compute_workgroups_per_dimension,
+                    )?;
+                    pass.set_pipeline(&self.pipeline);
+                    pass.set_bind_group(0, &bg, &[]);
+                    pass.dispatch_workgroups(cx, cy, cz);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a copy of `texture` (at the configured size) into a mappable buffer.
+    fn encode_output_readback(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &Texture,
+    ) -> OutputReadback {
         let bytes_per_pixel = 4u32;
         let unpadded_bytes_per_row = self.config.width * bytes_per_pixel;
         let padded_bytes_per_row =
             unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
         let output_size = (padded_bytes_per_row * self.config.height) as u64;
-        let readback_buffer = self.device.create_buffer(&BufferDescriptor {
+        let buffer = self.device.create_buffer(&BufferDescriptor {
             label: Some("renderer readback buffer"),
             size: output_size,
             usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("renderer command encoder"),
-            });
-        {
-            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
-                label: Some("cell render pass"),
-                timestamp_writes: None,
-            });
-            pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &bg, &[]);
-            let x = self.config.width.div_ceil(RENDER_WG_SIZE_X);
-            let y = self.config.height.div_ceil(RENDER_WG_SIZE_Y);
-            pass.dispatch_workgroups(x, y, 1);
-        }
-
-        let mut frame_to_present: Option<SurfaceTexture> = None;
-        match surface.get_current_texture() {
-            Ok(frame) => {
-                {
-                    let view = frame.texture.create_view(&TextureViewDescriptor::default());
-                    self.blitter
-                        .copy(&self.device, &mut encoder, &self.output_view, &view);
-                }
-                frame_to_present = Some(frame);
-            }
-            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
-                surface.configure(&self.device, &self.config);
-            }
-            Err(SurfaceError::Timeout) => {}
-            Err(SurfaceError::OutOfMemory) => {
-                anyhow::bail!("surface out of memory");
-            }
-            Err(SurfaceError::Other) => {}
-        }
-
         encoder.copy_texture_to_buffer(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.output_texture,
+                texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::TexelCopyBufferInfo {
-                buffer: &readback_buffer,
+                buffer: &buffer,
                 layout: wgpu::TexelCopyBufferLayout {
                     offset: 0,
                     bytes_per_row: Some(padded_bytes_per_row),
@@ -289,12 +677,21 @@ impl ComputeRenderer {
                 depth_or_array_layers: 1,
             },
         );
-        self.queue.submit([encoder.finish()]);
-        if let Some(frame) = frame_to_present {
-            frame.present();
+        OutputReadback {
+            buffer,
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
         }
+    }
 
-        let slice = readback_buffer.slice(..);
+    /// Map a submitted readback buffer and strip the row padding.
+    fn read_output(&self, readback: OutputReadback) -> anyhow::Result<Vec<u8>> {
+        let OutputReadback {
+            buffer,
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+        } = readback;
+        let slice = buffer.slice(..);
         let (tx, rx) = channel();
         slice.map_async(MapMode::Read, move |res| {
             tx.send(res).unwrap();
@@ -303,8 +700,7 @@ impl ComputeRenderer {
         rx.recv()??;
 
         let data = slice.get_mapped_range();
-        let mut rgba =
-            vec![0u8; (self.config.width * self.config.height * bytes_per_pixel) as usize];
+        let mut rgba = vec![0u8; (unpadded_bytes_per_row * self.config.height) as usize];
         for row in 0..self.config.height as usize {
             let src_offset = row * padded_bytes_per_row as usize;
             let dst_offset = row * unpadded_bytes_per_row as usize;
@@ -312,11 +708,93 @@ impl ComputeRenderer {
                 .copy_from_slice(&data[src_offset..src_offset + unpadded_bytes_per_row as usize]);
         }
         drop(data);
-        readback_buffer.unmap();
+        buffer.unmap();
         Ok(rgba)
     }
 }
 
+struct OutputReadback {
+    buffer: Buffer,
+    unpadded_bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+}
+
+/// Pixel tile for each workgroup of the per-pixel passes on a device with `limits`:
+/// powers of two up to `MAX_RENDER_WG_SIZE` on each axis, with the longer side
+/// (the width on a tie) halved until the invocation limit is met.
+pub fn default_workgroup_size(limits: &wgpu::Limits) -> [u32; 2] {
+    let axis_size = |limit: u32| MAX_RENDER_WG_SIZE.min(1 << limit.max(1).ilog2());
+    let mut size = [
+        axis_size(limits.max_compute_workgroup_size_x),
+        axis_size(limits.max_compute_workgroup_size_y),
+    ];
+    while size[0] * size[1] > limits.max_compute_invocations_per_workgroup.max(1) {
+        let axis = if size[0] >= size[1] { 0 } else { 1 };
+        size[axis] /= 2;
+    }
+    size
+}
+
+fn workgroup_size_fits([x, y]: [u32; 2], limits: &wgpu::Limits) -> bool {
+    let max_invocations = limits.max_compute_invocations_per_workgroup;
+    x >= 1
+        && y >= 1
+        && x <= limits.max_compute_workgroup_size_x
+        && y <= limits.max_compute_workgroup_size_y
+        && x.checked_mul(y).is_some_and(|n| n <= max_invocations)
+}
+
+/// Compile the cell render shader and build the pipeline(s) for `dispatch`.
+fn create_render_pipelines(
+    device: &Device,
+    dispatch: RenderDispatch,
+    [wg_x, wg_y]: [u32; 2],
+) -> (ComputePipeline, Option<ComputePipeline>) {
+    let shader = load_with_common(
+        device, "cell render compute shader", include_str!("cell_render.wgsl"),
+    );
+    // Override constants of cell_render.wgsl, matched by the dispatch math.
+    let constants = [
+        ("RENDER_WG_SIZE_X", wg_x as f64),
+        ("RENDER_WG_SIZE_Y", wg_y as f64),
+    ];
+    let create = |label: &str, entry_point: &str| {
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(label),
+            layout: None,
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: None,
+        })
+    };
+    match dispatch {
+        RenderDispatch::PerPixel => (create("cell render pipeline", "main"), None),
+        RenderDispatch::PerCell => (
+            create("cell render per-cell pipeline", "render_cells"),
+            Some(create("cell render clear pipeline", "clear_output")),
+        ),
+    }
+}
+
+async fn request_renderer_device(adapter: &wgpu::Adapter) -> anyhow::Result<(Device, Queue)> {
+    let limits = adapter.limits();
+    adapter
+        .request_device(&DeviceDescriptor {
+            label: Some("gpu renderer device"),
+            required_features: Features::empty(),
+            required_limits: limits,
+            experimental_features: Default::default(),
+            memory_hints: Default::default(),
+            trace: Default::default(),
+        })
+        .await
+        .context("Failed to create renderer device")
+}
+
 fn create_output_texture(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
     let texture = device.create_texture(&TextureDescriptor {
         label: Some("cell render output texture"),
@@ -352,3 +830,368 @@ fn create_storage_buffer_or_dummy<T: Pod>(device: &Device, label: &str, data: &[
         usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
     })
 }
+
+#[cfg(test)]
+mod workgroup_size_tests {
+    use super::*;
+
+    fn limits(invocations: u32, max_x: u32, max_y: u32) -> wgpu::Limits {
+        wgpu::Limits {
+            max_compute_invocations_per_workgroup: invocations,
+            max_compute_workgroup_size_x: max_x,
+            max_compute_workgroup_size_y: max_y,
+            ..wgpu::Limits::default()
+        }
+    }
+
+    #[test]
+    fn default_workgroup_size_fits_device_limits() {
+        assert_eq!(default_workgroup_size(&wgpu::Limits::default()), [16, 16]);
+        assert_eq!(default_workgroup_size(&limits(64, 256, 256)), [8, 8]);
+        assert_eq!(default_workgroup_size(&limits(32, 256, 256)), [4, 8]);
+        assert_eq!(default_workgroup_size(&limits(256, 4, 256)), [4, 16]);
+        assert_eq!(default_workgroup_size(&limits(100, 256, 256)), [8, 8]);
+        assert_eq!(default_workgroup_size(&limits(0, 0, 0)), [1, 1]);
+        for l in [
+            limits(64, 256, 256),
+            limits(256, 4, 256),
+            limits(100, 256, 256),
+        ] {
+            assert!(workgroup_size_fits(default_workgroup_size(&l), &l));
+        }
+        assert!(!workgroup_size_fits([32, 16], &wgpu::Limits::default()));
+        assert!(!workgroup_size_fits([0, 8], &wgpu::Limits::default()));
+        assert!(!workgroup_size_fits(
+            [u32::MAX, 2],
+            &limits(u32::MAX, u32::MAX, u32::MAX)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "gpu-tests"))]
+mod tests {
+    use super::*;
+    use crate::geometry::rect::Rect;
+    use crate::gpu::quad_tree::build_quadtree;
+    use crate::path::IDENTITY_AFFINE;
+    use crate::quad_tree::QuadTree;
+    use crate::render::{RenderOptions, render};
+    use crate::seg_entry::init_root_seg_entries;
+    use crate::test_utils::{TestScene, pixel, solid};
+
+    fn render_offscreen(
+        renderer: &ComputeRenderer,
+        scene: &TestScene,
+        root: Rect,
+        max_depth: u8,
+    ) -> anyhow::Result<Vec<u8>> {
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let (metadata, seg_entries) =
+            build_quadtree(root, root_entries, max_depth, 1, &scene.abs_segments)?;
+        let (path_paints, gradient_stops) = build_path_paints(&scene.abs_paths, &scene.paints);
+        renderer.render_to_rgba_headless(
+            &metadata,
+            &seg_entries,
+            &scene.abs_segments,
+            &path_paints,
+            &gradient_stops,
+        )
+    }
+
+    #[test]
+    fn every_pixel_is_rendered_at_any_workgroup_size() {
+        // Not a multiple of any tile edge, so every dispatch has partial workgroups.
+        let (w, h) = (37u32, 29u32);
+        let color = [20, 200, 90, 255];
+        let mut scene = TestScene::new();
+        scene.push_rect(0.0, 0.0, w as f32, h as f32, solid(color));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+
+        let mut renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let default_size = renderer.workgroup_size();
+        for size in [default_size, [8, 8], [16, 4], [1, 32]] {
+            renderer = renderer.with_workgroup_size(size).unwrap();
+            for dispatch in [RenderDispatch::PerPixel, RenderDispatch::PerCell] {
+                renderer = renderer.with_dispatch(dispatch);
+                let pixels = render_offscreen(&renderer, &scene, root, 2).unwrap();
+                for y in 0..h {
+                    for x in 0..w {
+                        assert_eq!(
+                            pixel(&pixels, w, x, y),
+                            color,
+                            "({x}, {y}) with {size:?} {dispatch:?}"
+                        );
+                    }
+                }
+            }
+        }
+        assert!(renderer.with_workgroup_size([4096, 4096]).is_err());
+    }
+
+    #[test]
+    fn linear_gradient_matches_cpu() {
+        let (w, h) = (32u32, 32u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(
+            4.0,
+            4.0,
+            28.0,
+            28.0,
+            Paint::LinearGradient {
+                start: [4.0, 0.0],
+                end: [28.0, 0.0],
+                stops: vec![
+                    GradientStop {
+                        offset: 0.0,
+                        rgba: [255, 0, 0, 255],
+                    },
+                    GradientStop {
+                        offset: 1.0,
+                        rgba: [0, 0, 255, 255],
+                    },
+                ],
+                inv_transform: IDENTITY_AFFINE,
+            },
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let gpu_pixels = render_offscreen(&renderer, &scene, root, 2).unwrap();
+
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let mut cpu_pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut cpu_pixels,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+
+        for y in 5..27 {
+            for x in 5..27 {
+                let cpu = pixel(&cpu_pixels, w, x, y);
+                let gpu = pixel(&gpu_pixels, w, x, y);
+                for c in 0..4 {
+                    assert!(
+                        cpu[c].abs_diff(gpu[c]) <= 1,
+                        "({x}, {y}): cpu {cpu:?} gpu {gpu:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn per_cell_dispatch_matches_per_pixel_and_cpu() {
+        let (w, h) = (48u32, 48u32);
+        let mut scene = TestScene::new();
+        // Enough edges that a shallow tree leaves cells spanning several entry chunks.
+        let circle: Vec<(f32, f32)> = (0..150)
+            .map(|i| {
+                let a = i as f32 / 150.0 * std::f32::consts::TAU;
+                (24.0 + 18.0 * a.cos(), 24.0 + 18.0 * a.sin())
+            })
+            .collect();
+        scene
+            .push_polygon(&circle, solid([0, 0, 255, 255]))
+            .push_rect(3.0, 3.0, 29.0, 21.0, solid([255, 0, 0, 255]))
+            .push_polygon(&[(6.0, 45.0), (45.0, 10.0), (45.0, 45.0)], solid([0, 255, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+
+        let per_pixel = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let per_cell = pollster::block_on(ComputeRenderer::new_headless(w, h))
+            .unwrap()
+            .with_dispatch(RenderDispatch::PerCell);
+
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let mut cpu_pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut cpu_pixels,
+            w,
+            h,
+            &RenderOptions {
+                background: Some([0, 0, 0, 255]),
+                ..RenderOptions::default()
+            },
+        );
+
+        for max_depth in [1, 3] {
+            let expected = render_offscreen(&per_pixel, &scene, root, max_depth).unwrap();
+            let pixels = render_offscreen(&per_cell, &scene, root, max_depth).unwrap();
+            assert_eq!(pixels, expected, "max_depth {max_depth}");
+            // Away from the edges, where sample placement cannot differ.
+            for (x, y) in [(1, 1), (10, 10), (24, 30), (20, 12), (42, 40), (40, 16)] {
+                assert_eq!(
+                    pixel(&pixels, w, x, y),
+                    pixel(&cpu_pixels, w, x, y),
+                    "max_depth {max_depth} ({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn self_intersecting_fill_matches_cpu_for_both_rules() {
+        let (w, h) = (48u32, 48u32);
+        // Pentagram: its centre pentagon has winding 2.
+        let star: Vec<(f32, f32)> = (0..5)
+            .map(|i| {
+                let a = (i * 2) as f32 / 5.0 * std::f32::consts::TAU - std::f32::consts::FRAC_PI_2;
+                (24.0 + 21.0 * a.cos(), 25.0 + 21.0 * a.sin())
+            })
+            .collect();
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+
+        for fill_rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            let mut scene = TestScene::new();
+            scene.push_polygon(&star, solid([255, 0, 0, 255]));
+            scene.abs_paths[0].fill_rule = fill_rule;
+
+            let gpu_pixels = render_offscreen(&renderer, &scene, root, 3).unwrap();
+            let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+            let mut cpu_pixels = vec![0u8; (w * h * 4) as usize];
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut cpu_pixels,
+                w,
+                h,
+                &RenderOptions {
+                    background: Some([0, 0, 0, 255]),
+                    ..RenderOptions::default()
+                },
+            );
+
+            let filled = |pixels: &[u8]| -> Vec<(u32, u32)> {
+                (0..h)
+                    .flat_map(|y| (0..w).map(move |x| (x, y)))
+                    .filter(|&(x, y)| pixel(pixels, w, x, y)[0] > 0)
+                    .collect()
+            };
+            assert_eq!(filled(&gpu_pixels), filled(&cpu_pixels), "{fill_rule:?}");
+            let centre_filled = pixel(&gpu_pixels, w, 24, 25)[0] > 0;
+            assert_eq!(centre_filled, fill_rule == FillRule::NonZero, "{fill_rule:?}");
+        }
+    }
+
+    #[test]
+    fn missing_paint_renders_opaque_black() {
+        let (w, h) = (16u32, 16u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(0.0, 0.0, 16.0, 16.0, solid([0, 255, 0, 255]))
+            .push_rect(4.0, 4.0, 12.0, 12.0, solid([255, 0, 0, 128]));
+        // The second path's paint_id now points past the end.
+        scene.paints.truncate(1);
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let pixels = render_offscreen(&renderer, &scene, root, 3).unwrap();
+        assert_eq!(pixel(&pixels, w, 8, 8), [0, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, w, 1, 1), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn only_horizontal_segments_render_background() {
+        let (w, h) = (32u32, 32u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(&[(2.0, 8.0), (30.0, 8.0)], solid([255, 0, 0, 255]))
+            .push_polygon(&[(0.0, 16.0), (32.0, 16.0)], solid([0, 255, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let pixels = render_offscreen(&renderer, &scene, root, 3).unwrap();
+        assert!(pixels.chunks(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn headless_renderer_reads_back_full_frame() {
+        let (w, h) = (24u32, 16u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 12.0, 12.0, solid([0, 255, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let pixels = render_offscreen(&renderer, &scene, root, 2).unwrap();
+        assert_eq!(pixels.len(), (w * h * 4) as usize);
+        assert_eq!(pixel(&pixels, w, 8, 8), [0, 255, 0, 255]);
+        assert_eq!(pixel(&pixels, w, 20, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn premultiply_scales_rgb_by_alpha() {
+        let (w, h) = (8u32, 8u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(0.0, 0.0, 8.0, 8.0, solid([255, 255, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+
+        let mut renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        renderer.set_premultiply(true);
+        let pixels = render_offscreen(&renderer, &scene, root, 0).unwrap();
+        assert_eq!(pixel(&pixels, w, 4, 4), [128; 4]);
+    }
+
+    #[test]
+    fn render_into_external_view_matches_internal_output() {
+        let (w, h) = (32u32, 32u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(2.0, 2.0, 20.0, 20.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 30.0), (30.0, 4.0), (30.0, 30.0)],
+                solid([0, 0, 255, 200]),
+            );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let renderer = pollster::block_on(ComputeRenderer::new_headless(w, h)).unwrap();
+        let expected = render_offscreen(&renderer, &scene, root, 3).unwrap();
+
+        // Stands in for a texture owned by a host application.
+        let external = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("external target texture"),
+            size: Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let external_view = external.create_view(&TextureViewDescriptor::default());
+
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let (metadata, seg_entries) =
+            build_quadtree(root, root_entries, 3, 1, &scene.abs_segments).unwrap();
+        let (path_paints, gradient_stops) = build_path_paints(&scene.abs_paths, &scene.paints);
+        renderer
+            .render_into_view(
+                &external_view,
+                &metadata,
+                &seg_entries,
+                &scene.abs_segments,
+                &path_paints,
+                &gradient_stops,
+            )
+            .unwrap();
+
+        let mut encoder = renderer.device.create_command_encoder(&Default::default());
+        let readback = renderer.encode_output_readback(&mut encoder, &external);
+        renderer.queue.submit([encoder.finish()]);
+        let pixels = renderer.read_output(readback).unwrap();
+        assert_eq!(pixels, expected);
+        assert_eq!(pixel(&pixels, w, 5, 5), [255, 0, 0, 255]);
+    }
+}
@@ -1,6 +1,7 @@
 // Depends on: common.wgsl
 
-const WG_SIZE: u32 = 2u;
+// Must match WG_SIZE in subdivide_seg_entry.rs.
+const WG_SIZE: u32 = 256u;
 
 fn inclusive_scan_block(lid: u32) {
     var offset = 1u;
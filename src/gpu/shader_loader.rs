@@ -26,3 +26,39 @@ pub fn load_with_common(device: &Device, label: &str, main_source: &str) -> Shad
 pub fn load_with_split_helpers(device: &Device, label: &str, main_source: &str) -> ShaderModule {
     load_shader(device, label, &[COMMON, SPLIT_HELPERS], main_source)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::naga;
+
+    /// Parse and validate a shader source the same way `load_shader` assembles it.
+    fn validate(includes: &[&str], main_source: &str) {
+        let mut combined = String::new();
+        for include in includes {
+            combined.push_str(include);
+            combined.push('\n');
+        }
+        combined.push_str(main_source);
+        let module = naga::front::wgsl::parse_str(&combined)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&combined)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&combined)));
+    }
+
+    #[test]
+    fn shaders_validate() {
+        validate(&[COMMON], include_str!("cell_render.wgsl"));
+        validate(&[COMMON], include_str!("quadcell_split.wgsl"));
+        validate(&[COMMON, SPLIT_HELPERS], include_str!("build_split_entries.wgsl"));
+        validate(&[COMMON], include_str!("winding_block_sum.wgsl"));
+        validate(&[COMMON], include_str!("scan_entry_offsets.wgsl"));
+        validate(&[COMMON, SPLIT_HELPERS], include_str!("split_to_seg_entry.wgsl"));
+        validate(&[COMMON], include_str!("quadcell_update_metadata.wgsl"));
+        validate(&[COMMON], include_str!("prepare_level.wgsl"));
+    }
+}
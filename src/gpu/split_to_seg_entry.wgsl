@@ -1,6 +1,7 @@
 // Depends on: common.wgsl, split_helpers.wgsl
 
-const WG_SIZE: u32 = 2u;
+// Must match WG_SIZE in subdivide_seg_entry.rs.
+const WG_SIZE: u32 = 256u;
 
 @group(0) @binding(0) var<storage, read_write> seg_entries: array<SegEntry>;
 @group(0) @binding(1) var<storage, read_write> split_entries: array<SplitEntry>;
@@ -78,7 +79,7 @@ fn main(
         }
 
         if (offset_idx == offsets_length - 1u) {
-            result_info[0].seg_entries_length = curr_offset;
+            result_info[0].emitted_entries_length = curr_offset;
         }
     }
 }
@@ -4,27 +4,34 @@ use crate::geometry::rect::Rect;
 use crate::gpu::init::init_wgpu;
 use crate::gpu::quad_tree::CellMetadata;
 use bytemuck::{bytes_of, AnyBitPattern, Pod, Zeroable};
+use std::cell::{Cell, RefCell};
 use std::sync::mpsc::channel;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::wgt::BufferDescriptor;
 use crate::gpu::shader_loader::{load_with_common, load_with_split_helpers};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutEntry, BindingType, Buffer,
-    BufferBindingType, BufferUsages, ComputePipelineDescriptor, ShaderStages,
+    BufferBindingType, BufferUsages, ComputePassDescriptor, ComputePassTimestampWrites,
+    ComputePipeline, ComputePipelineDescriptor, QuerySet, ShaderStages,
 };
 
-const WG_SIZE: u32 = 2;
-
-fn split_dispatch_3d(workgroups_needed: u32, max_dim: u32) -> [u32; 3] {
-    let x = workgroups_needed.min(max_dim).max(1);
-    let remaining_after_x = (workgroups_needed + x - 1) / x;
-    let y = remaining_after_x.min(max_dim);
-
-    let xy = (x as u64) * (y as u64);
-    let z = ((workgroups_needed as u64) + xy - 1) / xy;
-    assert!(z <= max_dim as u64, "dispatch exceeds max_dim^3");
-
-    [x, y, z as u32]
+/// Threads per workgroup of the per-entry kernels; must match `WG_SIZE` in
+/// build_split_entries.wgsl, winding_block_sum.wgsl, scan_entry_offsets.wgsl and
+/// split_to_seg_entry.wgsl.
+const WG_SIZE: u32 = 256;
+
+/// Spread `workgroups_needed` workgroups over a dispatch of at most `max_dim` per
+/// dimension; an error when they do not fit in `max_dim^3`.
+pub(crate) fn split_dispatch_3d(workgroups_needed: u32, max_dim: u32) -> anyhow::Result<[u32; 3]> {
+    let (needed, max_dim_64) = (workgroups_needed as u64, max_dim as u64);
+    let x = needed.min(max_dim_64).max(1);
+    let y = needed.div_ceil(x).min(max_dim_64);
+    let z = needed.div_ceil((x * y).max(1));
+    anyhow::ensure!(
+        z <= max_dim_64,
+        "dispatch of {workgroups_needed} workgroups exceeds {max_dim}^3"
+    );
+    Ok([x as u32, y as u32, z as u32])
 }
 
 #[repr(C)]
@@ -34,7 +41,10 @@ pub struct SplitResultInfo {
     // Minimum entry count threshold for splitting a cell further.
     // Cells with entry_count <= min_seg are treated as leaves in quadcell_split.wgsl.
     pub min_seg: u32,
-    pub _pad: [u32; 2],
+    // Child entries written by split_to_seg_entry.wgsl. Kept apart from
+    // seg_entries_length, which the other threads of that kernel are still reading.
+    pub emitted_entries_length: u32,
+    pub _pad: u32,
 }
 
 #[repr(C)]
@@ -45,6 +55,22 @@ struct ScanParams {
     _pad: [u32; 2],
 }
 
+/// Read by prepare_level.wgsl to lay out one level's scan parameters and
+/// indirect dispatch sizes.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct LevelLayout {
+    max_workgroups_per_dim: u32,
+    num_winding_levels: u32,
+    num_offset_levels: u32,
+    _pad: u32,
+}
+
+/// Indirect dispatch slots written by prepare_level.wgsl, each three `u32`s: the
+/// per-entry kernels, one per winding then offset scan level, then update_metadata.
+const ENTRIES_DISPATCH_SLOT: u64 = 0;
+const DISPATCH_ARGS_BYTES: u64 = 3 * size_of::<u32>() as u64;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct WindingBlockInfo {
@@ -56,6 +82,90 @@ pub struct WindingBlockInfo {
     tail_winding: [i32; 4],
 }
 
+/// Total bytes `QuadTreeGpuContext::new` allocates in GPU buffers (including readback
+/// copies) for `num_seg_entries` root entries, `num_segments` segments and `max_depth`.
+///
+/// Most buffers scale with `num_seg_entries * 4^max_depth`, so this grows quickly;
+/// use it to pick a depth that fits before creating the context.
+pub fn required_buffer_bytes(
+    num_seg_entries: usize,
+    num_segments: usize,
+    max_depth: u8,
+) -> anyhow::Result<u64> {
+    let overflow = || anyhow::anyhow!("GPU buffer size overflows u64 for max_depth {max_depth}");
+    let pow4 = |exp: u8| 4u64.checked_pow(exp as u32).ok_or_else(overflow);
+    let mul = |a: u64, b: usize| a.checked_mul(b as u64).ok_or_else(overflow);
+
+    let initial_entries = num_seg_entries.max(1) as u64;
+    let max_seg_entries = initial_entries
+        .checked_mul(pow4(max_depth)?)
+        .ok_or_else(overflow)?;
+    let max_split_entries = initial_entries
+        .checked_mul(pow4(max_depth.saturating_sub(1))?)
+        .ok_or_else(overflow)?;
+    let max_offsets = mul(max_split_entries, 4)?;
+
+    // Each entry below is (bytes, copies): storage buffers that are read back
+    // have a same-sized MAP_READ twin.
+    let mut buffers: Vec<(u64, u64)> = vec![
+        (mul(max_seg_entries, size_of::<SegEntry>())?, 2),
+        (
+            mul(pow4(max_depth)?, size_of::<CellMetadata>())?.max(size_of::<CellMetadata>() as u64),
+            3,
+        ),
+        ((num_segments * size_of::<AbstractLineSegment>()) as u64, 1),
+        (mul(max_split_entries, size_of::<SplitEntry>())?, 2),
+        (
+            mul(max_offsets, size_of::<u32>())?.max(size_of::<u32>() as u64),
+            2,
+        ),
+        (size_of::<SplitResultInfo>() as u64, 2),
+    ];
+
+    // Winding block sums: level 0, one buffer per recursive scan level, and a sentinel.
+    let mut winding_levels = vec![mul(max_split_entries, size_of::<WindingBlockInfo>())?.max(32)];
+    let mut level_elms = max_split_entries as usize;
+    while level_elms > WG_SIZE as usize {
+        let num_blocks = level_elms.div_ceil(WG_SIZE as usize).max(1);
+        winding_levels.push(((num_blocks * size_of::<WindingBlockInfo>()) as u64).max(32));
+        level_elms = num_blocks;
+    }
+    winding_levels.push(32);
+    let num_winding_scans = winding_levels.len() - 1;
+    buffers.extend(winding_levels.into_iter().map(|bytes| (bytes, 2)));
+
+    // Offset block sums: level 0 is the offsets buffer itself, plus a sentinel.
+    let mut num_offset_sums = 1;
+    let mut offset_level_elms = max_offsets as usize;
+    while offset_level_elms > WG_SIZE as usize {
+        let num_blocks = offset_level_elms.div_ceil(WG_SIZE as usize).max(1);
+        buffers.push((
+            ((num_blocks * size_of::<u32>()) as u64).max(size_of::<u32>() as u64),
+            1,
+        ));
+        num_offset_sums += 1;
+        offset_level_elms = num_blocks;
+    }
+    buffers.push((size_of::<u32>() as u64, 1));
+
+    let num_scan_params = (num_winding_scans + num_offset_sums) as u64;
+    buffers.push((size_of::<ScanParams>() as u64, num_scan_params));
+    // Indirect builds: every level's scan params in one buffer, the dispatch slots
+    // and the level layout.
+    buffers.push((size_of::<ScanParams>() as u64 * num_scan_params, 1));
+    buffers.push((DISPATCH_ARGS_BYTES * (num_scan_params + 2), 1));
+    buffers.push((size_of::<LevelLayout>() as u64, 1));
+
+    buffers
+        .into_iter()
+        .try_fold(0u64, |total, (bytes, copies)| {
+            bytes
+                .checked_mul(copies)
+                .and_then(|b| total.checked_add(b))
+                .ok_or_else(overflow)
+        })
+}
+
 struct Resources {
     // Ping-pong metadata buffers: depth % 2 selects which is input vs output.
     cell_metadata_buffer_1: wgpu::Buffer,
@@ -68,6 +178,10 @@ struct Resources {
     winding_scan_params_buffers: Vec<Buffer>,
     offset_block_sum_buffers: Vec<Buffer>,
     offset_scan_params_buffers: Vec<Buffer>,
+    // Written on the GPU by prepare_level.wgsl for indirect builds.
+    level_scan_params_buffer: wgpu::Buffer,
+    dispatch_args_buffer: wgpu::Buffer,
+    level_layout_buffer: wgpu::Buffer,
     result_info_buffer: wgpu::Buffer,
     winding_block_sum_readback_buffers: Vec<Buffer>,
     split_entries_readback_buffer: wgpu::Buffer,
@@ -254,11 +368,43 @@ impl Resources {
         let winding_scan_params_buffers = (0..winding_block_sum_buffers.len().saturating_sub(1))
             .map(|_| create_scan_params_buffer("winding scan params buffer"))
             .collect();
-        let offset_scan_params_buffers =
+        let offset_scan_params_buffers: Vec<Buffer> =
             (0..(1 + offset_block_sum_buffers.len()).saturating_sub(1))
                 .map(|_| create_scan_params_buffer("offset scan params buffer"))
                 .collect();
 
+        let num_winding_levels = winding_block_sum_buffers.len() - 1;
+        let num_offset_levels = offset_scan_params_buffers.len();
+        let num_scan_levels = (num_winding_levels + num_offset_levels) as u64;
+        let level_scan_params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("level scan params buffer"),
+            size: check_storage_size(
+                "level scan params buffer",
+                size_of::<ScanParams>() as u64 * num_scan_levels,
+            ),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let dispatch_args_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("dispatch args buffer"),
+            size: check_storage_size(
+                "dispatch args buffer",
+                DISPATCH_ARGS_BYTES * (num_scan_levels + 2),
+            ),
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+        let level_layout_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("level layout buffer"),
+            contents: bytes_of(&LevelLayout {
+                max_workgroups_per_dim: limits.max_compute_workgroups_per_dimension,
+                num_winding_levels: num_winding_levels as u32,
+                num_offset_levels: num_offset_levels as u32,
+                _pad: 0,
+            }),
+            usage: BufferUsages::STORAGE,
+        });
+
         let result_info_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("result info buffer"),
             size: size_of::<SplitResultInfo>() as u64,
@@ -320,6 +466,9 @@ impl Resources {
             winding_scan_params_buffers,
             offset_block_sum_buffers,
             offset_scan_params_buffers,
+            level_scan_params_buffer,
+            dispatch_args_buffer,
+            level_layout_buffer,
             result_info_buffer,
             winding_block_sum_readback_buffers,
             cell_offsets_readback_buffer,
@@ -341,6 +490,7 @@ struct Pipelines {
     mark_tail_winding_offsets: wgpu::ComputePipeline,
     add_winding_carry: wgpu::ComputePipeline,
     update_metadata: wgpu::ComputePipeline,
+    prepare_level: wgpu::ComputePipeline,
 }
 
 impl Pipelines {
@@ -363,6 +513,9 @@ impl Pipelines {
         let update_metadata_shader = load_with_common(
             device, "update metadata shader", include_str!("quadcell_update_metadata.wgsl"),
         );
+        let prepare_level_shader = load_with_common(
+            device, "prepare level shader", include_str!("prepare_level.wgsl"),
+        );
 
         let quadcell_split = device.create_compute_pipeline(&ComputePipelineDescriptor {
             label: Some("quadcell split pipeline"),
@@ -484,6 +637,14 @@ impl Pipelines {
             compilation_options: Default::default(),
             cache: Default::default(),
         });
+        let prepare_level = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("prepare level pipeline"),
+            layout: None,
+            module: &prepare_level_shader,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: Default::default(),
+        });
         Self {
             quadcell_split,
             build_split_entries: build_split,
@@ -494,6 +655,7 @@ impl Pipelines {
             add_offset_carry,
             emit_seg_entries,
             update_metadata,
+            prepare_level,
         }
     }
 }
@@ -513,6 +675,7 @@ struct BindGroups {
     emit_result: wgpu::BindGroup,
     winding_scan_bgs: Vec<wgpu::BindGroup>,
     update_metadata: [wgpu::BindGroup; 2],
+    prepare_level: wgpu::BindGroup,
 }
 
 impl BindGroups {
@@ -529,6 +692,9 @@ impl BindGroups {
             winding_scan_params_buffers,
             offset_block_sum_buffers,
             offset_scan_params_buffers,
+            level_scan_params_buffer,
+            dispatch_args_buffer,
+            level_layout_buffer,
             // result info
             result_info_buffer,
             ..
@@ -542,6 +708,7 @@ impl BindGroups {
             scan_offset_block,
             emit_seg_entries,
             update_metadata,
+            prepare_level,
             ..
         } = pipelines;
 
@@ -668,6 +835,17 @@ impl BindGroups {
             ],
         });
 
+        let prepare_level = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("prepare level bind group"),
+            layout: &prepare_level.get_bind_group_layout(0),
+            entries: &[
+                bg_entry(0, result_info_buffer),
+                bg_entry(1, level_scan_params_buffer),
+                bg_entry(2, dispatch_args_buffer),
+                bg_entry(3, level_layout_buffer),
+            ],
+        });
+
         Self {
             split_quadcell: [split_quadcell_ping, split_quadcell_pong],
             split_seg_entry: [split_seg_entry_ping, split_seg_entry_pong],
@@ -676,11 +854,12 @@ impl BindGroups {
             offset_scan_bgs,
             emit_result,
             update_metadata: [update_metadata_ping, update_metadata_pong],
+            prepare_level,
         }
     }
 }
 
-fn dispatch_for_items(items: u32, max_dim: u32) -> [u32; 3] {
+fn dispatch_for_items(items: u32, max_dim: u32) -> anyhow::Result<[u32; 3]> {
     let wg = items.max(1).div_ceil(WG_SIZE);
     split_dispatch_3d(wg, max_dim)
 }
@@ -700,6 +879,89 @@ fn hierarchical_level_counts(initial: u32, levels: usize) -> Vec<u32> {
     out
 }
 
+/// GPU time of one kernel dispatch, as measured by `QuadTreeGpuContext::read_timings`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KernelTiming {
+    /// Name of the kernel's pipeline, e.g. `"scan_winding_block"`.
+    pub kernel: &'static str,
+    /// Depth of the level the kernel subdivided.
+    pub depth: u8,
+    /// Hierarchical scan level, for the scan kernels that run once per level.
+    pub scan_level: Option<usize>,
+    pub micros: f64,
+}
+
+/// One dispatch of a level: the kernel, its bind group and its workgroup count,
+/// `None` for an indirect dispatch from the args at `slot`.
+struct Kernel<'a> {
+    name: &'static str,
+    scan_level: Option<usize>,
+    pipeline: &'a ComputePipeline,
+    bind_group: &'a BindGroup,
+    workgroups: Option<[u32; 3]>,
+    slot: u64,
+}
+
+/// Timestamp queries around each kernel of a `process_level` or
+/// `process_levels_indirect` call.
+struct KernelProfiler {
+    query_set: QuerySet,
+    /// Two queries per kernel: its start, then its end.
+    capacity: u32,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    /// `(name, depth, scan_level)` of the kernels timed by the last call, in query
+    /// order.
+    kernels: RefCell<Vec<TimedKernel>>,
+}
+
+type TimedKernel = (&'static str, u8, Option<usize>);
+
+impl KernelProfiler {
+    fn new(device: &wgpu::Device, max_kernels: u32) -> Self {
+        let capacity = max_kernels * 2;
+        let bytes = capacity as u64 * size_of::<u64>() as u64;
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("kernel timestamp query set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: capacity,
+            }),
+            capacity,
+            resolve_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("kernel timestamp resolve buffer"),
+                size: bytes,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("kernel timestamp readback buffer"),
+                size: bytes,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            kernels: RefCell::new(vec![]),
+        }
+    }
+}
+
+/// Resolve the timestamps of the `timed` kernels for `read_timings`.
+fn resolve_timings(
+    encoder: &mut wgpu::CommandEncoder,
+    profiler: &KernelProfiler,
+    timed: Vec<TimedKernel>,
+) {
+    if !timed.is_empty() {
+        encoder.resolve_query_set(
+            &profiler.query_set,
+            0..timed.len() as u32 * 2,
+            &profiler.resolve_buffer,
+            0,
+        );
+    }
+    *profiler.kernels.borrow_mut() = timed;
+}
+
 pub struct QuadTreeGpuContext {
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -709,34 +971,37 @@ pub struct QuadTreeGpuContext {
     num_seg_entries: u32,
     // Minimum entry count for a cell to be split further (passed to quadcell_split.wgsl).
     min_seg: u32,
+    submissions: Cell<u32>,
+    /// Deepest level the buffers are sized for.
+    max_depth: u8,
+    /// Set by `enable_profiling`.
+    profiler: Option<KernelProfiler>,
 }
 
 impl QuadTreeGpuContext {
+    /// `memory_budget`, if set, is the maximum number of bytes the context may
+    /// allocate in GPU buffers; see [`required_buffer_bytes`].
     pub async fn new(
         seg_entries: &[SegEntry],
         segments: &[AbstractLineSegment],
         parent_bound: &Rect,
         max_depth: u8,
         min_seg: u32,
+        memory_budget: Option<u64>,
     ) -> anyhow::Result<Self> {
+        if let Some(budget) = memory_budget {
+            let required = required_buffer_bytes(seg_entries.len(), segments.len(), max_depth)?;
+            anyhow::ensure!(
+                required <= budget,
+                "GPU quadtree build needs {required} bytes for max_depth {max_depth}, over the budget of {budget} bytes"
+            );
+        }
         let (device, queue) = init_wgpu().await;
 
         let pipelines = Pipelines::new(&device);
         let resources = Resources::new(&device, &seg_entries, &segments, max_depth);
         let bind_groups = BindGroups::new(&device, &resources, &pipelines);
-        // Write initial data
-        let root_meta = CellMetadata::new(parent_bound, 0, seg_entries.len() as u32);
-        queue.write_buffer(
-            &resources.cell_metadata_buffer_1,
-            0,
-            bytemuck::cast_slice(&[root_meta]),
-        );
-        queue.write_buffer(
-            &resources.seg_entries_buffer,
-            0,
-            bytemuck::cast_slice(seg_entries),
-        );
-        Ok(Self {
+        let ctx = Self {
             device,
             queue,
             pipelines,
@@ -744,18 +1009,101 @@ impl QuadTreeGpuContext {
             bind_groups,
             num_seg_entries: seg_entries.len() as u32,
             min_seg,
-        })
+            submissions: Cell::new(0),
+            max_depth,
+            profiler: None,
+        };
+        ctx.write_root(seg_entries, parent_bound);
+        Ok(ctx)
+    }
+
+    /// Swap in another drawing, e.g. the same one flattened for a new size,
+    /// keeping the device and the compiled pipelines. The buffers are allocated
+    /// again for the new entry and segment counts and `max_depth`.
+    pub fn reload(
+        &mut self,
+        seg_entries: &[SegEntry],
+        segments: &[AbstractLineSegment],
+        parent_bound: &Rect,
+        max_depth: u8,
+    ) {
+        self.resources = Resources::new(&self.device, seg_entries, segments, max_depth);
+        self.bind_groups = BindGroups::new(&self.device, &self.resources, &self.pipelines);
+        self.num_seg_entries = seg_entries.len() as u32;
+        self.max_depth = max_depth;
+        // The profiler is sized for the old scan levels and depth.
+        if self.profiler.take().is_some() {
+            self.enable_profiling();
+        }
+        self.write_root(seg_entries, parent_bound);
+    }
+
+    /// Write the root cell and its entries, the input of the first level.
+    fn write_root(&self, seg_entries: &[SegEntry], parent_bound: &Rect) {
+        let root_meta = CellMetadata::new(parent_bound, 0, seg_entries.len() as u32);
+        self.queue.write_buffer(
+            &self.resources.cell_metadata_buffer_1,
+            0,
+            bytemuck::cast_slice(&[root_meta]),
+        );
+        self.queue.write_buffer(
+            &self.resources.seg_entries_buffer,
+            0,
+            bytemuck::cast_slice(seg_entries),
+        );
+    }
+
+    /// Time every kernel dispatch of the following `process_level` and
+    /// `process_levels_indirect` calls with timestamp queries, for `read_timings`.
+    /// Each kernel then runs in a compute pass of its own; the prepare_level passes
+    /// of an indirect build are not timed. Returns `false`, leaving profiling off, when the device
+    /// lacks `Features::TIMESTAMP_QUERY`.
+    pub fn enable_profiling(&mut self) -> bool {
+        let features = self.device.features();
+        if !features.contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return false;
+        }
+        // quadcell_split, build_split_entries, mark_tail, emit and update_metadata,
+        // plus at most a scan and a carry kernel per scan level.
+        let scan_levels =
+            self.bind_groups.winding_scan_bgs.len() + self.bind_groups.offset_scan_bgs.len();
+        let level_kernels = 5 + 2 * scan_levels as u32;
+        let max_kernels = level_kernels * self.max_depth.max(1) as u32;
+        self.profiler = Some(KernelProfiler::new(&self.device, max_kernels));
+        true
+    }
+
+    /// GPU time of each kernel of the last `process_level` or
+    /// `process_levels_indirect` call, in dispatch order. Empty when profiling is off
+    /// or no level has run since it was enabled.
+    pub fn read_timings(&self) -> anyhow::Result<Vec<KernelTiming>> {
+        let Some(profiler) = &self.profiler else {
+            return Ok(vec![]);
+        };
+        let kernels = profiler.kernels.borrow();
+        if kernels.is_empty() {
+            return Ok(vec![]);
+        }
+        let stamps = self.readback::<u64>(&profiler.resolve_buffer, &profiler.readback_buffer)?;
+        let period_ns = self.queue.get_timestamp_period() as f64;
+        Ok(kernels
+            .iter()
+            .zip(stamps.chunks_exact(2))
+            .map(|(&(kernel, depth, scan_level), stamp)| KernelTiming {
+                kernel,
+                depth,
+                scan_level,
+                micros: stamp[1].saturating_sub(stamp[0]) as f64 * period_ns / 1000.0,
+            })
+            .collect())
     }
 
     /// Run one level of quad-tree subdivision on the GPU.
     ///
     /// `num_entries` is the actual live entry count for this depth; it is written into
     /// `result_info` before any dispatch so shaders do not have to rely on `arrayLength()`.
-    pub fn process_level(&self, depth: u8, num_cells: u32, num_entries: u32) {
-        let max_dim = self.device.limits().max_compute_workgroups_per_dimension;
-        let ping = (depth % 2) as usize;
+    pub fn process_level(&self, depth: u8, num_cells: u32, num_entries: u32) -> anyhow::Result<()> {
         let num_offsets = num_entries.saturating_mul(4);
-        let max_result_entries = num_offsets; // each entry can split into at most 4 child entries
         let winding_levels =
             hierarchical_level_counts(num_entries, self.bind_groups.winding_scan_bgs.len());
         let offset_levels =
@@ -768,7 +1116,8 @@ impl QuadTreeGpuContext {
             bytemuck::cast_slice(&[SplitResultInfo {
                 seg_entries_length: num_entries,
                 min_seg: self.min_seg,
-                _pad: [0; 2],
+                emitted_entries_length: 0,
+                _pad: 0,
             }]),
         );
         for (i, &level_len) in winding_levels.iter().enumerate() {
@@ -795,72 +1144,286 @@ impl QuadTreeGpuContext {
         }
 
         let mut encoder = self.device.create_command_encoder(&Default::default());
+        let kernels = self.level_kernels(depth, num_cells, Some(num_entries))?;
+        self.clear_level_scratch(&mut encoder);
+        match &self.profiler {
+            Some(profiler) => {
+                let mut timed = vec![];
+                self.encode_timed_kernels(&mut encoder, profiler, depth, &kernels, &mut timed)?;
+                resolve_timings(&mut encoder, profiler, timed);
+            }
+            None => self.encode_kernels(&mut encoder, &kernels),
+        }
+        self.submit(encoder);
+        Ok(())
+    }
 
-        // Clear intermediates from the previous level.
-        encoder.clear_buffer(&self.resources.cell_offsets_buffer, 0, None);
-        encoder.clear_buffer(&self.resources.winding_block_sum_buffers[0], 0, None);
+    /// Run levels `0..max_depth` in a single submission.
+    ///
+    /// Unlike [`Self::process_level`], the entry count never leaves the GPU: before
+    /// each level, prepare_level.wgsl turns the count emitted by the previous one into
+    /// the scan parameters and indirect dispatch sizes of the next. Read the final
+    /// count from `SplitResultInfo::emitted_entries_length`.
+    pub fn process_levels_indirect(&self, max_depth: u8) -> anyhow::Result<()> {
+        self.queue.write_buffer(
+            &self.resources.result_info_buffer,
+            0,
+            bytemuck::cast_slice(&[SplitResultInfo {
+                seg_entries_length: 0,
+                min_seg: self.min_seg,
+                emitted_entries_length: self.num_seg_entries,
+                _pad: 0,
+            }]),
+        );
 
-        {
-            let mut pass = encoder.begin_compute_pass(&Default::default());
-
-            // QuadCell split
-            pass.set_pipeline(&self.pipelines.quadcell_split);
-            pass.set_bind_group(0, &self.bind_groups.split_quadcell[ping], &[]);
-            let [x, y, z] = split_dispatch_3d(num_cells, max_dim);
-            pass.dispatch_workgroups(x, y, z);
-
-            // Build split entries
-            pass.set_pipeline(&self.pipelines.build_split_entries);
-            pass.set_bind_group(0, &self.bind_groups.split_seg_entry[ping], &[]);
-            let [x, y, z] = dispatch_for_items(num_entries, max_dim);
-            pass.dispatch_workgroups(x, y, z);
-
-            let winding_bgs = &self.bind_groups.winding_scan_bgs;
-            for i in 0..winding_levels.len() {
-                pass.set_pipeline(&self.pipelines.scan_winding_block);
-                pass.set_bind_group(0, &winding_bgs[i], &[]);
-                let [x, y, z] = dispatch_for_items(winding_levels[i], max_dim);
-                pass.dispatch_workgroups(x, y, z);
-            }
-            for i in (0..winding_levels.len().saturating_sub(1)).rev() {
-                pass.set_pipeline(&self.pipelines.add_winding_carry);
-                pass.set_bind_group(0, &winding_bgs[i], &[]);
-                let [x, y, z] = dispatch_for_items(winding_levels[i], max_dim);
-                pass.dispatch_workgroups(x, y, z);
+        let scan_params_bytes = size_of::<ScanParams>() as u64;
+        let scan_params_buffers = self
+            .resources
+            .winding_scan_params_buffers
+            .iter()
+            .chain(&self.resources.offset_scan_params_buffers);
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        let mut num_cells = 1u32;
+        let mut timed = vec![];
+        for depth in 0..max_depth {
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&self.pipelines.prepare_level);
+                pass.set_bind_group(0, &self.bind_groups.prepare_level, &[]);
+                pass.dispatch_workgroups(1, 1, 1);
             }
-
-            pass.set_pipeline(&self.pipelines.mark_tail_winding_offsets);
-            pass.set_bind_group(0, &self.bind_groups.mark_tail, &[]);
-            let [x, y, z] = dispatch_for_items(num_entries, max_dim);
-            pass.dispatch_workgroups(x, y, z);
-
-            let offset_bgs = &self.bind_groups.offset_scan_bgs;
-            for i in 0..offset_levels.len() {
-                pass.set_pipeline(&self.pipelines.scan_offset_block);
-                pass.set_bind_group(0, &offset_bgs[i], &[]);
-                let [x, y, z] = dispatch_for_items(offset_levels[i], max_dim);
-                pass.dispatch_workgroups(x, y, z);
+            for (i, buffer) in scan_params_buffers.clone().enumerate() {
+                encoder.copy_buffer_to_buffer(
+                    &self.resources.level_scan_params_buffer,
+                    i as u64 * scan_params_bytes,
+                    buffer,
+                    0,
+                    scan_params_bytes,
+                );
             }
-            for i in (0..offset_levels.len().saturating_sub(1)).rev() {
-                pass.set_pipeline(&self.pipelines.add_offset_carry);
-                pass.set_bind_group(0, &offset_bgs[i], &[]);
-                let [x, y, z] = dispatch_for_items(offset_levels[i], max_dim);
-                pass.dispatch_workgroups(x, y, z);
+            let kernels = self.level_kernels(depth, num_cells, None)?;
+            self.clear_level_scratch(&mut encoder);
+            match &self.profiler {
+                Some(profiler) => {
+                    self.encode_timed_kernels(&mut encoder, profiler, depth, &kernels, &mut timed)?
+                }
+                None => self.encode_kernels(&mut encoder, &kernels),
             }
+            num_cells = num_cells.saturating_mul(4);
+        }
+        if let Some(profiler) = &self.profiler {
+            resolve_timings(&mut encoder, profiler, timed);
+        }
+        self.submit(encoder);
+        Ok(())
+    }
 
-            pass.set_pipeline(&self.pipelines.emit_seg_entries);
-            pass.set_bind_group(0, &self.bind_groups.emit_result, &[]);
-            let [x, y, z] = dispatch_for_items(num_offsets, max_dim);
-            pass.dispatch_workgroups(x, y, z);
+    /// Number of command buffer submissions so far, readbacks included.
+    pub fn submissions(&self) -> u32 {
+        self.submissions.get()
+    }
 
-            // Dispatch by max_result_entries (upper bound); shader early-returns for
-            // out-of-range threads since the actual count is only known on the GPU.
-            pass.set_pipeline(&self.pipelines.update_metadata);
-            pass.set_bind_group(0, &self.bind_groups.update_metadata[ping], &[]);
-            let [x, y, z] = split_dispatch_3d(max_result_entries.max(1), max_dim);
-            pass.dispatch_workgroups(x, y, z);
-        }
+    fn submit(&self, encoder: wgpu::CommandEncoder) {
         self.queue.submit([encoder.finish()]);
+        self.submissions.set(self.submissions.get() + 1);
+    }
+
+    /// The kernels of one level, in dispatch order. With `num_entries` the dispatch
+    /// sizes are computed here; without, they come from the slots prepare_level.wgsl
+    /// wrote.
+    fn level_kernels(
+        &self,
+        depth: u8,
+        num_cells: u32,
+        num_entries: Option<u32>,
+    ) -> anyhow::Result<Vec<Kernel<'_>>> {
+        let max_dim = self.device.limits().max_compute_workgroups_per_dimension;
+        let ping = (depth % 2) as usize;
+        let pipelines = &self.pipelines;
+        let winding_bgs = &self.bind_groups.winding_scan_bgs;
+        let offset_bgs = &self.bind_groups.offset_scan_bgs;
+        let winding_slot = |i: usize| ENTRIES_DISPATCH_SLOT + 1 + i as u64;
+        let offset_slot = |i: usize| winding_slot(winding_bgs.len() + i);
+        let update_metadata_slot = offset_slot(offset_bgs.len());
+
+        // Per level, `Some(len)` or `None` for an indirect dispatch. An indirect build
+        // runs every level; the ones past the top dispatch no workgroups.
+        let scan_levels = |initial: u32, num_levels: usize| match num_entries {
+            Some(_) => hierarchical_level_counts(initial, num_levels)
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => vec![None; num_levels],
+        };
+        let n = num_entries.unwrap_or(0);
+        let winding_levels: Vec<Option<u32>> = scan_levels(n, winding_bgs.len());
+        let offset_levels: Vec<Option<u32>> = scan_levels(n.saturating_mul(4), offset_bgs.len());
+
+        let for_items = |items: Option<u32>| {
+            items
+                .map(|items| dispatch_for_items(items, max_dim))
+                .transpose()
+        };
+        let kernel = |name, pipeline, bind_group, slot, workgroups| Kernel {
+            name,
+            scan_level: None,
+            pipeline,
+            bind_group,
+            workgroups,
+            slot,
+        };
+        let scan_kernel = |name, pipeline, bind_group, slot, workgroups, level| Kernel {
+            scan_level: Some(level),
+            ..kernel(name, pipeline, bind_group, slot, workgroups)
+        };
+
+        let mut kernels = vec![
+            kernel(
+                "quadcell_split",
+                &pipelines.quadcell_split,
+                &self.bind_groups.split_quadcell[ping],
+                0,
+                Some(split_dispatch_3d(num_cells, max_dim)?),
+            ),
+            kernel(
+                "build_split_entries",
+                &pipelines.build_split_entries,
+                &self.bind_groups.split_seg_entry[ping],
+                ENTRIES_DISPATCH_SLOT,
+                for_items(num_entries)?,
+            ),
+        ];
+        for (i, &level) in winding_levels.iter().enumerate() {
+            kernels.push(scan_kernel(
+                "scan_winding_block",
+                &pipelines.scan_winding_block,
+                &winding_bgs[i],
+                winding_slot(i),
+                for_items(level)?,
+                i,
+            ));
+        }
+        for (i, &level) in winding_levels.iter().enumerate().rev().skip(1) {
+            kernels.push(scan_kernel(
+                "add_winding_carry",
+                &pipelines.add_winding_carry,
+                &winding_bgs[i],
+                winding_slot(i),
+                for_items(level)?,
+                i,
+            ));
+        }
+
+        kernels.push(kernel(
+            "mark_tail_winding_offsets",
+            &pipelines.mark_tail_winding_offsets,
+            &self.bind_groups.mark_tail,
+            ENTRIES_DISPATCH_SLOT,
+            for_items(num_entries)?,
+        ));
+
+        for (i, &level) in offset_levels.iter().enumerate() {
+            kernels.push(scan_kernel(
+                "scan_offset_block",
+                &pipelines.scan_offset_block,
+                &offset_bgs[i],
+                offset_slot(i),
+                for_items(level)?,
+                i,
+            ));
+        }
+        for (i, &level) in offset_levels.iter().enumerate().rev().skip(1) {
+            kernels.push(scan_kernel(
+                "add_offset_carry",
+                &pipelines.add_offset_carry,
+                &offset_bgs[i],
+                offset_slot(i),
+                for_items(level)?,
+                i,
+            ));
+        }
+
+        // One thread per offset, the same items as the first offset scan level.
+        kernels.push(kernel(
+            "emit_seg_entries",
+            &pipelines.emit_seg_entries,
+            &self.bind_groups.emit_result,
+            offset_slot(0),
+            for_items(num_entries.map(|n| n.saturating_mul(4)))?,
+        ));
+
+        // Dispatch by the upper bound of 4 child entries per entry; shader early-returns
+        // for out-of-range threads since the actual count is only known on the GPU.
+        kernels.push(kernel(
+            "update_metadata",
+            &pipelines.update_metadata,
+            &self.bind_groups.update_metadata[ping],
+            update_metadata_slot,
+            num_entries
+                .map(|n| split_dispatch_3d(n.saturating_mul(4).max(1), max_dim))
+                .transpose()?,
+        ));
+        Ok(kernels)
+    }
+
+    /// Clear intermediates from the previous level.
+    fn clear_level_scratch(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.resources.cell_offsets_buffer, 0, None);
+        encoder.clear_buffer(&self.resources.winding_block_sum_buffers[0], 0, None);
+    }
+
+    fn dispatch_kernel(&self, pass: &mut wgpu::ComputePass, kernel: &Kernel) {
+        pass.set_pipeline(kernel.pipeline);
+        pass.set_bind_group(0, kernel.bind_group, &[]);
+        match kernel.workgroups {
+            Some([x, y, z]) => pass.dispatch_workgroups(x, y, z),
+            None => pass.dispatch_workgroups_indirect(
+                &self.resources.dispatch_args_buffer,
+                kernel.slot * DISPATCH_ARGS_BYTES,
+            ),
+        }
+    }
+
+    /// Encode `kernels` into a single compute pass.
+    fn encode_kernels(&self, encoder: &mut wgpu::CommandEncoder, kernels: &[Kernel]) {
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        for kernel in kernels {
+            self.dispatch_kernel(&mut pass, kernel);
+        }
+    }
+
+    /// Encode each of `kernels` of level `depth` into a pass of its own with
+    /// timestamps written at its start and end, after the queries of the kernels
+    /// already in `timed`, and append them to `timed`.
+    fn encode_timed_kernels(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        profiler: &KernelProfiler,
+        depth: u8,
+        kernels: &[Kernel],
+        timed: &mut Vec<TimedKernel>,
+    ) -> anyhow::Result<()> {
+        let num_queries = (timed.len() + kernels.len()) as u32 * 2;
+        anyhow::ensure!(
+            num_queries <= profiler.capacity,
+            "{} kernels need {num_queries} timestamp queries, over the {} allocated",
+            timed.len() + kernels.len(),
+            profiler.capacity
+        );
+        for kernel in kernels {
+            let query = 2 * timed.len() as u32;
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some(kernel.name),
+                timestamp_writes: Some(ComputePassTimestampWrites {
+                    query_set: &profiler.query_set,
+                    beginning_of_pass_write_index: Some(query),
+                    end_of_pass_write_index: Some(query + 1),
+                }),
+            });
+            self.dispatch_kernel(&mut pass, kernel);
+            timed.push((kernel.name, depth, kernel.scan_level));
+        }
+        Ok(())
     }
 
     pub fn readback<T: AnyBitPattern>(
@@ -870,7 +1433,7 @@ impl QuadTreeGpuContext {
     ) -> anyhow::Result<Vec<T>> {
         let mut encoder = self.device.create_command_encoder(&Default::default());
         encoder.copy_buffer_to_buffer(&source_buffer, 0, readback_buffer, 0, source_buffer.size());
-        self.queue.submit([encoder.finish()]);
+        self.submit(encoder);
         let slice = readback_buffer.slice(..);
         let (tx, rx) = channel();
 
@@ -956,3 +1519,251 @@ impl QuadTreeGpuContext {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod dispatch_tests {
+    use super::*;
+
+    #[test]
+    fn split_dispatch_3d_rejects_oversized_dispatches() {
+        assert_eq!(split_dispatch_3d(1, 65535).unwrap(), [1, 1, 1]);
+        assert_eq!(split_dispatch_3d(70_000, 65535).unwrap(), [65535, 2, 1]);
+        let [x, y, z] = split_dispatch_3d(u32::MAX, 65535).unwrap();
+        assert_eq!([x, y, z], [65535, 65535, 2]);
+        assert!(x as u64 * y as u64 * z as u64 >= u32::MAX as u64);
+
+        // `workgroups_needed + x - 1` used to overflow u32 here before the check.
+        let err = split_dispatch_3d(u32::MAX, 1000).unwrap_err();
+        assert!(err.to_string().contains("exceeds 1000^3"), "{err}");
+        assert!(split_dispatch_3d(1_000_000_001, 1000).is_err());
+        assert_eq!(split_dispatch_3d(1_000_000_000, 1000).unwrap(), [1000; 3]);
+        assert!(split_dispatch_3d(1, 0).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "gpu-tests"))]
+mod tests {
+    use super::*;
+    use crate::seg_entry::{
+        build_split_entries, consolidate_winding_inc, init_root_seg_entries,
+        update_to_global_offset,
+    };
+    use crate::test_utils::{TestScene, solid};
+    use usvg::tiny_skia_path::Point;
+
+    impl Resources {
+        fn total_bytes(&self) -> u64 {
+            let single = [
+                &self.cell_metadata_buffer_1,
+                &self.cell_metadata_buffer_2,
+                &self.seg_entries_buffer,
+                &self.segments_buffer,
+                &self.split_entries_buffer,
+                &self.cell_offsets_buffer,
+                &self.result_info_buffer,
+                &self.level_scan_params_buffer,
+                &self.dispatch_args_buffer,
+                &self.level_layout_buffer,
+                &self.split_entries_readback_buffer,
+                &self.cell_offsets_readback_buffer,
+                &self.cell_metadata_readback_buffer,
+                &self.seg_entry_readback_buffer,
+                &self.result_info_readback_buffer,
+            ];
+            let lists = [
+                &self.winding_block_sum_buffers,
+                &self.winding_scan_params_buffers,
+                &self.offset_block_sum_buffers,
+                &self.offset_scan_params_buffers,
+                &self.winding_block_sum_readback_buffers,
+            ];
+            single.iter().map(|b| b.size()).sum::<u64>()
+                + lists
+                    .iter()
+                    .flat_map(|l| l.iter())
+                    .map(|b| b.size())
+                    .sum::<u64>()
+        }
+    }
+
+    #[test]
+    fn required_buffer_bytes_matches_allocated_buffers() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 60.0, 40.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let entries = init_root_seg_entries(&scene.abs_segments);
+        let (device, _queue) = pollster::block_on(init_wgpu());
+        for max_depth in [1, 3] {
+            let resources = Resources::new(&device, &entries, &scene.abs_segments, max_depth);
+            let expected =
+                required_buffer_bytes(entries.len(), scene.abs_segments.len(), max_depth).unwrap();
+            assert_eq!(resources.total_bytes(), expected, "max_depth {max_depth}");
+        }
+    }
+
+    #[test]
+    fn context_rejects_depth_over_budget() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 60.0, 40.0, solid([255, 0, 0, 255]));
+        let entries = init_root_seg_entries(&scene.abs_segments);
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let required = required_buffer_bytes(entries.len(), scene.abs_segments.len(), 4).unwrap();
+        let result = pollster::block_on(QuadTreeGpuContext::new(
+            &entries,
+            &scene.abs_segments,
+            &root,
+            4,
+            1,
+            Some(required - 1),
+        ));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn profiling_times_every_kernel_of_a_level() {
+        let mut scene = TestScene::new();
+        for i in 0..40 {
+            let (x, y) = ((i % 8) as f32 * 8.0, (i / 8) as f32 * 8.0);
+            scene.push_polygon(
+                &[(x + 1.0, y + 1.0), (x + 7.0, y + 2.0), (x + 3.0, y + 7.0)],
+                solid([255, 0, 0, 255]),
+            );
+        }
+        let entries = init_root_seg_entries(&scene.abs_segments);
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let mut ctx = pollster::block_on(QuadTreeGpuContext::new(
+            &entries,
+            &scene.abs_segments,
+            &root,
+            1,
+            1,
+            None,
+        ))
+        .unwrap();
+        assert!(ctx.read_timings().unwrap().is_empty());
+        // Without TIMESTAMP_QUERY there is nothing to time.
+        if !ctx.enable_profiling() {
+            return;
+        }
+        ctx.process_level(0, 1, entries.len() as u32).unwrap();
+        let timings = ctx.read_timings().unwrap();
+
+        let kernels: Vec<_> = timings.iter().map(|t| t.kernel).collect();
+        assert_eq!(kernels.first(), Some(&"quadcell_split"));
+        assert_eq!(kernels.last(), Some(&"update_metadata"));
+        for kernel in [
+            "scan_winding_block",
+            "scan_offset_block",
+            "emit_seg_entries",
+        ] {
+            assert!(kernels.contains(&kernel), "{kernel} missing");
+        }
+        assert!(timings.iter().all(|t| t.depth == 0));
+        let total_micros: f64 = timings.iter().map(|t| t.micros).sum();
+        assert!(total_micros > 0.0, "{timings:?}");
+    }
+
+    #[test]
+    fn profiling_times_every_level_of_an_indirect_build() {
+        let mut scene = TestScene::new();
+        scene.push_polygon(
+            &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+            solid([255, 0, 0, 255]),
+        );
+        let entries = init_root_seg_entries(&scene.abs_segments);
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let max_depth = 3;
+        let mut ctx = pollster::block_on(QuadTreeGpuContext::new(
+            &entries,
+            &scene.abs_segments,
+            &root,
+            max_depth,
+            1,
+            None,
+        ))
+        .unwrap();
+        // Without TIMESTAMP_QUERY there is nothing to time.
+        if !ctx.enable_profiling() {
+            return;
+        }
+        ctx.process_levels_indirect(max_depth).unwrap();
+        let timings = ctx.read_timings().unwrap();
+
+        for depth in 0..max_depth {
+            let level: Vec<_> = timings
+                .iter()
+                .filter(|t| t.depth == depth)
+                .map(|t| t.kernel)
+                .collect();
+            assert_eq!(level.first(), Some(&"quadcell_split"), "depth {depth}");
+            assert_eq!(level.last(), Some(&"update_metadata"), "depth {depth}");
+        }
+        let depths: Vec<u8> = timings.iter().map(|t| t.depth).collect();
+        assert!(depths.is_sorted(), "{depths:?}");
+    }
+
+    #[test]
+    fn scan_offsets_match_cpu_across_workgroups() {
+        // Three 120-point stars around the root midpoint: 360 root entries span two
+        // winding-scan workgroups (the last path straddles the boundary) and their
+        // 1440 offsets need a second scan level.
+        let mut scene = TestScene::new();
+        for (i, radius) in [20.0f32, 26.0, 30.0].into_iter().enumerate() {
+            let points: Vec<(f32, f32)> = (0..120)
+                .map(|k| {
+                    let r = if k % 2 == 0 { radius } else { radius * 0.6 };
+                    let angle = (k as f32 + i as f32 * 0.3) * std::f32::consts::TAU / 120.0;
+                    (32.0 + r * angle.cos(), 32.0 + r * angle.sin())
+                })
+                .collect();
+            scene.push_polygon(&points, solid([255, 0, 0, 255]));
+        }
+        let entries = init_root_seg_entries(&scene.abs_segments);
+        assert!(entries.len() > WG_SIZE as usize);
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+
+        let ctx = pollster::block_on(QuadTreeGpuContext::new(
+            &entries,
+            &scene.abs_segments,
+            &root,
+            1,
+            1,
+            None,
+        ))
+        .unwrap();
+        ctx.process_level(0, 1, entries.len() as u32).unwrap();
+        let gpu_offsets = ctx
+            .readback::<u32>(
+                &ctx.resources.cell_offsets_buffer,
+                &ctx.resources.cell_offsets_readback_buffer,
+            )
+            .unwrap();
+
+        let [mid_x, mid_y] = root.mid_point();
+        let mut cpu_entries = entries.clone();
+        let mut split_entries = build_split_entries(
+            &root,
+            &Point { x: mid_x, y: mid_y },
+            &mut cpu_entries,
+            &scene.abs_segments,
+        );
+        consolidate_winding_inc(&mut split_entries);
+        let total = update_to_global_offset(&mut split_entries);
+
+        // The GPU scan is inclusive and laid out cell-major; the CPU offsets are
+        // exclusive, so each GPU offset is the CPU offset of the next slot.
+        let exclusive: Vec<u32> = (0..4)
+            .flat_map(|cell| split_entries.iter().map(move |e| e.offsets[cell]))
+            .collect();
+        let expected: Vec<u32> = exclusive[1..]
+            .iter()
+            .copied()
+            .chain(std::iter::once(total))
+            .collect();
+        assert_eq!(&gpu_offsets[..expected.len()], &expected[..]);
+    }
+}
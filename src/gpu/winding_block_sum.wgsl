@@ -1,6 +1,7 @@
 // Depends on: common.wgsl
 
-const WG_SIZE: u32 = 2u;
+// Must match WG_SIZE in subdivide_seg_entry.rs.
+const WG_SIZE: u32 = 256u;
 
 fn neutral_winc() -> WindingBlockInfo {
     var z = WindingBlockInfo();
@@ -55,7 +56,7 @@ fn inclusive_scan_winding_inc(lid: u32) {
 @group(0) @binding(5) var<storage, read_write> result_info: array<SplitResultInfo>;
 @group(0) @binding(6) var<storage, read_write> scan_params: array<ScanParams>;
 
-var<workgroup> wincs: array<WindingBlockInfo, 2>;
+var<workgroup> wincs: array<WindingBlockInfo, WG_SIZE>;
 
 @compute
 @workgroup_size(WG_SIZE)
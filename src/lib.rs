@@ -0,0 +1,133 @@
+pub mod abstract_segment;
+pub mod geometry;
+pub mod gpu;
+pub mod path;
+pub mod pdf_writer;
+pub mod png_writer;
+pub mod post_process;
+pub mod preview;
+pub mod quad_tree;
+pub mod render;
+pub mod seg_entry;
+pub mod svg_parser;
+pub mod svg_writer;
+#[cfg(test)]
+mod test_utils;
+
+use geometry::rect::Rect;
+use quad_tree::QuadTree;
+use render::{render_trees, RenderOptions, TreeLayer};
+use svg_parser::{parse_svg_str, FlattenOptions, ParsedSvg};
+
+/// Rasterize `svg` on the CPU into a `width` x `height` straight-alpha RGBA8 buffer.
+///
+/// The drawing is stretched from its own size to `width` x `height`, then split
+/// into a quadtree of at most `max_depth` levels whose cells stop dividing at
+/// `min_seg` segments or fewer.
+///
+/// ```
+/// use baby_parallel_vector_graphics::render_svg_to_rgba;
+///
+/// let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8">
+///     <rect x="0" y="0" width="4" height="8" fill="#00f"/>
+/// </svg>"##;
+/// let pixels = render_svg_to_rgba(svg, 16, 16, 3, 1)?;
+/// assert_eq!(pixels.len(), 16 * 16 * 4);
+/// assert_eq!(pixels[..4], [0, 0, 255, 255]);
+/// assert_eq!(pixels[15 * 4..16 * 4], [0, 0, 0, 0]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn render_svg_to_rgba(
+    svg: &str,
+    width: u32,
+    height: u32,
+    max_depth: u8,
+    min_seg: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut parsed = parse_svg_str(svg, &FlattenOptions::default())?;
+    parsed.scale_to(width, height)?;
+    let tree = build_render_tree(&parsed, max_depth, min_seg)?;
+    Ok(render_parsed_svg(&parsed, &tree, &RenderOptions::default()))
+}
+
+/// CPU quadtree over `parsed.root_bounds(0.0)`, the canvas and every path.
+pub fn build_render_tree(
+    parsed: &ParsedSvg,
+    max_depth: u8,
+    min_seg: usize,
+) -> anyhow::Result<QuadTree> {
+    build_render_tree_in(parsed, parsed.root_bounds(0.0)?, max_depth, min_seg)
+}
+
+/// CPU quadtree over a fixed `root`. Segments outside it are clipped away, which
+/// changes the fill of any path that crosses its edge.
+pub fn build_render_tree_in(
+    parsed: &ParsedSvg,
+    root: Rect,
+    max_depth: u8,
+    min_seg: usize,
+) -> anyhow::Result<QuadTree> {
+    QuadTree::new(
+        &parsed.abs_segments,
+        &parsed.abs_paths,
+        root,
+        max_depth,
+        min_seg,
+    )
+}
+
+/// Render `parsed`, with its groups, through `tree` into a newly allocated
+/// `parsed.width` x `parsed.height` RGBA8 buffer.
+pub fn render_parsed_svg(parsed: &ParsedSvg, tree: &QuadTree, options: &RenderOptions) -> Vec<u8> {
+    let mut pixels = vec![0u8; parsed.width as usize * parsed.height as usize * 4];
+    let layer = TreeLayer {
+        tree,
+        abs_segments: &parsed.abs_segments,
+        abs_paths: &parsed.abs_paths,
+        paints: &parsed.paints,
+        groups: &parsed.groups,
+        offset: [0.0, 0.0],
+    };
+    render_trees(&[layer], &mut pixels, parsed.width, parsed.height, options);
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use usvg::tiny_skia_path::Point;
+
+    #[test]
+    fn default_root_covers_paths_off_the_canvas() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">
+                <rect x="10" y="10" width="20" height="20" fill="blue"/>
+                <rect x="2000" y="2000" width="20" height="20" fill="red"/>
+            </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        assert_eq!(
+            parsed.root_bounds(0.0).unwrap().to_ltrb(),
+            [0.0, 0.0, 2020.0, 2020.0]
+        );
+        assert_eq!(
+            parsed.root_bounds(4.0).unwrap().to_ltrb(),
+            [-4.0, -4.0, 2024.0, 2024.0]
+        );
+
+        let color_at = |tree: &QuadTree, x: f32, y: f32| {
+            let p = Point::from_xy(x, y);
+            tree.color_at(p, &parsed.abs_segments, &parsed.abs_paths, &parsed.paints)
+        };
+        let references_far_path = |tree: &QuadTree| tree.entries.iter().any(|e| e.path_idx == 1);
+
+        let derived = build_render_tree(&parsed, 4, 1).unwrap();
+        assert!(references_far_path(&derived));
+        assert_eq!(color_at(&derived, 2010.5, 2010.5), [255, 0, 0, 255]);
+        assert_eq!(color_at(&derived, 20.5, 20.5), [0, 0, 255, 255]);
+
+        let fixed = Rect::from_ltrb(0.0, 0.0, 1000.0, 1000.0).unwrap();
+        let clipped = build_render_tree_in(&parsed, fixed, 4, 1).unwrap();
+        assert!(!references_far_path(&clipped));
+        assert_eq!(color_at(&clipped, 2010.5, 2010.5)[3], 0);
+        assert_eq!(color_at(&clipped, 20.5, 20.5), [0, 0, 255, 255]);
+    }
+}
@@ -1,55 +1,69 @@
-mod abstract_segment;
-mod seg_entry;
-mod geometry;
-mod gpu;
-mod path;
-mod png_writer;
-mod quad_tree;
-mod render;
-mod svg_parser;
-
-use crate::abstract_segment::{AbstractLineSegment, SegType};
-use crate::seg_entry::init_root_seg_entries;
-use crate::geometry::rect::Rect;
-use crate::gpu::quad_tree::build_quadtree;
-use crate::gpu::render::{build_path_paints, ComputeRenderer};
-use crate::path::AbstractPath;
-use crate::path::Paint;
-use crate::png_writer::save_png_rgba8;
-use crate::quad_tree::QuadTree;
-use crate::render::render;
-use crate::svg_parser::{parse_svg, ParsedSvg};
+use baby_parallel_vector_graphics::geometry::rect::Rect;
+use baby_parallel_vector_graphics::gpu::quad_tree::build_quadtree;
+use baby_parallel_vector_graphics::gpu::render::{build_path_paints, ComputeRenderer};
+use baby_parallel_vector_graphics::pdf_writer::save_pdf;
+use baby_parallel_vector_graphics::png_writer::{save_png_rgba8, save_png_tiles_rgba8};
+use baby_parallel_vector_graphics::post_process::despeckle;
+use baby_parallel_vector_graphics::preview::run_preview;
+use baby_parallel_vector_graphics::quad_tree::export_svg;
+use baby_parallel_vector_graphics::render::{render_depth_map, RenderOptions};
+use baby_parallel_vector_graphics::seg_entry::init_root_seg_entries;
+use baby_parallel_vector_graphics::svg_parser::{
+    parse_svg_with_warnings, FlattenOptions, ParsedSvg, SAMPLE_SVG_PATH,
+};
+use baby_parallel_vector_graphics::svg_writer::save_svg;
+use baby_parallel_vector_graphics::{build_render_tree, render_parsed_svg};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
-use usvg::tiny_skia_path::Point;
-use usvg::Path;
 use winit::dpi::PhysicalSize;
 use winit::event_loop::EventLoop;
 use winit::window::Window;
 
 const OUTPUT_WIDTH_OVERRIDE: Option<u32> = None;
 const OUTPUT_HEIGHT_OVERRIDE: Option<u32> = None;
+/// Also save the CPU quadtree's depth map (darker = deeper) to `output/depth_map.png`.
+const SAVE_DEPTH_MAP: bool = false;
+/// Also save the CPU quadtree's cells and the segments to `output/quad_tree.svg`.
+const SAVE_TREE_SVG: bool = false;
+/// Draw cell borders and shortcut/winding bars over the CPU render.
+const DRAW_DEBUG_OVERLAY: bool = true;
+/// Also save the CPU render as a `[columns, rows]` grid of tile PNGs plus
+/// `output/test_cpu_tiles.json`.
+const OUTPUT_TILES: Option<[u32; 2]> = None;
+/// Clear isolated CPU-render pixels with alpha below this before saving.
+const DESPECKLE_ALPHA_THRESHOLD: Option<u8> = None;
 
 fn main() -> anyhow::Result<()> {
-    let mut parsed = parse_svg()?;
+    // Usage: baby-parallel-vector-graphics [--preview] [input.svg]
+    let mut args = std::env::args().skip(1).peekable();
+    let preview = args.next_if(|arg| arg == "--preview").is_some();
+    let svg_path = args
+        .next()
+        .map_or_else(|| PathBuf::from(SAMPLE_SVG_PATH), PathBuf::from);
+    if preview {
+        return run_preview(&svg_path);
+    }
+    let (mut parsed, warnings) = parse_svg_with_warnings(&svg_path, &FlattenOptions::default())?;
+    for warning in &warnings {
+        eprintln!("warning: {warning:?}");
+    }
     let render_width = OUTPUT_WIDTH_OVERRIDE.unwrap_or(parsed.width).max(1);
     let render_height = OUTPUT_HEIGHT_OVERRIDE.unwrap_or(parsed.height).max(1);
 
-    if render_width != parsed.width || render_height != parsed.height {
-        let sx = render_width as f32 / parsed.width as f32;
-        let sy = render_height as f32 / parsed.height as f32;
-        scale_geometry(&mut parsed.abs_paths, &mut parsed.abs_segments, sx, sy)?;
-    }
+    parsed.scale_to(render_width, render_height)?;
     let ParsedSvg {
         abs_paths,
         abs_segments,
         paints,
         ..
-    } = parsed;
+    } = &parsed;
 
     let root_bounds = Rect::from_ltrb(0.0, 0.0, render_width as f32, render_height as f32).unwrap();
-    let root_entries = init_root_seg_entries(&abs_segments);
-    let (metadata, seg_entries) = build_quadtree(root_bounds, root_entries, 4, 1, &abs_segments)?;
-    let path_paints = build_path_paints(&abs_paths, &paints);
+    let root_entries = init_root_seg_entries(abs_segments);
+    let (metadata, seg_entries) = build_quadtree(root_bounds, root_entries, 4, 1, abs_segments)?;
+    let (path_paints, gradient_stops) = build_path_paints(abs_paths, paints);
 
     // Rendering on GPU, compute to offscreen texture + surface blit + PNG readback
     let event_loop = EventLoop::new()?;
@@ -74,67 +88,80 @@ fn main() -> anyhow::Result<()> {
         &surface,
         &metadata,
         &seg_entries,
-        &abs_segments,
+        abs_segments,
         &path_paints,
+        &gradient_stops,
     )?;
     save_png_rgba8(
         "output/test_gpu.png",
         render_width,
         render_height,
         &gpu_pixels,
-    );
+    )?;
 
     // Rendering on CPU for reference
-    let render_tree = QuadTree::new(&abs_segments, root_bounds, 4, 1)?;
-    let mut cpu_pixels = vec![0u8; (render_width as usize) * (render_height as usize) * 4];
-    render(
+    let render_tree = build_render_tree(&parsed, 4, 1)?;
+    let mut cpu_pixels = render_parsed_svg(
+        &parsed,
         &render_tree,
-        &abs_segments,
-        &abs_paths,
-        &paints,
-        &mut cpu_pixels,
-        render_width,
-        render_height,
+        &RenderOptions {
+            debug_overlay: DRAW_DEBUG_OVERLAY,
+            ..Default::default()
+        },
     );
+    if let Some(threshold) = DESPECKLE_ALPHA_THRESHOLD {
+        despeckle(&mut cpu_pixels, render_width, render_height, threshold);
+    }
     save_png_rgba8(
         "output/test_cpu.png",
         render_width,
         render_height,
         &cpu_pixels,
-    );
-    Ok(())
-}
-
-fn create_paint_array(paints: &mut Vec<Paint>, path: &Path) {
-    let fill = path.fill().unwrap().paint();
-    if let usvg::Paint::Color(c) = fill {
-        paints.push(Paint::SolidColor {
-            rgba: [c.red, c.green, c.blue, 255],
-        });
+    )?;
+    if let Some(grid) = OUTPUT_TILES {
+        save_png_tiles_rgba8(
+            "output",
+            "test_cpu_tiles",
+            render_width,
+            render_height,
+            &cpu_pixels,
+            grid,
+        )?;
     }
-}
 
-fn scale_geometry(
-    abs_paths: &mut [AbstractPath],
-    abs_segments: &mut [AbstractLineSegment],
-    sx: f32,
-    sy: f32,
-) -> anyhow::Result<()> {
-    for path in abs_paths {
-        let [l, t, r, b] = path.bounding_box.to_ltrb();
-        path.bounding_box = Rect::from_ltrb(l * sx, t * sy, r * sx, b * sy)
-            .ok_or_else(|| anyhow::anyhow!("Invalid path bounding box after scaling"))?;
+    if SAVE_DEPTH_MAP {
+        let mut depth_pixels = vec![0u8; cpu_pixels.len()];
+        render_depth_map(&render_tree, &mut depth_pixels, render_width, render_height);
+        save_png_rgba8(
+            "output/depth_map.png",
+            render_width,
+            render_height,
+            &depth_pixels,
+        )?;
     }
-    for seg in abs_segments {
-        let p0 = Point {
-            x: seg.x0 * sx,
-            y: seg.y0 * sy,
-        };
-        let p1 = Point {
-            x: seg.x1 * sx,
-            y: seg.y1 * sy,
-        };
-        *seg = AbstractLineSegment::new(p0, p1, SegType::Linear, seg.path_idx);
+
+    if SAVE_TREE_SVG {
+        let mut out = BufWriter::new(File::create("output/quad_tree.svg")?);
+        export_svg(&render_tree, Some(abs_segments), &mut out)?;
+        out.flush()?;
     }
+
+    // Vector export of the same paths
+    save_pdf(
+        "output/test.pdf",
+        render_width,
+        render_height,
+        abs_segments,
+        abs_paths,
+        paints,
+    )?;
+    save_svg(
+        "output/test.svg",
+        render_width,
+        render_height,
+        abs_segments,
+        abs_paths,
+        paints,
+    )?;
     Ok(())
 }
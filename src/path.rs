@@ -1,4 +1,5 @@
 use crate::geometry::rect::Rect;
+use std::ops::Range;
 use usvg::FillRule;
 
 #[derive(Debug)]
@@ -8,9 +9,222 @@ pub struct AbstractPath {
     pub fill_rule: FillRule,
     pub paint_id: usize,
     pub bounding_box: Rect,
+    /// Set when the path is the outline of a stroke: the stroke width, in the user
+    /// units of the SVG path it outlines. The outline is filled like any other path.
+    pub stroke_width: Option<f32>,
 }
 
-#[derive(Debug)]
+impl AbstractPath {
+    /// Whether a point with winding number `winding` is inside the path.
+    pub fn fills(&self, winding: i32) -> bool {
+        match self.fill_rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    /// Whether `(x, y)` is in the path's bounding box, edges included. Outside it the
+    /// path's winding is the same everywhere, so the renderer skips its segment tests
+    /// there unless that winding fills.
+    pub fn may_fill(&self, x: f32, y: f32) -> bool {
+        let [l, t, r, b] = self.bounding_box.to_ltrb();
+        l <= x && x <= r && t <= y && y <= b
+    }
+}
+
+/// Paths between a `SegType::Push` and its `SegType::Commit`: they are composited
+/// together in isolation, then onto the content below at `opacity`, like an SVG
+/// group with `opacity` or `isolation: isolate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathGroup {
+    pub paths: Range<usize>,
+    pub opacity: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    /// Straight (non-premultiplied) colour; alpha carries `stop-opacity`.
+    pub rgba: [u8; 4],
+}
+
+/// Row-major 2x3 affine matrix `[sx, kx, tx, ky, sy, ty]`.
+pub type Affine = [f32; 6];
+
+pub const IDENTITY_AFFINE: Affine = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+pub fn map_affine(m: &Affine, x: f32, y: f32) -> [f32; 2] {
+    [m[0] * x + m[1] * y + m[2], m[3] * x + m[4] * y + m[5]]
+}
+
+#[derive(Debug, Clone)]
 pub enum Paint {
-    SolidColor { rgba: [u8; 4] },
+    SolidColor {
+        /// Straight (non-premultiplied) colour; alpha carries `fill-opacity`.
+        rgba: [u8; 4],
+    },
+    /// Gradient along `start -> end`, padded beyond both ends.
+    /// `inv_transform` maps user space into gradient space.
+    LinearGradient {
+        start: [f32; 2],
+        end: [f32; 2],
+        stops: Vec<GradientStop>,
+        inv_transform: Affine,
+    },
+    /// Gradient by distance from `center`; the focal point is ignored.
+    RadialGradient {
+        center: [f32; 2],
+        radius: f32,
+        stops: Vec<GradientStop>,
+        inv_transform: Affine,
+    },
+}
+
+/// Paint used for a path whose `paint_id` is past the end of the paint list.
+pub const FALLBACK_PAINT: Paint = Paint::SolidColor {
+    rgba: [0, 0, 0, 255],
+};
+
+/// The paint at `paint_id`, or [`FALLBACK_PAINT`] (opaque black) when `paints` is
+/// too short, so a paint/path count mismatch does not panic in the renderers.
+pub fn resolve_paint(paints: &[Paint], paint_id: usize) -> &Paint {
+    paints.get(paint_id).unwrap_or(&FALLBACK_PAINT)
+}
+
+impl Paint {
+    /// Colour of the paint at user-space position `(x, y)`.
+    pub fn sample(&self, x: f32, y: f32) -> [u8; 4] {
+        match self {
+            Paint::SolidColor { rgba } => *rgba,
+            Paint::LinearGradient {
+                start,
+                end,
+                stops,
+                inv_transform,
+            } => {
+                let [gx, gy] = map_affine(inv_transform, x, y);
+                let dx = end[0] - start[0];
+                let dy = end[1] - start[1];
+                let len_sq = dx * dx + dy * dy;
+                let t = if len_sq > 0.0 {
+                    ((gx - start[0]) * dx + (gy - start[1]) * dy) / len_sq
+                } else {
+                    0.0
+                };
+                sample_stops(stops, t)
+            }
+            Paint::RadialGradient {
+                center,
+                radius,
+                stops,
+                inv_transform,
+            } => {
+                let [gx, gy] = map_affine(inv_transform, x, y);
+                let dist = ((gx - center[0]).powi(2) + (gy - center[1]).powi(2)).sqrt();
+                let t = if *radius > 0.0 { dist / radius } else { 0.0 };
+                sample_stops(stops, t)
+            }
+        }
+    }
+}
+
+/// Interpolate the stop colours, alpha included, at `t` (clamped to [0, 1]); mirrors
+/// `sample_stops` in cell_render.wgsl.
+fn sample_stops(stops: &[GradientStop], t: f32) -> [u8; 4] {
+    let Some(first) = stops.first() else {
+        return [0; 4];
+    };
+    let t = t.clamp(0.0, 1.0);
+    if t <= first.offset {
+        return first.rgba;
+    }
+    let mut prev = first;
+    for next in &stops[1..] {
+        if t <= next.offset {
+            let f = (t - prev.offset) / (next.offset - prev.offset);
+            let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+            return std::array::from_fn(|c| lerp(prev.rgba[c], next.rgba[c]));
+        }
+        prev = next;
+    }
+    prev.rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_paint_falls_back_past_the_end() {
+        let paints = [Paint::SolidColor {
+            rgba: [0, 255, 0, 128],
+        }];
+        assert_eq!(resolve_paint(&paints, 0).sample(0.0, 0.0), [0, 255, 0, 128]);
+        for paint_id in [1, usize::MAX] {
+            assert_eq!(resolve_paint(&paints, paint_id).sample(0.0, 0.0), [0, 0, 0, 255]);
+        }
+        assert_eq!(resolve_paint(&[], 0).sample(0.0, 0.0), [0, 0, 0, 255]);
+    }
+
+    fn red_to_blue() -> Vec<GradientStop> {
+        vec![
+            GradientStop {
+                offset: 0.0,
+                rgba: [255, 0, 0, 255],
+            },
+            GradientStop {
+                offset: 1.0,
+                rgba: [0, 0, 255, 255],
+            },
+        ]
+    }
+
+    #[test]
+    fn linear_gradient_interpolates_and_pads() {
+        let paint = Paint::LinearGradient {
+            start: [0.0, 0.0],
+            end: [10.0, 0.0],
+            stops: red_to_blue(),
+            inv_transform: IDENTITY_AFFINE,
+        };
+        assert_eq!(paint.sample(-5.0, 3.0), [255, 0, 0, 255]);
+        assert_eq!(paint.sample(5.0, 3.0), [128, 0, 128, 255]);
+        assert_eq!(paint.sample(15.0, 3.0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn radial_gradient_uses_inverse_transform() {
+        // Gradient space is user space shifted by (+10, 0).
+        let paint = Paint::RadialGradient {
+            center: [0.0, 0.0],
+            radius: 4.0,
+            stops: red_to_blue(),
+            inv_transform: [1.0, 0.0, -10.0, 0.0, 1.0, 0.0],
+        };
+        assert_eq!(paint.sample(10.0, 0.0), [255, 0, 0, 255]);
+        assert_eq!(paint.sample(12.0, 0.0), [128, 0, 128, 255]);
+        assert_eq!(paint.sample(20.0, 0.0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn gradient_interpolates_stop_alpha() {
+        let paint = Paint::LinearGradient {
+            start: [0.0, 0.0],
+            end: [10.0, 0.0],
+            stops: vec![
+                GradientStop {
+                    offset: 0.0,
+                    rgba: [255, 0, 0, 255],
+                },
+                GradientStop {
+                    offset: 1.0,
+                    rgba: [0, 0, 255, 0],
+                },
+            ],
+            inv_transform: IDENTITY_AFFINE,
+        };
+        assert_eq!(paint.sample(0.0, 0.0), [255, 0, 0, 255]);
+        assert_eq!(paint.sample(5.0, 0.0), [128, 0, 128, 128]);
+        assert_eq!(paint.sample(10.0, 0.0), [0, 0, 255, 0]);
+    }
 }
@@ -0,0 +1,155 @@
+use crate::abstract_segment::AbstractLineSegment;
+use crate::path::{resolve_paint, AbstractPath, Paint};
+use std::fmt::Write as _;
+use std::fs;
+use usvg::FillRule;
+
+/// Export the filled paths as a single-page PDF of `width` x `height` points.
+///
+/// Each path becomes one polygon fill (`f*` for even-odd, `f` for non-zero) in
+/// path order. Only flat colours are written: gradients are approximated by their
+/// colour at the path's bounding-box centre, and alpha is dropped.
+pub fn to_pdf_bytes(
+    width: u32,
+    height: u32,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> Vec<u8> {
+    let content = fill_commands(height, abs_segments, abs_paths, paints);
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width} {height}] /Contents 4 0 R >>"),
+        format!(
+            "<< /Length {} >>\nstream\n{content}endstream",
+            content.len()
+        ),
+    ];
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        let _ = write!(pdf, "{} 0 obj\n{body}\nendobj\n", i + 1);
+    }
+    let xref_offset = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(pdf, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+        objects.len() + 1
+    );
+    pdf.into_bytes()
+}
+
+pub fn save_pdf(
+    path: &str,
+    width: u32,
+    height: u32,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> anyhow::Result<()> {
+    fs::write(
+        path,
+        to_pdf_bytes(width, height, abs_segments, abs_paths, paints),
+    )?;
+    Ok(())
+}
+
+/// Content stream: flip to SVG's y-down space, then one `rg` + polygon + fill per path.
+fn fill_commands(
+    height: u32,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> String {
+    let mut out = format!("1 0 0 -1 0 {height} cm\n");
+    for path in abs_paths {
+        let segments = &abs_segments[path.seg_start_idx..path.seg_end_idx];
+        if segments.is_empty() {
+            continue;
+        }
+        let [r, g, b] = flat_rgb(resolve_paint(paints, path.paint_id), path);
+        let _ = writeln!(
+            out,
+            "{:.4} {:.4} {:.4} rg",
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0
+        );
+
+        // Segments of a subpath are chained end to start; a break starts a new subpath.
+        let mut prev_end: Option<(f32, f32)> = None;
+        for seg in segments {
+            if prev_end != Some((seg.x0, seg.y0)) {
+                if prev_end.is_some() {
+                    out.push_str("h\n");
+                }
+                let _ = writeln!(out, "{:.3} {:.3} m", seg.x0, seg.y0);
+            }
+            let _ = writeln!(out, "{:.3} {:.3} l", seg.x1, seg.y1);
+            prev_end = Some((seg.x1, seg.y1));
+        }
+        out.push_str("h\n");
+        out.push_str(match path.fill_rule {
+            FillRule::EvenOdd => "f*\n",
+            FillRule::NonZero => "f\n",
+        });
+    }
+    out
+}
+
+fn flat_rgb(paint: &Paint, path: &AbstractPath) -> [u8; 3] {
+    let [x, y] = path.bounding_box.mid_point();
+    let [r, g, b, _] = paint.sample(x, y);
+    [r, g, b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, solid};
+
+    #[test]
+    fn pdf_parses_with_one_fill_per_path() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(10.0, 10.0, 60.0, 40.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(20.0, 90.0), (90.0, 20.0), (80.0, 80.0)],
+                solid([0, 0, 255, 255]),
+            )
+            .push_rect(5.0, 70.0, 30.0, 95.0, solid([0, 128, 0, 255]));
+        let bytes = to_pdf_bytes(
+            100,
+            100,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+        );
+
+        let doc = lopdf::Document::load_mem(&bytes).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 1);
+        let page_id = pages[&1];
+        let content =
+            lopdf::content::Content::decode(&doc.get_page_content(page_id).unwrap()).unwrap();
+        let fills = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "f*" || op.operator == "f")
+            .count();
+        assert_eq!(fills, scene.abs_paths.len());
+        let colors: Vec<_> = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "rg")
+            .collect();
+        assert_eq!(colors.len(), scene.abs_paths.len());
+    }
+}
@@ -1,14 +1,287 @@
-use std::fs::File;
-use std::io::BufWriter;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 
-pub fn save_png_rgba8(path: &str, w: u32, h: u32, rgba: &[u8]) {
-    let file = File::create(path).unwrap();
-    let wtr = BufWriter::new(file);
+/// Write `rgba` as a PNG. PNG stores straight alpha, so render without
+/// `RenderOptions::premultiply` for output saved here.
+pub fn save_png_rgba8(path: &str, w: u32, h: u32, rgba: &[u8]) -> io::Result<()> {
+    let file = File::create(path)?;
+    encode_png_rgba8(BufWriter::new(file), w, h, rgba)
+}
 
-    let mut encoder = png::Encoder::new(wtr, w, h);
+/// Encode `rgba` as a PNG into `writer`, e.g. a `Vec<u8>` or a socket, and flush
+/// it so buffered writers surface their write errors here.
+pub fn encode_png_rgba8<W: Write>(mut writer: W, w: u32, h: u32, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(&mut writer, w, h);
     encoder.set_color(png::ColorType::Rgba);
     encoder.set_depth(png::BitDepth::Eight);
 
-    let mut writer = encoder.write_header().unwrap();
-    writer.write_image_data(rgba).unwrap();
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(rgba)?;
+    png_writer.finish()?;
+    writer.flush()
+}
+
+/// One tile of a split image: its place in the full image and its PNG bytes.
+pub struct PngTile {
+    pub file_name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+
+/// Split `rgba` into a `columns` x `rows` grid and encode each tile as a standalone
+/// PNG named `{stem}_{column}_{row}.png`, row-major. Tile edges are spread evenly,
+/// so tiles differ in size by at most one pixel.
+pub fn encode_png_tiles_rgba8(
+    stem: &str,
+    w: u32,
+    h: u32,
+    rgba: &[u8],
+    [columns, rows]: [u32; 2],
+) -> anyhow::Result<Vec<PngTile>> {
+    check_tile_grid(w, h, rgba, [columns, rows])?;
+    let mut tiles = Vec::with_capacity(columns as usize * rows as usize);
+    for_each_png_tile(stem, w, h, rgba, [columns, rows], |tile| {
+        tiles.push(tile);
+        Ok(())
+    })?;
+    Ok(tiles)
+}
+
+/// Check that `rgba` is a `w` x `h` image that splits into `columns` x `rows`
+/// non-empty tiles.
+fn check_tile_grid(w: u32, h: u32, rgba: &[u8], [columns, rows]: [u32; 2]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        (1..=w).contains(&columns) && (1..=h).contains(&rows),
+        "cannot split {w}x{h} into {columns}x{rows} non-empty tiles"
+    );
+    anyhow::ensure!(
+        rgba.len() == w as usize * h as usize * 4,
+        "{} bytes for a {w}x{h} image",
+        rgba.len()
+    );
+    Ok(())
+}
+
+/// `encode_png_tiles_rgba8`, handing each tile to `on_tile` as soon as it is
+/// encoded instead of keeping them all. The grid must have passed `check_tile_grid`.
+fn for_each_png_tile(
+    stem: &str,
+    w: u32,
+    h: u32,
+    rgba: &[u8],
+    [columns, rows]: [u32; 2],
+    mut on_tile: impl FnMut(PngTile) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let edge = |size: u32, n: u32, i: u32| (size as u64 * i as u64 / n as u64) as u32;
+    for row in 0..rows {
+        let (y, bottom) = (edge(h, rows, row), edge(h, rows, row + 1));
+        for column in 0..columns {
+            let (x, right) = (edge(w, columns, column), edge(w, columns, column + 1));
+            let (width, height) = (right - x, bottom - y);
+            let mut tile = Vec::with_capacity(width as usize * height as usize * 4);
+            for ty in y..bottom {
+                let start = (ty as usize * w as usize + x as usize) * 4;
+                tile.extend_from_slice(&rgba[start..start + width as usize * 4]);
+            }
+            let mut png = Vec::new();
+            encode_png_rgba8(&mut png, width, height, &tile)?;
+            on_tile(PngTile {
+                file_name: format!("{stem}_{column}_{row}.png"),
+                x,
+                y,
+                width,
+                height,
+                png,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// JSON manifest of `tiles` of a `w` x `h` image: the full size and, per tile,
+/// its file name and pixel rect.
+pub fn tile_manifest(w: u32, h: u32, tiles: &[PngTile]) -> String {
+    let entries: Vec<String> = tiles
+        .iter()
+        .map(|t| {
+            format!(
+                "    {{\"file\": {}, \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}}}",
+                json_string(&t.file_name),
+                t.x,
+                t.y,
+                t.width,
+                t.height
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"width\": {w},\n  \"height\": {h},\n  \"tiles\": [\n{}\n  ]\n}}\n",
+        entries.join(",\n")
+    )
+}
+
+/// `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Write `rgba` as a grid of tile PNGs in `dir`, with the manifest in
+/// `{stem}.json` next to them; see `encode_png_tiles_rgba8`. Each tile is written
+/// as soon as it is encoded.
+pub fn save_png_tiles_rgba8(
+    dir: &str,
+    stem: &str,
+    w: u32,
+    h: u32,
+    rgba: &[u8],
+    grid: [u32; 2],
+) -> anyhow::Result<()> {
+    check_tile_grid(w, h, rgba, grid)?;
+    let dir = Path::new(dir);
+    let mut tiles = Vec::with_capacity(grid[0] as usize * grid[1] as usize);
+    for_each_png_tile(stem, w, h, rgba, grid, |mut tile| {
+        fs::write(dir.join(&tile.file_name), &tile.png)?;
+        // The manifest only needs the file name and rect.
+        tile.png = Vec::new();
+        tiles.push(tile);
+        Ok(())
+    })?;
+    fs::write(
+        dir.join(format!("{stem}.json")),
+        tile_manifest(w, h, &tiles),
+    )?;
+    Ok(())
+}
+
+/// Encode an RGBA8 image as a `data:image/png;base64,...` URI for embedding in HTML or logs.
+pub fn to_data_uri(w: u32, h: u32, rgba: &[u8]) -> anyhow::Result<String> {
+    let mut png_bytes: Vec<u8> = Vec::new();
+    encode_png_rgba8(&mut png_bytes, w, h, rgba)?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(&png_bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn encode_into_vec_round_trip() {
+        let (w, h) = (2u32, 2u32);
+        let rgba: Vec<u8> = vec![
+            255, 0, 0, 255, 0, 255, 0, 128, //
+            0, 0, 255, 0, 10, 20, 30, 40,
+        ];
+        let mut png_bytes = Vec::new();
+        encode_png_rgba8(Cursor::new(&mut png_bytes), w, h, &rgba).unwrap();
+
+        let decoder = png::Decoder::new(Cursor::new(png_bytes));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0u8; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (w, h));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(&buf[..info.buffer_size()], &rgba[..]);
+    }
+
+    #[test]
+    fn save_reports_io_errors() {
+        let dir = std::env::temp_dir().join("png_writer_missing_dir");
+        let path = dir.join("image.png");
+        let err = save_png_rgba8(path.to_str().unwrap(), 1, 1, &[0, 0, 0, 255]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn data_uri_round_trip() {
+        let (w, h) = (2u32, 2u32);
+        let rgba: Vec<u8> = vec![
+            255, 0, 0, 255, 0, 255, 0, 255, //
+            0, 0, 255, 255, 10, 20, 30, 40,
+        ];
+        let uri = to_data_uri(w, h, &rgba).unwrap();
+        let prefix = "data:image/png;base64,";
+        assert!(uri.starts_with(prefix));
+
+        let png_bytes = STANDARD.decode(&uri[prefix.len()..]).unwrap();
+        let decoder = png::Decoder::new(Cursor::new(png_bytes));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0u8; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (w, h));
+        assert_eq!(&buf[..info.buffer_size()], &rgba[..]);
+    }
+
+    #[test]
+    fn two_by_two_tiles_reassemble_to_the_image() {
+        let (w, h) = (5u32, 3u32);
+        let rgba: Vec<u8> = (0..w * h * 4).map(|i| i as u8).collect();
+        let tiles = encode_png_tiles_rgba8("out", w, h, &rgba, [2, 2]).unwrap();
+        let rects: Vec<_> = tiles
+            .iter()
+            .map(|t| [t.x, t.y, t.width, t.height])
+            .collect();
+        assert_eq!(
+            rects,
+            [[0, 0, 2, 1], [2, 0, 3, 1], [0, 1, 2, 2], [2, 1, 3, 2]]
+        );
+
+        let mut reassembled = vec![0u8; rgba.len()];
+        for tile in &tiles {
+            let decoder = png::Decoder::new(Cursor::new(&tile.png));
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0u8; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            assert_eq!((info.width, info.height), (tile.width, tile.height));
+            let row_len = tile.width as usize * 4;
+            for (ty, row) in buf[..info.buffer_size()].chunks(row_len).enumerate() {
+                let start = ((tile.y as usize + ty) * w as usize + tile.x as usize) * 4;
+                reassembled[start..start + row_len].copy_from_slice(row);
+            }
+        }
+        assert_eq!(reassembled, rgba);
+
+        let manifest = tile_manifest(w, h, &tiles);
+        assert!(manifest.contains("\"width\": 5,"));
+        assert!(manifest.contains(
+            "{\"file\": \"out_1_1.png\", \"x\": 2, \"y\": 1, \"width\": 3, \"height\": 2}"
+        ));
+        assert!(encode_png_tiles_rgba8("out", w, h, &rgba, [6, 1]).is_err());
+        // Rejected before anything is sized by the grid.
+        let huge = [u32::MAX, u32::MAX];
+        assert!(encode_png_tiles_rgba8("out", w, h, &rgba, huge).is_err());
+        let dir = std::env::temp_dir();
+        assert!(save_png_tiles_rgba8(dir.to_str().unwrap(), "out", w, h, &rgba, huge).is_err());
+    }
+
+    #[test]
+    fn manifest_escapes_file_names() {
+        let rgba = [0u8; 4];
+        let tiles = encode_png_tiles_rgba8("a\"b\\c\n", 1, 1, &rgba, [1, 1]).unwrap();
+        let manifest = tile_manifest(1, 1, &tiles);
+        assert!(
+            manifest.contains(r#"{"file": "a\"b\\c\n_0_0.png", "x": 0,"#),
+            "{manifest}"
+        );
+    }
 }
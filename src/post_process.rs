@@ -0,0 +1,59 @@
+/// Clear isolated anti-aliasing specks: pixels whose alpha is below `alpha_threshold`
+/// and whose eight neighbours are all fully transparent. Pixels on the image border
+/// only consider their in-bounds neighbours.
+///
+/// Returns the number of pixels cleared. Decisions use the input image, so clearing
+/// one speck never exposes another.
+pub fn despeckle(pixels: &mut [u8], width: u32, height: u32, alpha_threshold: u8) -> usize {
+    let (w, h) = (width as usize, height as usize);
+    let alpha = |x: usize, y: usize| pixels[(y * w + x) * 4 + 3];
+    let mut specks = vec![];
+    for y in 0..h {
+        for x in 0..w {
+            let a = alpha(x, y);
+            if a == 0 || a >= alpha_threshold {
+                continue;
+            }
+            let isolated = (y.saturating_sub(1)..(y + 2).min(h)).all(|ny| {
+                (x.saturating_sub(1)..(x + 2).min(w))
+                    .all(|nx| (nx, ny) == (x, y) || alpha(nx, ny) == 0)
+            });
+            if isolated {
+                specks.push(y * w + x);
+            }
+        }
+    }
+    for &i in &specks {
+        pixels[i * 4..i * 4 + 4].fill(0);
+    }
+    specks.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::pixel;
+
+    #[test]
+    fn despeckle_clears_only_faint_isolated_pixels() {
+        let (w, h) = (8u32, 8u32);
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        let mut set = |x: u32, y: u32, a: u8| {
+            let i = ((y * w + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[255, 0, 0, a]);
+        };
+        set(1, 1, 40); // faint speck: cleared
+        set(6, 1, 200); // strong speck: kept
+        set(0, 7, 30); // faint speck in a corner: cleared
+        set(4, 5, 40); // faint but touching a neighbour: kept
+        set(5, 5, 255);
+
+        let cleared = despeckle(&mut pixels, w, h, 64);
+        assert_eq!(cleared, 2);
+        assert_eq!(pixel(&pixels, w, 1, 1), [0; 4]);
+        assert_eq!(pixel(&pixels, w, 0, 7), [0; 4]);
+        assert_eq!(pixel(&pixels, w, 6, 1), [255, 0, 0, 200]);
+        assert_eq!(pixel(&pixels, w, 4, 5), [255, 0, 0, 40]);
+        assert_eq!(pixel(&pixels, w, 5, 5), [255, 0, 0, 255]);
+    }
+}
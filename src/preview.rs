@@ -0,0 +1,202 @@
+use crate::abstract_segment::AbstractLineSegment;
+use crate::geometry::rect::Rect;
+use crate::gpu::quad_tree::{build_quadtree_in, CellMetadata};
+use crate::gpu::render::{build_path_paints, ComputeRenderer, GradientStopGpu, PathPaintGpu};
+use crate::gpu::subdivide_seg_entry::QuadTreeGpuContext;
+use crate::seg_entry::{init_root_seg_entries, SegEntry};
+use crate::svg_parser::{parse_svg_str, FlattenOptions};
+use anyhow::Context;
+use std::path::Path;
+use std::sync::Arc;
+use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::window::{Window, WindowId};
+
+const PREVIEW_MAX_DEPTH: u8 = 4;
+const PREVIEW_MIN_SEG: usize = 1;
+
+/// Open a window showing the SVG at `svg_path`, rendered on the GPU, until it is
+/// closed.
+///
+/// The drawing is letterboxed into the window; on resize it is flattened and
+/// subdivided again for the new size before the next frame, on a quad-tree GPU
+/// context created once here.
+pub fn run_preview(svg_path: &Path) -> anyhow::Result<()> {
+    let svg = std::fs::read_to_string(svg_path)
+        .with_context(|| format!("failed to read SVG file {}", svg_path.display()))?;
+    let parsed = parse_svg_str(&svg, &FlattenOptions::default())
+        .with_context(|| format!("failed to parse SVG file {}", svg_path.display()))?;
+    let initial_size = PhysicalSize::new(parsed.width.max(1), parsed.height.max(1));
+    let (width, height) = (initial_size.width as f32, initial_size.height as f32);
+    let root = Rect::from_ltrb(0.0, 0.0, width, height).context("invalid SVG size")?;
+    let quad_tree_ctx = pollster::block_on(QuadTreeGpuContext::new(
+        &init_root_seg_entries(&parsed.abs_segments),
+        &parsed.abs_segments,
+        &root,
+        PREVIEW_MAX_DEPTH,
+        PREVIEW_MIN_SEG as u32,
+        None,
+    ))?;
+    let mut app = Preview {
+        svg,
+        quad_tree_ctx,
+        title: format!("{} - baby-parallel-vector-graphics", svg_path.display()),
+        initial_size,
+        instance: wgpu::Instance::new(&wgpu::InstanceDescriptor::default()),
+        window: None,
+        error: None,
+    };
+    EventLoop::new()?.run_app(&mut app)?;
+    app.error.map_or(Ok(()), Err)
+}
+
+struct Preview {
+    svg: String,
+    /// Reloaded for every scene, so resizes do not create a new device.
+    quad_tree_ctx: QuadTreeGpuContext,
+    title: String,
+    initial_size: PhysicalSize<u32>,
+    instance: wgpu::Instance,
+    window: Option<PreviewWindow>,
+    /// First error from an event handler; the event loop exits once it is set.
+    error: Option<anyhow::Error>,
+}
+
+struct PreviewWindow {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    renderer: ComputeRenderer,
+    /// Built for `renderer.size()`; `None` until the first frame after a resize.
+    scene: Option<PreviewScene>,
+}
+
+/// What `ComputeRenderer` draws, for one window size.
+struct PreviewScene {
+    metadata: Vec<CellMetadata>,
+    seg_entries: Vec<SegEntry>,
+    abs_segments: Vec<AbstractLineSegment>,
+    path_paints: Vec<PathPaintGpu>,
+    gradient_stops: Vec<GradientStopGpu>,
+}
+
+impl PreviewScene {
+    fn build(
+        svg: &str,
+        quad_tree_ctx: &mut QuadTreeGpuContext,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let options = FlattenOptions {
+            fit_to: Some([width, height]),
+            ..FlattenOptions::default()
+        };
+        let parsed = parse_svg_str(svg, &options)?;
+        let root = Rect::from_ltrb(0.0, 0.0, width as f32, height as f32)
+            .ok_or_else(|| anyhow::anyhow!("invalid preview size {width}x{height}"))?;
+        let (metadata, seg_entries) = build_quadtree_in(
+            quad_tree_ctx,
+            root,
+            init_root_seg_entries(&parsed.abs_segments),
+            PREVIEW_MAX_DEPTH,
+            &parsed.abs_segments,
+        )?;
+        let (path_paints, gradient_stops) = build_path_paints(&parsed.abs_paths, &parsed.paints);
+        Ok(Self {
+            metadata,
+            seg_entries,
+            abs_segments: parsed.abs_segments,
+            path_paints,
+            gradient_stops,
+        })
+    }
+}
+
+impl Preview {
+    fn open_window(&self, event_loop: &ActiveEventLoop) -> anyhow::Result<PreviewWindow> {
+        let window = Arc::new(
+            event_loop.create_window(
+                Window::default_attributes()
+                    .with_title(self.title.as_str())
+                    .with_inner_size(self.initial_size),
+            )?,
+        );
+        let surface = self.instance.create_surface(window.clone())?;
+        let size = window.inner_size();
+        let renderer = pollster::block_on(ComputeRenderer::new(
+            &self.instance,
+            &surface,
+            size.width,
+            size.height,
+        ))?;
+        Ok(PreviewWindow {
+            window,
+            surface,
+            renderer,
+            scene: None,
+        })
+    }
+
+    fn handle(&mut self, event: WindowEvent) -> anyhow::Result<bool> {
+        let Some(preview) = self.window.as_mut() else {
+            return Ok(true);
+        };
+        match event {
+            WindowEvent::CloseRequested => return Ok(false),
+            WindowEvent::Resized(size) => {
+                preview
+                    .renderer
+                    .resize(&preview.surface, size.width, size.height);
+                preview.scene = None;
+                preview.window.request_redraw();
+            }
+            WindowEvent::RedrawRequested => {
+                let scene = match preview.scene.take() {
+                    Some(scene) => scene,
+                    None => {
+                        let (width, height) = preview.renderer.size();
+                        PreviewScene::build(&self.svg, &mut self.quad_tree_ctx, width, height)?
+                    }
+                };
+                preview.renderer.render_to_surface(
+                    &preview.surface,
+                    &scene.metadata,
+                    &scene.seg_entries,
+                    &scene.abs_segments,
+                    &scene.path_paints,
+                    &scene.gradient_stops,
+                )?;
+                preview.scene = Some(scene);
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+}
+
+impl ApplicationHandler for Preview {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        match self.open_window(event_loop) {
+            Ok(window) => self.window = Some(window),
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match self.handle(event) {
+            Ok(true) => {}
+            Ok(false) => event_loop.exit(),
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+            }
+        }
+    }
+}
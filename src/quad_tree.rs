@@ -1,8 +1,14 @@
 use crate::abstract_segment::AbstractLineSegment;
+use crate::path::{resolve_paint, AbstractPath, Paint};
+use crate::render::{sample_point, TreeLayer};
 use crate::seg_entry::{
-    init_root_seg_entries, subdivide_seg_entry, SegEntry, CellId, ABSTRACT,
+    init_root_seg_entries, sort_cell_entries, subdivide_seg_entry_with_scratch, SegEntry,
+    SubdivideScratch, CellId, ABSTRACT, WINDING_INCREMENT,
 };
-use crate::geometry::rect::Rect;
+use crate::geometry::rect::{exact_i32, Rect};
+use bytemuck::{Pod, Zeroable};
+use rayon::prelude::*;
+use std::io::{self, Write};
 use std::ops::Range;
 use usvg::tiny_skia_path::Point;
 
@@ -16,7 +22,9 @@ pub struct QuadCell {
     pub id: CellId,
     pub depth: u8,
     pub bbox: Rect,
-    pub children: Option<[CellId; 4]>,
+    /// Child ids in (TL, TR, BL, BR) order; quadrants that received no entries are
+    /// empty space and get no node.
+    pub children: Option<[Option<CellId>; 4]>,
     /// Set once this cell is finalised as a leaf.
     pub leaf_entry_range: Option<Range<usize>>,
 }
@@ -26,31 +34,659 @@ pub struct QuadTree {
     pub entries: Vec<SegEntry>,
 }
 
+/// How the CPU builder grows its node, frontier and leaf entry vectors.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum CapacityPolicy {
+    /// Let each `Vec` grow on demand.
+    OnDemand,
+    /// Reserve for the worst-case 4-way split of the current frontier at every level.
+    #[default]
+    ReserveFrontier,
+}
+
+/// A quad tree builder, so callers can switch between the CPU and GPU
+/// subdivision without caring which one produced the tree.
+pub trait Subdivider {
+    fn subdivide(
+        &self,
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        max_depth: u8,
+        min_seg: usize,
+    ) -> anyhow::Result<QuadTree>;
+}
+
+/// Builds the tree on the CPU with `QuadTree::with_capacity_policy`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CpuSubdivider {
+    pub capacity_policy: CapacityPolicy,
+}
+
+impl Subdivider for CpuSubdivider {
+    fn subdivide(
+        &self,
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        max_depth: u8,
+        min_seg: usize,
+    ) -> anyhow::Result<QuadTree> {
+        QuadTree::with_capacity_policy(
+            abs_segments,
+            abs_paths,
+            root_bbox,
+            max_depth,
+            min_seg,
+            self.capacity_policy,
+        )
+    }
+}
+
+const TREE_BYTES_MAGIC: [u8; 4] = *b"BPQT";
+const TREE_BYTES_VERSION: u32 = 2;
+/// Stands in for `None` in `CellRecord` fields.
+const NO_INDEX: u32 = u32::MAX;
+
+/// Header of the `QuadTree::to_bytes` layout; the record arrays follow directly.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct TreeBytesHeader {
+    magic: [u8; 4],
+    version: u32,
+    node_count: u32,
+    entry_count: u32,
+}
+
+/// Fixed-size form of a `QuadCell`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct CellRecord {
+    bbox_ltrb: [f32; 4],
+    children: [u32; 4], // NO_INDEX for an empty quadrant; all NO_INDEX for a leaf
+    leaf_start: u32,    // NO_INDEX if the cell has no leaf range
+    leaf_end: u32,
+    id: u32,
+    depth: u32,
+}
+
 impl QuadTree {
     pub fn new(
         abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
         root_bbox: Rect,
         max_depth: u8,
         min_seg: usize,
     ) -> anyhow::Result<Self> {
-        let root_entries = init_root_seg_entries(&abs_segments);
-        let (nodes, entries) =
-            build_quadtree(root_bbox, root_entries, max_depth, min_seg, abs_segments)?;
-        Ok(Self { nodes, entries })
+        Self::with_capacity_policy(
+            abs_segments,
+            abs_paths,
+            root_bbox,
+            max_depth,
+            min_seg,
+            CapacityPolicy::default(),
+        )
+    }
+
+    pub fn with_capacity_policy(
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        max_depth: u8,
+        min_seg: usize,
+        capacity_policy: CapacityPolicy,
+    ) -> anyhow::Result<Self> {
+        Self::build(
+            abs_segments,
+            abs_paths,
+            root_bbox,
+            &[],
+            max_depth,
+            min_seg,
+            capacity_policy,
+        )
+    }
+
+    /// Like `new`, with `seed_entries` added to the root cell next to the ABSTRACT
+    /// entries of `abs_segments`. A `WINDING_INCREMENT` seed gives its path a baseline
+    /// winding over the whole root, e.g. to carry coverage over from a previous pass;
+    /// ABSTRACT seeds must name a segment of their own path.
+    pub fn with_seed_entries(
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        seed_entries: &[SegEntry],
+        max_depth: u8,
+        min_seg: usize,
+    ) -> anyhow::Result<Self> {
+        Self::build(
+            abs_segments,
+            abs_paths,
+            root_bbox,
+            seed_entries,
+            max_depth,
+            min_seg,
+            CapacityPolicy::default(),
+        )
+    }
+
+    fn build(
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        root_bbox: Rect,
+        seed_entries: &[SegEntry],
+        max_depth: u8,
+        min_seg: usize,
+        capacity_policy: CapacityPolicy,
+    ) -> anyhow::Result<Self> {
+        validate_path_indices(abs_segments, abs_paths)?;
+        validate_entries("seed entry", seed_entries, abs_segments, abs_paths)?;
+        let mut root_entries = init_root_seg_entries(abs_segments);
+        root_entries.extend(seed_entries.iter().map(|seed| SegEntry {
+            cell_pos: 0,
+            cell_id: 0,
+            ..*seed
+        }));
+        // Group the seeds with their paths' segments: a root that is never split keeps
+        // this order, and the renderers walk a leaf's entries path by path.
+        sort_cell_entries(&mut root_entries);
+        let (nodes, entries) = build_quadtree(
+            root_bbox,
+            root_entries,
+            max_depth,
+            min_seg,
+            abs_segments,
+            capacity_policy,
+        )?;
+        let tree = Self { nodes, entries };
+        #[cfg(debug_assertions)]
+        tree.validate_leaf_ranges()?;
+        Ok(tree)
+    }
+
+    /// Check that the leaf entry ranges partition `entries`: sorted by start they
+    /// must be contiguous, non-overlapping and cover `0..entries.len()`, and each
+    /// entry must carry the id of the leaf that owns it.
+    pub fn validate_leaf_ranges(&self) -> anyhow::Result<()> {
+        let mut leaves: Vec<(CellId, &Range<usize>)> = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.leaf_entry_range.as_ref().map(|r| (node.id, r)))
+            .collect();
+        leaves.sort_by_key(|(_, range)| (range.start, range.end));
+
+        let mut expected_start = 0;
+        for (cell_id, range) in leaves {
+            anyhow::ensure!(
+                range.start == expected_start,
+                "leaf {} range {:?} does not start at {} (gap or overlap)",
+                cell_id,
+                range,
+                expected_start
+            );
+            anyhow::ensure!(
+                range.end <= self.entries.len(),
+                "leaf {} range {:?} exceeds {} entries",
+                cell_id,
+                range,
+                self.entries.len()
+            );
+            if let Some(i) = range.clone().find(|&i| self.entries[i].cell_id != cell_id) {
+                anyhow::bail!(
+                    "entry {} in leaf {} range has cell_id {}",
+                    i,
+                    cell_id,
+                    self.entries[i].cell_id
+                );
+            }
+            expected_start = range.end;
+        }
+        anyhow::ensure!(
+            expected_start == self.entries.len(),
+            "leaf ranges cover 0..{} but there are {} entries",
+            expected_start,
+            self.entries.len()
+        );
+        Ok(())
+    }
+
+    /// Deepest cell containing `(x, y)`, or `None` outside the root bbox or in an
+    /// empty quadrant that was pruned during the build.
+    ///
+    /// Cells are half-open like `Rect::contains`, so a point on a midline goes to the
+    /// bottom/right child, the cell the renderer samples it in. Points on the root's
+    /// right or bottom edge still belong to the root. Integer points in cells with
+    /// integer midpoints are classified with integer comparisons.
+    pub fn leaf_at(&self, x: f32, y: f32) -> Option<&QuadCell> {
+        let mut node = self.nodes.first()?;
+        let [l, t, r, b] = node.bbox.to_ltrb();
+        if !(l <= x && x <= r && t <= y && y <= b) {
+            return None;
+        }
+        let int_point = exact_i32(x).zip(exact_i32(y)).map(|(xi, yi)| [xi, yi]);
+        while let Some(children) = node.children {
+            let pos = match (int_point, integer_mid(&node.bbox)) {
+                (Some([xi, yi]), Some(mid)) => classify_child_int(mid, xi, yi),
+                _ => classify_child(node.bbox.mid_point(), x, y),
+            };
+            node = &self.nodes[children[pos]? as usize];
+        }
+        Some(node)
+    }
+
+    /// Composited colour at `p`, for picking: only the leaf containing `p` is
+    /// evaluated, with the same per-sample logic as `render`. The tree must have been
+    /// built from `abs_segments` and `abs_paths`.
+    pub fn color_at(
+        &self,
+        p: Point,
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        paints: &[Paint],
+    ) -> [u8; 4] {
+        let layer = TreeLayer {
+            tree: self,
+            abs_segments,
+            abs_paths,
+            paints,
+            groups: &[],
+            offset: [0.0, 0.0],
+        };
+        sample_point(&layer, p.x, p.y)
+    }
+
+    /// Indices of the paths that fill some of the interior of cell `cell_id`, ascending;
+    /// for an inner cell, of any of its leaves. A path counts if one of its edges
+    /// crosses a leaf, or if its accumulated winding increment fills the whole leaf
+    /// under its fill rule. Panics if `cell_id` is not a node of this tree.
+    pub fn paths_filling(&self, cell_id: CellId, abs_paths: &[AbstractPath]) -> Vec<usize> {
+        let mut paths = Vec::new();
+        let mut stack = vec![cell_id];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id as usize];
+            if let Some(children) = node.children {
+                stack.extend(children.into_iter().flatten());
+            }
+            let Some(range) = node.leaf_entry_range.clone() else {
+                continue;
+            };
+            for path_entries in self.entries[range].chunk_by(|a, b| a.path_idx == b.path_idx) {
+                let path_idx = path_entries[0].path_idx as usize;
+                let crossed = path_entries.iter().any(|e| e.entry_type & ABSTRACT != 0);
+                let winding: i32 = path_entries
+                    .iter()
+                    .filter(|e| e.entry_type & WINDING_INCREMENT != 0)
+                    .map(|e| e.winding_increment())
+                    .sum();
+                if crossed || abs_paths[path_idx].fills(winding) {
+                    paths.push(path_idx);
+                }
+            }
+        }
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+    }
+
+    /// Rectangles of uniform colour for vector output: leaves whose visible colour is
+    /// constant are coalesced bottom-up, so four siblings of the same colour become
+    /// their parent's rect. Leaves crossed by a visible edge, or showing a gradient,
+    /// are left out, as are fully transparent regions. Groups are ignored, as in
+    /// `color_at`.
+    pub fn merged_fill_rects(
+        &self,
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+        paints: &[Paint],
+    ) -> Vec<(Rect, [u8; 4])> {
+        let mut rects = Vec::new();
+        if self.nodes.is_empty() {
+            return rects;
+        }
+        let scene = (abs_segments, abs_paths, paints);
+        if let Some(color) = self.merge_uniform_cells(0, scene, &mut rects) {
+            push_fill_rect(&mut rects, self.nodes[0].bbox, color);
+        }
+        rects
+    }
+
+    /// Colour of cell `id` if it is uniform as a whole; otherwise push the rects of
+    /// its uniform parts and return `None`.
+    fn merge_uniform_cells(
+        &self,
+        id: CellId,
+        scene: (&[AbstractLineSegment], &[AbstractPath], &[Paint]),
+        rects: &mut Vec<(Rect, [u8; 4])>,
+    ) -> Option<[u8; 4]> {
+        let node = &self.nodes[id as usize];
+        let Some(children) = node.children else {
+            return self.uniform_leaf_color(node, scene);
+        };
+        // A pruned quadrant is empty space, so its parent is never uniform.
+        let colors =
+            children.map(|child| child.and_then(|c| self.merge_uniform_cells(c, scene, rects)));
+        if colors[0].is_some() && colors.iter().all(|&c| c == colors[0]) {
+            return colors[0];
+        }
+        for (child, color) in children.into_iter().zip(colors) {
+            if let (Some(child), Some(color)) = (child, color) {
+                push_fill_rect(rects, self.nodes[child as usize].bbox, color);
+            }
+        }
+        None
+    }
+
+    /// Visible colour of a leaf if it is the same everywhere in the cell: the paths
+    /// above the first opaque one covering the cell must cover it too (no edge in
+    /// the cell) and use solid paints.
+    fn uniform_leaf_color(
+        &self,
+        node: &QuadCell,
+        (abs_segments, abs_paths, paints): (&[AbstractLineSegment], &[AbstractPath], &[Paint]),
+    ) -> Option<[u8; 4]> {
+        let range = node.leaf_entry_range.clone()?;
+        let entries = &self.entries[range];
+        for path_entries in entries.chunk_by(|a, b| a.path_idx == b.path_idx).rev() {
+            if path_entries.iter().any(|e| e.entry_type & ABSTRACT != 0) {
+                return None;
+            }
+            let winding: i32 = path_entries.iter().map(|e| e.winding_increment()).sum();
+            let path = &abs_paths[path_entries[0].path_idx as usize];
+            if !path.fills(winding) {
+                continue;
+            }
+            match resolve_paint(paints, path.paint_id) {
+                Paint::SolidColor { rgba } if rgba[3] == 255 => break,
+                Paint::SolidColor { .. } => {}
+                _ => return None,
+            }
+        }
+        let [x, y] = node.bbox.mid_point();
+        Some(self.color_at(Point { x, y }, abs_segments, abs_paths, paints))
+    }
+
+    /// Serialise the tree for caching: a versioned header followed by the cell records
+    /// and the raw `SegEntry` array, in native byte order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = TreeBytesHeader {
+            magic: TREE_BYTES_MAGIC,
+            version: TREE_BYTES_VERSION,
+            node_count: self.nodes.len() as u32,
+            entry_count: self.entries.len() as u32,
+        };
+        let records: Vec<CellRecord> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let (leaf_start, leaf_end) = node
+                    .leaf_entry_range
+                    .as_ref()
+                    .map_or((NO_INDEX, NO_INDEX), |r| (r.start as u32, r.end as u32));
+                CellRecord {
+                    bbox_ltrb: node.bbox.to_ltrb(),
+                    children: node
+                        .children
+                        .map_or([NO_INDEX; 4], |ids| ids.map(|id| id.unwrap_or(NO_INDEX))),
+                    leaf_start,
+                    leaf_end,
+                    id: node.id,
+                    depth: node.depth as u32,
+                }
+            })
+            .collect();
+
+        let mut bytes = Vec::with_capacity(
+            size_of::<TreeBytesHeader>()
+                + size_of_val(records.as_slice())
+                + size_of_val(self.entries.as_slice()),
+        );
+        bytes.extend_from_slice(bytemuck::bytes_of(&header));
+        bytes.extend_from_slice(bytemuck::cast_slice(&records));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.entries));
+        bytes
+    }
+
+    /// Load a tree written by `to_bytes` for the drawing made of `abs_segments` and
+    /// `abs_paths`, rejecting foreign or stale data, trees whose child links or leaf
+    /// ranges are inconsistent, and entries that name segments or paths the drawing
+    /// does not have (e.g. a cache built for another drawing).
+    pub fn from_bytes(
+        bytes: &[u8],
+        abs_segments: &[AbstractLineSegment],
+        abs_paths: &[AbstractPath],
+    ) -> anyhow::Result<Self> {
+        let header_len = size_of::<TreeBytesHeader>();
+        anyhow::ensure!(bytes.len() >= header_len, "quadtree data is truncated");
+        let header: TreeBytesHeader = bytemuck::pod_read_unaligned(&bytes[..header_len]);
+        anyhow::ensure!(
+            header.magic == TREE_BYTES_MAGIC,
+            "not a serialised quadtree (bad magic)"
+        );
+        anyhow::ensure!(
+            header.version == TREE_BYTES_VERSION,
+            "quadtree data version {} is not supported (expected {})",
+            header.version,
+            TREE_BYTES_VERSION
+        );
+
+        let node_count = header.node_count as usize;
+        let entry_count = header.entry_count as usize;
+        let nodes_len = node_count * size_of::<CellRecord>();
+        let entries_len = entry_count * size_of::<SegEntry>();
+        anyhow::ensure!(
+            bytes.len() == header_len + nodes_len + entries_len,
+            "quadtree data is {} bytes, expected {} for {} cells and {} entries",
+            bytes.len(),
+            header_len + nodes_len + entries_len,
+            node_count,
+            entry_count
+        );
+        let (node_bytes, entry_bytes) = bytes[header_len..].split_at(nodes_len);
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for (i, chunk) in node_bytes.chunks_exact(size_of::<CellRecord>()).enumerate() {
+            let record: CellRecord = bytemuck::pod_read_unaligned(chunk);
+            let bbox = Rect::from_ltrb_slice(&record.bbox_ltrb)
+                .ok_or_else(|| anyhow::anyhow!("cell {i} has an invalid bbox"))?;
+            let children = (record.children != [NO_INDEX; 4])
+                .then(|| record.children.map(|c| (c != NO_INDEX).then_some(c)));
+            if let Some(children) = children {
+                anyhow::ensure!(
+                    children
+                        .iter()
+                        .flatten()
+                        .all(|&c| (c as usize) > i && (c as usize) < node_count),
+                    "cell {i} has out-of-range children {children:?}"
+                );
+            }
+            let leaf_entry_range = (record.leaf_start != NO_INDEX)
+                .then_some(record.leaf_start as usize..record.leaf_end as usize);
+            if let Some(range) = &leaf_entry_range {
+                anyhow::ensure!(
+                    range.start <= range.end && range.end <= entry_count,
+                    "cell {i} has leaf range {range:?} outside {entry_count} entries"
+                );
+            }
+            nodes.push(QuadCell {
+                id: record.id,
+                depth: u8::try_from(record.depth)?,
+                bbox,
+                children,
+                leaf_entry_range,
+            });
+        }
+        let entries = entry_bytes
+            .chunks_exact(size_of::<SegEntry>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+
+        let tree = Self { nodes, entries };
+        tree.validate_leaf_ranges()?;
+        validate_entries("entry", &tree.entries, abs_segments, abs_paths)?;
+        Ok(tree)
+    }
+}
+
+/// Write the cell structure of `tree` as an SVG for inspecting the subdivision: one
+/// `<rect>` per cell, coloured by depth, with leaves filled and internal cells only
+/// outlined (dashed). `segments`, if given, are drawn on top as `<line>`s.
+///
+/// The SVG's viewBox is the root cell, in the tree's own coordinates.
+pub fn export_svg(
+    tree: &QuadTree,
+    segments: Option<&[AbstractLineSegment]>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let Some(root) = tree.nodes.first() else {
+        return writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\"/>");
+    };
+    let [l, t, r, b] = root.bbox.to_ltrb();
+    let (width, height) = (r - l, b - t);
+    let stroke_width = width.max(height) / 512.0;
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"{l} {t} {width} {height}\">"
+    )?;
+    // Level order, so deeper cells are drawn over their ancestors.
+    for node in &tree.nodes {
+        let [l, t, r, b] = node.bbox.to_ltrb();
+        let hue = (node.depth as u32 * 47) % 360;
+        let fill = if node.leaf_entry_range.is_some() {
+            format!("fill=\"hsl({hue}, 70%, 45%)\" fill-opacity=\"0.25\"")
+        } else {
+            "fill=\"none\" stroke-dasharray=\"4 2\"".to_string()
+        };
+        writeln!(
+            out,
+            "<rect x=\"{l}\" y=\"{t}\" width=\"{}\" height=\"{}\" {fill} \
+             stroke=\"hsl({hue}, 70%, 35%)\" stroke-width=\"{stroke_width}\"/>",
+            r - l,
+            b - t
+        )?;
+    }
+    for seg in segments.unwrap_or_default() {
+        writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" \
+             stroke-width=\"{stroke_width}\"/>",
+            seg.x0, seg.y0, seg.x1, seg.y1
+        )?;
     }
+    writeln!(out, "</svg>")
+}
+
+fn push_fill_rect(rects: &mut Vec<(Rect, [u8; 4])>, bbox: Rect, color: [u8; 4]) {
+    if color[3] != 0 {
+        rects.push((bbox, color));
+    }
+}
+
+/// Every segment's `path_idx` must index `abs_paths`; the renderers look paths up by it.
+pub fn validate_path_indices(
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+) -> anyhow::Result<()> {
+    if let Some((i, seg)) = abs_segments
+        .iter()
+        .enumerate()
+        .find(|(_, seg)| seg.path_idx as usize >= abs_paths.len())
+    {
+        anyhow::bail!(
+            "segment {} references path_idx {}, but there are only {} paths",
+            i,
+            seg.path_idx,
+            abs_paths.len()
+        );
+    }
+    Ok(())
+}
+
+/// Every entry must name a path of `abs_paths`, and an ABSTRACT entry a segment of
+/// that path. `what` names the entries in errors.
+fn validate_entries(
+    what: &str,
+    entries: &[SegEntry],
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+) -> anyhow::Result<()> {
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.path_idx as usize >= abs_paths.len() {
+            anyhow::bail!(
+                "{} {} references path_idx {}, but there are only {} paths",
+                what,
+                i,
+                entry.path_idx,
+                abs_paths.len()
+            );
+        }
+        match entry.entry_type {
+            WINDING_INCREMENT => {}
+            ABSTRACT => {
+                let seg = abs_segments.get(entry.seg_idx as usize);
+                if seg.is_none_or(|seg| seg.path_idx != entry.path_idx) {
+                    anyhow::bail!(
+                        "{} {} references seg_idx {}, which is not a segment of path {}",
+                        what,
+                        i,
+                        entry.seg_idx,
+                        entry.path_idx
+                    );
+                }
+            }
+            other => anyhow::bail!("{} {} has unsupported entry_type {}", what, i, other),
+        }
+    }
+    Ok(())
+}
+
+/// Child index (TL, TR, BL, BR) of `(x, y)` around `mid`; children are half-open, so
+/// points on a midline go to the bottom/right child.
+///
+/// This is the sample-ownership convention of the renderers, and deliberately not
+/// the one of `classify_child` in `seg_entry.rs` and `build_split_entries.wgsl`:
+/// there a segment endpoint on a midline marks the top/left child as occupied, and
+/// any other child the segment enters is found by the edge crossing tests.
+pub fn classify_child(mid: [f32; 2], x: f32, y: f32) -> usize {
+    match (x < mid[0], y < mid[1]) {
+        (true, true) => 0,
+        (false, true) => 1,
+        (true, false) => 2,
+        (false, false) => 3,
+    }
+}
+
+/// Integer fast path of [`classify_child`], free of float rounding at the midlines.
+pub fn classify_child_int(mid: [i32; 2], x: i32, y: i32) -> usize {
+    match (x < mid[0], y < mid[1]) {
+        (true, true) => 0,
+        (false, true) => 1,
+        (true, false) => 2,
+        (false, false) => 3,
+    }
+}
+
+/// Midpoint of `bbox` when its edges and midpoint are all integers.
+pub fn integer_mid(bbox: &Rect) -> Option<[i32; 2]> {
+    let [l, t, r, b] = bbox.to_integer_ltrb()?;
+    let (sx, sy) = (l as i64 + r as i64, t as i64 + b as i64);
+    (sx % 2 == 0 && sy % 2 == 0).then_some([(sx / 2) as i32, (sy / 2) as i32])
 }
 
 /// Build a quad tree by level-order subdivision.
 ///
 /// Each level processes the current frontier, subdividing cells that have more
-/// than `min_seg` ABSTRACT entries and marking the rest as leaves.
-fn build_quadtree(
+/// than `min_seg` ABSTRACT entries and marking the rest as leaves. The cells of a
+/// level are subdivided in parallel on the rayon thread pool.
+pub(crate) fn build_quadtree(
     root_bbox: Rect,
     root_entries: Vec<SegEntry>,
     max_depth: u8,
     min_seg: usize,
     abs_segments: &[AbstractLineSegment],
+    capacity_policy: CapacityPolicy,
 ) -> anyhow::Result<(Vec<QuadCell>, Vec<SegEntry>)> {
+    let reserve = capacity_policy == CapacityPolicy::ReserveFrontier;
     let mut nodes: Vec<QuadCell> = Vec::new();
     let mut leaf_entries: Vec<SegEntry> = Vec::new();
 
@@ -73,30 +709,44 @@ fn build_quadtree(
             break;
         }
 
-        let mut next_frontier: Vec<(CellId, Vec<SegEntry>)> = Vec::new();
-
-        for (parent_id, mut parent_entries) in frontier {
-            let abstract_count = parent_entries
-                .iter()
-                .filter(|e| (e.entry_type & ABSTRACT) != 0)
-                .count();
-            let parent_bbox = nodes[parent_id as usize].bbox;
+        // Cells of a level subdivide independently; each worker reuses its own scratch.
+        let splits: Vec<(CellId, CellSplit)> = frontier
+            .into_par_iter()
+            .map_init(
+                SubdivideScratch::default,
+                |scratch, (parent_id, entries)| {
+                    let split =
+                        split_cell(&nodes, parent_id, entries, min_seg, abs_segments, scratch)?;
+                    Ok((parent_id, split))
+                },
+            )
+            .collect::<anyhow::Result<_>>()?;
 
-            // Decide whether this cell needs further subdivision
-            if abstract_count <= min_seg {
-                save_as_leaf(&mut nodes, &mut leaf_entries, parent_id, parent_entries);
-                continue;
-            }
+        let mut next_frontier: Vec<(CellId, Vec<SegEntry>)> = if reserve {
+            // Every frontier cell splits into at most 4 children.
+            nodes.reserve(splits.len() * 4);
+            Vec::with_capacity(splits.len() * 4)
+        } else {
+            Vec::new()
+        };
 
-            let [mid_x, mid_y] = parent_bbox.mid_point();
-            let mid = Point { x: mid_x, y: mid_y };
-            let Some(child_bounds) = get_child_bounds(parent_bbox, mid) else {
-                save_as_leaf(&mut nodes, &mut leaf_entries, parent_id, parent_entries);
-                continue;
+        // Ids are handed out in frontier order, so the tree does not depend on how
+        // the cells were scheduled.
+        for (parent_id, split) in splits {
+            let (child_bounds, children) = match split {
+                CellSplit::Leaf(entries) => {
+                    save_as_leaf(&mut nodes, &mut leaf_entries, parent_id, entries);
+                    continue;
+                }
+                CellSplit::Children(child_bounds, children) => (child_bounds, children),
             };
 
             // --- Create child nodes ---
-            let child_ids: [CellId; 4] = std::array::from_fn(|pos| {
+            // Only quadrants that received entries get a node: a quadrant with no
+            // segments and no winding increment is outside every path.
+            // subdivide output is already in (TL, TR, BL, BR) order.
+            let mut child_ids: [Option<CellId>; 4] = [None; 4];
+            for (pos, mut entries) in children {
                 let id = nodes.len() as CellId;
                 nodes.push(QuadCell {
                     id,
@@ -105,25 +755,22 @@ fn build_quadtree(
                     children: None,
                     leaf_entry_range: None,
                 });
-                id
-            });
-            nodes[parent_id as usize].children = Some(child_ids);
-
-            let child_entries =
-                subdivide_seg_entry(&mut parent_entries, &parent_bbox, &mid, abs_segments)?;
-
-            // subdivide output is already in (TL, TR, BL, BR) order.
-            for (child_id, entries) in group_by_cell_pos(child_entries, &child_ids) {
-                if !entries.is_empty() {
-                    next_frontier.push((child_id, entries));
+                for entry in &mut entries {
+                    entry.cell_id = id;
                 }
+                child_ids[pos] = Some(id);
+                next_frontier.push((id, entries));
             }
+            nodes[parent_id as usize].children = Some(child_ids);
         }
 
         frontier = next_frontier;
     }
 
     // Remaining frontier cells reached max depth; finalize them as leaves.
+    if reserve {
+        leaf_entries.reserve(frontier.iter().map(|(_, entries)| entries.len()).sum());
+    }
     for (cell_id, entries) in frontier {
         save_as_leaf(&mut nodes, &mut leaf_entries, cell_id, entries);
     }
@@ -131,6 +778,47 @@ fn build_quadtree(
     Ok((nodes, leaf_entries))
 }
 
+/// What a frontier cell becomes at the end of a level.
+enum CellSplit {
+    Leaf(Vec<SegEntry>),
+    /// Child bounds and the non-empty `(cell_pos, entries)` groups.
+    Children([Rect; 4], Vec<(usize, Vec<SegEntry>)>),
+}
+
+/// Subdivide one frontier cell, or keep it whole if it has at most `min_seg`
+/// ABSTRACT entries or cannot be split.
+fn split_cell(
+    nodes: &[QuadCell],
+    cell_id: CellId,
+    mut entries: Vec<SegEntry>,
+    min_seg: usize,
+    abs_segments: &[AbstractLineSegment],
+    scratch: &mut SubdivideScratch,
+) -> anyhow::Result<CellSplit> {
+    let abstract_count = entries
+        .iter()
+        .filter(|e| (e.entry_type & ABSTRACT) != 0)
+        .count();
+    if abstract_count <= min_seg {
+        return Ok(CellSplit::Leaf(entries));
+    }
+
+    let bbox = nodes[cell_id as usize].bbox;
+    let [mid_x, mid_y] = bbox.mid_point();
+    let mid = Point { x: mid_x, y: mid_y };
+    let Some(child_bounds) = get_child_bounds(bbox, mid) else {
+        return Ok(CellSplit::Leaf(entries));
+    };
+
+    sort_cell_entries(&mut entries);
+    let child_entries =
+        subdivide_seg_entry_with_scratch(&mut entries, &bbox, &mid, abs_segments, scratch)?;
+    Ok(CellSplit::Children(
+        child_bounds,
+        group_by_cell_pos(child_entries),
+    ))
+}
+
 /// Mark a cell as a leaf and append its entries to the global leaf entry list.
 fn save_as_leaf(
     nodes: &mut Vec<QuadCell>,
@@ -143,38 +831,842 @@ fn save_as_leaf(
     nodes[cell_id as usize].leaf_entry_range = Some(start..leaf_entries.len());
 }
 
-/// Partition a flat entry list into per-child groups based on `cell_pos`.
+/// Partition a flat entry list into non-empty `(cell_pos, entries)` groups, each
+/// copied into an exactly sized `Vec`.
 ///
 /// Assumes entries are contiguous per cell in order TL(0), TR(1), BL(2), BR(3).
-fn group_by_cell_pos(
-    entries: Vec<SegEntry>,
-    child_ids: &[CellId; 4],
-) -> Vec<(CellId, Vec<SegEntry>)> {
-    let mut result: Vec<(CellId, Vec<SegEntry>)> = Vec::new();
-    let mut current_pos: Option<usize> = None;
-    let mut current_group: Vec<SegEntry> = Vec::new();
-
-    for mut entry in entries {
-        let pos = entry.cell_pos as usize;
-        if current_pos != Some(pos) {
-            if let Some(prev_pos) = current_pos {
-                result.push((child_ids[prev_pos], std::mem::take(&mut current_group)));
-            }
-            current_pos = Some(pos);
-        }
-        entry.cell_id = child_ids[pos];
-        current_group.push(entry);
-    }
-    if let Some(pos) = current_pos {
-        result.push((child_ids[pos], current_group));
-    }
-    result
+fn group_by_cell_pos(entries: &[SegEntry]) -> Vec<(usize, Vec<SegEntry>)> {
+    entries
+        .chunk_by(|a, b| a.cell_pos == b.cell_pos)
+        .map(|group| (group[0].cell_pos as usize, group.to_vec()))
+        .collect()
 }
 
-fn get_child_bounds(parent_bbox: Rect, mid: Point) -> Option<[Rect; 4]> {
+pub(crate) fn get_child_bounds(parent_bbox: Rect, mid: Point) -> Option<[Rect; 4]> {
     let tl = Rect::from_ltrb(parent_bbox.left(), parent_bbox.top(), mid.x, mid.y)?;
     let tr = Rect::from_ltrb(mid.x, parent_bbox.top(), parent_bbox.right(), mid.y)?;
     let bl = Rect::from_ltrb(parent_bbox.left(), mid.y, mid.x, parent_bbox.bottom())?;
     let br = Rect::from_ltrb(mid.x, mid.y, parent_bbox.right(), parent_bbox.bottom())?;
     Some([tl, tr, bl, br])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, reference_winding, solid};
+
+    fn build(scene: &TestScene, max_depth: u8, min_seg: usize) -> QuadTree {
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        QuadTree::new(
+            &scene.abs_segments,
+            &scene.abs_paths,
+            root,
+            max_depth,
+            min_seg,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn export_svg_parses_back_with_a_shape_per_cell_and_segment() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let tree = build(&scene, 3, 1);
+        let mut svg = Vec::new();
+        export_svg(&tree, Some(&scene.abs_segments), &mut svg).unwrap();
+
+        let parsed = usvg::Tree::from_data(&svg, &usvg::Options::default()).unwrap();
+        assert_eq!((parsed.size().width(), parsed.size().height()), (64.0, 64.0));
+        assert_eq!(
+            parsed.root().children().len(),
+            tree.nodes.len() + scene.abs_segments.len()
+        );
+        let svg = String::from_utf8(svg).unwrap();
+        let leaves = tree
+            .nodes
+            .iter()
+            .filter(|n| n.leaf_entry_range.is_some())
+            .count();
+        assert!(leaves > 0 && leaves < tree.nodes.len());
+        assert_eq!(svg.matches("fill-opacity").count(), leaves);
+        assert_eq!(
+            svg.matches("stroke-dasharray").count(),
+            tree.nodes.len() - leaves
+        );
+    }
+
+    #[test]
+    fn leaf_ranges_partition_entries() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let tree = build(&scene, 4, 1);
+        tree.validate_leaf_ranges().unwrap();
+
+        let mut covered = vec![0u32; tree.entries.len()];
+        for range in tree.nodes.iter().filter_map(|n| n.leaf_entry_range.clone()) {
+            for i in range {
+                covered[i] += 1;
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn max_depth_zero_keeps_every_entry_in_the_root() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let root_entries = init_root_seg_entries(&scene.abs_segments);
+        let tree = build(&scene, 0, 1);
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.nodes[0].leaf_entry_range, Some(0..root_entries.len()));
+        assert_eq!(tree.entries.len(), root_entries.len());
+    }
+
+    #[test]
+    fn validate_leaf_ranges_rejects_overlap_and_gap() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]));
+        let tree = build(&scene, 3, 1);
+        let leaf = tree
+            .nodes
+            .iter()
+            .position(|n| n.leaf_entry_range.as_ref().is_some_and(|r| r.start > 0))
+            .unwrap();
+
+        let mut overlapping = QuadTree {
+            nodes: tree.nodes.clone(),
+            entries: tree.entries.clone(),
+        };
+        let range = overlapping.nodes[leaf].leaf_entry_range.as_mut().unwrap();
+        range.start -= 1;
+        assert!(overlapping.validate_leaf_ranges().is_err());
+
+        let mut gapped = QuadTree {
+            nodes: tree.nodes.clone(),
+            entries: tree.entries.clone(),
+        };
+        gapped.entries.push(SegEntry::default());
+        assert!(gapped.validate_leaf_ranges().is_err());
+    }
+
+    #[test]
+    fn integer_and_float_classification_agree() {
+        for mid in [[0, 0], [8, 8], [16, 4], [-4, 12]] {
+            let mid_f = [mid[0] as f32, mid[1] as f32];
+            for y in -20..=20 {
+                for x in -20..=20 {
+                    assert_eq!(
+                        classify_child_int(mid, x, y),
+                        classify_child(mid_f, x as f32, y as f32),
+                        "mid {mid:?} point ({x}, {y})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn child_bounds_follow_quadrant_indices() {
+        // TL=0, TR=1, BL=2, BR=3 in `get_child_bounds`, `classify_child` and the
+        // TOP_LEFT..BOTTOM_RIGHT constants of the subdivision kernels.
+        let parent = Rect::from_ltrb(0.0, 0.0, 64.0, 32.0).unwrap();
+        let [mx, my] = parent.mid_point();
+        let bounds = get_child_bounds(parent, Point { x: mx, y: my }).unwrap();
+        for (x, y) in [(10.0, 5.0), (50.0, 5.0), (10.0, 25.0), (50.0, 25.0)] {
+            let pos = classify_child([mx, my], x, y);
+            let [l, t, r, b] = bounds[pos].to_ltrb();
+            assert!(l <= x && x <= r && t <= y && y <= b, "({x}, {y}) -> {pos}");
+        }
+        assert_eq!(bounds[1].to_ltrb(), [32.0, 0.0, 64.0, 16.0]);
+        assert_eq!(bounds[2].to_ltrb(), [0.0, 16.0, 32.0, 32.0]);
+    }
+
+    #[test]
+    fn leaf_at_returns_containing_cell() {
+        let mut scene = TestScene::new();
+        scene.push_polygon(
+            &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+            solid([0, 0, 255, 255]),
+        );
+        let tree = build(&scene, 4, 1);
+        assert!(tree.nodes.iter().any(|n| n.depth > 1));
+        assert!(tree.leaf_at(-1.0, 5.0).is_none());
+        for (x, y) in [
+            (33.0, 31.0),
+            (40.5, 20.25),
+            (48.0, 32.0),
+            (32.0, 48.0),
+            (50.0, 50.0),
+        ] {
+            let cell = tree.leaf_at(x, y).unwrap();
+            assert!(cell.children.is_none());
+            let [l, t, r, b] = cell.bbox.to_ltrb();
+            assert!(
+                l <= x && x <= r && t <= y && y <= b,
+                "({x}, {y}) not in {:?}",
+                cell.bbox
+            );
+        }
+        assert_eq!(integer_mid(&tree.nodes[0].bbox), Some([32, 32]));
+    }
+
+    #[test]
+    fn leaf_at_finds_cells_of_two_level_tree() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 60.0, 60.0, solid([255, 0, 0, 255]));
+        let tree = build(&scene, 2, 0);
+        let leaves = tree.nodes.iter().filter(|n| n.leaf_entry_range.is_some());
+        assert_eq!(leaves.count(), 16);
+
+        for ((x, y), ltrb) in [
+            ((20.0, 40.0), [16.0, 32.0, 32.0, 48.0]),
+            ((0.0, 0.0), [0.0, 0.0, 16.0, 16.0]),
+            ((63.5, 1.0), [48.0, 0.0, 64.0, 16.0]),
+            // Midlines go to the bottom/right cell at both levels.
+            ((32.0, 32.0), [32.0, 32.0, 48.0, 48.0]),
+            ((48.0, 16.5), [48.0, 16.0, 64.0, 32.0]),
+            ((64.0, 64.0), [48.0, 48.0, 64.0, 64.0]),
+        ] {
+            let cell = tree.leaf_at(x, y).unwrap();
+            assert_eq!(cell.depth, 2);
+            assert!(cell.leaf_entry_range.is_some());
+            assert_eq!(cell.bbox.to_ltrb(), ltrb, "({x}, {y})");
+        }
+        assert!(tree.leaf_at(64.5, 10.0).is_none());
+    }
+
+    #[test]
+    fn color_at_on_midlines_matches_render() {
+        use crate::render::{RenderOptions, render_to_rgba};
+        use crate::test_utils::pixel;
+
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(32.0, 8.0, 56.0, 40.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(4.0, 60.0), (16.0, 16.0), (48.0, 56.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let tree = build(&scene, 3, 1);
+        let pixels = render_to_rgba(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            64,
+            64,
+            &RenderOptions::default(),
+        );
+        // A single sample per pixel is taken at its integer coordinates, so every
+        // pixel on a multiple of 8 lies on a midline of some cell.
+        for y in 0..64u32 {
+            for x in 0..64u32 {
+                if x % 8 != 0 && y % 8 != 0 {
+                    continue;
+                }
+                let p = Point {
+                    x: x as f32,
+                    y: y as f32,
+                };
+                assert_eq!(
+                    tree.color_at(p, &scene.abs_segments, &scene.abs_paths, &scene.paints),
+                    pixel(&pixels, 64, x, y),
+                    "({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_quadrants_are_pruned() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 20.0, 20.0, solid([255, 0, 0, 255]));
+        let tree = build(&scene, 3, 1);
+
+        // Only the top-left quadrant of the root holds the rect.
+        let root_children = tree.nodes[0].children.unwrap();
+        assert!(root_children[0].is_some());
+        assert_eq!(root_children[1..], [None, None, None]);
+        assert!(tree.leaf_at(60.0, 60.0).is_none());
+
+        let split_count = tree.nodes.iter().filter(|n| n.children.is_some()).count();
+        assert!(tree.nodes.len() < 1 + 4 * split_count);
+        for node in &tree.nodes {
+            let has_entries = node
+                .leaf_entry_range
+                .as_ref()
+                .is_some_and(|r| !r.is_empty());
+            assert!(has_entries || node.children.is_some(), "{node:?}");
+        }
+    }
+
+    #[test]
+    fn color_at_picks_fill_inside_square_only() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(16.0, 16.0, 48.0, 48.0, solid([255, 0, 0, 255]))
+            .push_rect(40.0, 40.0, 60.0, 60.0, solid([0, 0, 255, 128]));
+        let tree = build(&scene, 4, 1);
+        let pick = |x, y| {
+            tree.color_at(
+                Point { x, y },
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+            )
+        };
+        assert_eq!(pick(20.0, 20.0), [255, 0, 0, 255]);
+        assert_eq!(pick(44.0, 44.0), [127, 0, 128, 255]);
+        assert_eq!(pick(4.0, 4.0), [0; 4]);
+        assert_eq!(pick(54.0, 20.0), [0; 4]);
+        assert_eq!(pick(-1.0, 20.0), [0; 4]);
+    }
+
+    #[test]
+    fn paths_filling_lists_overlapping_paths() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 40.0, solid([255, 0, 0, 255]))
+            .push_rect(20.0, 20.0, 60.0, 60.0, solid([0, 0, 255, 255]));
+        let tree = build(&scene, 4, 1);
+        let filling = |x, y| {
+            let id = tree.leaf_at(x, y).unwrap().id;
+            tree.paths_filling(id, &scene.abs_paths)
+        };
+        // Leaves inside both rects, away from every edge, and one on the red edge.
+        assert_eq!(filling(30.0, 30.0), vec![0, 1]);
+        assert_eq!(filling(10.0, 10.0), vec![0]);
+        assert_eq!(filling(50.0, 50.0), vec![1]);
+        assert_eq!(filling(39.0, 30.0), vec![0, 1]);
+        assert_eq!(tree.paths_filling(0, &scene.abs_paths), vec![0, 1]);
+    }
+
+    #[test]
+    fn overlapping_same_path_edges_match_reference_winding() {
+        let mut scene = TestScene::new();
+        // A square with a spike that runs up and back down the same line.
+        scene.push_polygon(
+            &[
+                (8.0, 8.0),
+                (56.0, 8.0),
+                (56.0, 56.0),
+                (32.0, 56.0),
+                (32.0, 20.0),
+                (32.0, 56.0),
+                (8.0, 56.0),
+            ],
+            solid([255, 0, 0, 255]),
+        );
+        // The same square traced twice in one path, with the repeat dropped.
+        let parsed = crate::svg_parser::parse_svg_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <path d="M 8 8 H 56 V 56 H 8 Z M 8 8 H 56 V 56 H 8 Z"
+                      fill="red" fill-rule="evenodd"/>
+            </svg>"#,
+            &crate::svg_parser::FlattenOptions {
+                dedup_coincident_edges: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(parsed.abs_segments.len(), 4);
+        let deduped = TestScene {
+            abs_segments: parsed.abs_segments,
+            abs_paths: parsed.abs_paths,
+            paints: parsed.paints,
+        };
+
+        for scene in [&scene, &deduped] {
+            let tree = build(scene, 4, 1);
+            for y in (1..64).step_by(3) {
+                for x in (1..64).step_by(3) {
+                    let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let winding = reference_winding(&scene.abs_segments, x, y);
+                    let inside = (8.0..56.0).contains(&x) && (8.0..56.0).contains(&y);
+                    assert_eq!(winding.abs(), inside as i32);
+                    let alpha = tree.color_at(
+                        Point { x, y },
+                        &scene.abs_segments,
+                        &scene.abs_paths,
+                        &scene.paints,
+                    )[3];
+                    assert_eq!(alpha != 0, winding % 2 != 0, "({x}, {y})");
+                }
+            }
+        }
+    }
+
+    /// Whether `tree` fills `(x, y)` the way `reference_winding` says it should,
+    /// under the path's fill rule.
+    fn fill_matches_reference(tree: &QuadTree, scene: &TestScene, x: f32, y: f32) -> bool {
+        let winding = reference_winding(&scene.abs_segments, x, y);
+        let alpha = tree.color_at(
+            Point { x, y },
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+        )[3];
+        (alpha != 0) == scene.abs_paths[0].fills(winding)
+    }
+
+    #[test]
+    fn edges_ending_on_the_split_ray_add_winding_once() {
+        // Vertices where a cell's winding ray (its mid row) meets its right edge, its
+        // midpoint, or runs on past it, so each edge sits between being counted as a
+        // ray crossing and as a shortcut: the root's (64, 32) and (32, 32), and the
+        // top-left child's (32, 16), (16, 16) and (48, 16).
+        let shapes: [&[(f32, f32)]; 6] = [
+            &[(16.0, 8.0), (64.0, 32.0), (16.0, 56.0)],
+            &[(8.0, 8.0), (32.0, 32.0), (8.0, 56.0)],
+            &[(56.0, 8.0), (32.0, 32.0), (56.0, 56.0), (60.0, 32.0)],
+            &[(4.0, 4.0), (32.0, 16.0), (16.0, 16.0), (4.0, 28.0)],
+            &[(40.0, 4.0), (48.0, 16.0), (60.0, 28.0), (36.0, 28.0)],
+            &[(8.0, 2.0), (64.0, 32.0), (32.0, 16.0), (8.0, 60.0)],
+        ];
+        for points in shapes {
+            let reversed: Vec<_> = points.iter().rev().copied().collect();
+            for points in [points.to_vec(), reversed] {
+                for fill_rule in [usvg::FillRule::EvenOdd, usvg::FillRule::NonZero] {
+                    let mut scene = TestScene::new();
+                    scene.push_polygon(&points, solid([255, 0, 0, 255]));
+                    scene.abs_paths[0].fill_rule = fill_rule;
+                    for max_depth in 0..=4 {
+                        let tree = build(&scene, max_depth, 1);
+                        for y in 0..64 {
+                            for x in 0..64 {
+                                // Off the grid, so no sample lies on an edge.
+                                let (x, y) = (x as f32 + 0.3137, y as f32 + 0.7291);
+                                assert!(
+                                    fill_matches_reference(&tree, &scene, x, y),
+                                    "{points:?} {fill_rule:?} depth {max_depth} at ({x}, {y})"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn polygons_on_cell_boundaries_match_reference_winding() {
+        // Fixed pseudo-random polygons with every vertex on a cell edge or midline
+        // down to depth 4.
+        let mut state = 12345u64;
+        let mut next = |n: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+        for _ in 0..200 {
+            let num_points = 3 + next(3) as usize;
+            let points: Vec<(f32, f32)> = (0..num_points)
+                .map(|_| ((next(17) * 4) as f32, (next(17) * 4) as f32))
+                .collect();
+            let mut scene = TestScene::new();
+            scene.push_polygon(&points, solid([255, 0, 0, 255]));
+            for max_depth in [1, 3, 5] {
+                let tree = build(&scene, max_depth, 1);
+                for y in (0..64).step_by(2) {
+                    for x in (0..64).step_by(2) {
+                        let (x, y) = (x as f32 + 0.3137, y as f32 + 0.7291);
+                        assert!(
+                            fill_matches_reference(&tree, &scene, x, y),
+                            "{points:?} depth {max_depth} at ({x}, {y})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn non_zero_fill_matches_reference_winding() {
+        let star = vec![
+            (32.0, 4.0),
+            (49.0, 58.0),
+            (4.0, 24.0),
+            (60.0, 24.0),
+            (15.0, 58.0),
+        ];
+        let square = [(8.0, 8.0), (56.0, 8.0), (56.0, 56.0), (8.0, 56.0)];
+        let reversed: Vec<_> = square.iter().rev().copied().collect();
+        for points in [star, square.to_vec(), reversed, [square, square].concat()] {
+            let mut scene = TestScene::new();
+            scene.push_polygon(&points, solid([255, 0, 0, 255]));
+            scene.abs_paths[0].fill_rule = usvg::FillRule::NonZero;
+            for max_depth in [0, 2, 4] {
+                let tree = build(&scene, max_depth, 1);
+                for y in (1..64).step_by(3) {
+                    for x in (1..64).step_by(3) {
+                        let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+                        let winding = reference_winding(&scene.abs_segments, x, y);
+                        let alpha = tree.color_at(
+                            Point { x, y },
+                            &scene.abs_segments,
+                            &scene.abs_paths,
+                            &scene.paints,
+                        )[3];
+                        assert_eq!(alpha != 0, winding != 0, "({x}, {y}) at depth {max_depth}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merged_fill_rects_coalesce_uniform_siblings() {
+        let red = [255, 0, 0, 255];
+        let mut scene = TestScene::new();
+        // The triangle splits the (16, 16)-(32, 32) cell, but the red rect hides it.
+        scene
+            .push_polygon(
+                &[(18.0, 18.0), (28.0, 20.0), (20.0, 28.0)],
+                solid([0, 0, 255, 255]),
+            )
+            .push_rect(4.0, 4.0, 60.0, 60.0, solid(red));
+        let tree = build(&scene, 3, 1);
+        let region = Rect::from_ltrb(16.0, 16.0, 32.0, 32.0).unwrap();
+        let leaves_in_region = tree
+            .nodes
+            .iter()
+            .filter(|n| n.leaf_entry_range.is_some() && n.bbox.intersect(&region) == Some(n.bbox))
+            .count();
+        assert_eq!(leaves_in_region, 4);
+
+        let rects = tree.merged_fill_rects(&scene.abs_segments, &scene.abs_paths, &scene.paints);
+        assert!(rects.contains(&(region, red)), "{rects:?}");
+        let inside_region = rects
+            .iter()
+            .filter(|(r, _)| r.intersect(&region) == Some(*r))
+            .count();
+        assert_eq!(inside_region, 1);
+        for (rect, color) in &rects {
+            let [x, y] = rect.mid_point();
+            let picked = tree.color_at(
+                Point { x, y },
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+            );
+            assert_eq!(picked, *color);
+        }
+    }
+
+    #[test]
+    fn min_seg_zero_splits_every_crossed_cell_to_max_depth() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let max_depth = 5;
+        let tree = build(&scene, max_depth, 0);
+        tree.validate_leaf_ranges().unwrap();
+
+        for node in tree.nodes.iter().filter(|n| n.leaf_entry_range.is_some()) {
+            assert!(node.depth <= max_depth);
+            let range = node.leaf_entry_range.clone().unwrap();
+            let crossed = tree.entries[range].iter().any(|e| e.entry_type & ABSTRACT != 0);
+            // Only cells without segments stop early; the rest split until max_depth.
+            assert!(!crossed || node.depth == max_depth, "{:?}", node.bbox);
+        }
+
+        let reference = build(&scene, max_depth, 1);
+        for y in (1..64).step_by(3) {
+            for x in (1..64).step_by(3) {
+                let p = Point {
+                    x: x as f32 + 0.5,
+                    y: y as f32 + 0.5,
+                };
+                let pick = |tree: &QuadTree| {
+                    tree.color_at(p, &scene.abs_segments, &scene.abs_paths, &scene.paints)
+                };
+                assert_eq!(pick(&tree), pick(&reference), "({}, {})", p.x, p.y);
+            }
+        }
+    }
+
+    #[test]
+    fn new_rejects_out_of_range_path_idx() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]));
+        scene.abs_segments[2].path_idx = 5;
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let err = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "segment 2 references path_idx 5, but there are only 1 paths"
+        );
+    }
+
+    #[test]
+    fn bytes_round_trip_renders_identically() {
+        use crate::render::{RenderOptions, render};
+
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 160]),
+            );
+        let tree = build(&scene, 4, 1);
+        let reloaded =
+            QuadTree::from_bytes(&tree.to_bytes(), &scene.abs_segments, &scene.abs_paths).unwrap();
+        assert_eq!(reloaded.entries, tree.entries);
+        assert_eq!(reloaded.nodes.len(), tree.nodes.len());
+
+        let draw = |tree: &QuadTree| {
+            let mut pixels = vec![0u8; 64 * 64 * 4];
+            render(
+                tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                64,
+                64,
+                &RenderOptions::default(),
+            );
+            pixels
+        };
+        assert_eq!(draw(&reloaded), draw(&tree));
+    }
+
+    #[test]
+    fn root_winding_seed_fills_whole_canvas() {
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let mut scene = TestScene::new();
+        scene.push_rect(8.0, 8.0, 24.0, 24.0, solid(red));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        // A segment-less path below the rect, filled only through its seed.
+        let end = scene.abs_segments.len();
+        scene.paints.push(solid(blue));
+        scene.abs_paths.insert(
+            0,
+            AbstractPath {
+                seg_start_idx: end,
+                seg_end_idx: end,
+                fill_rule: usvg::FillRule::NonZero,
+                paint_id: scene.paints.len() - 1,
+                bounding_box: root,
+                stroke_width: None,
+            },
+        );
+        for seg in &mut scene.abs_segments {
+            seg.path_idx += 1;
+        }
+        let seed = SegEntry {
+            entry_type: WINDING_INCREMENT,
+            data: 1,
+            path_idx: 0,
+            ..Default::default()
+        };
+        let tree =
+            QuadTree::with_seed_entries(&scene.abs_segments, &scene.abs_paths, root, &[seed], 4, 1)
+                .unwrap();
+
+        for (x, y) in [(0.5, 0.5), (63.5, 63.5), (40.0, 12.0), (4.0, 60.0)] {
+            let p = Point { x, y };
+            let color = tree.color_at(p, &scene.abs_segments, &scene.abs_paths, &scene.paints);
+            assert_eq!(color, blue, "({x}, {y})");
+        }
+        let p = Point { x: 16.0, y: 16.0 };
+        let color = tree.color_at(p, &scene.abs_segments, &scene.abs_paths, &scene.paints);
+        assert_eq!(color, red);
+        assert_eq!(tree.paths_filling(0, &scene.abs_paths), [0, 1]);
+
+        let bad = SegEntry {
+            path_idx: 2,
+            ..seed
+        };
+        let err =
+            QuadTree::with_seed_entries(&scene.abs_segments, &scene.abs_paths, root, &[bad], 4, 1)
+                .unwrap_err();
+        assert!(err.to_string().contains("seed entry 0"), "{err}");
+    }
+
+    #[test]
+    fn seeds_in_an_unsplit_root_stay_with_their_path() {
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 40.0, 40.0, solid(red));
+        scene.push_rect(20.0, 20.0, 60.0, 60.0, solid(blue));
+        scene.abs_paths[0].fill_rule = usvg::FillRule::NonZero;
+        let inside = Point { x: 10.0, y: 10.0 };
+        let winding = reference_winding(&scene.abs_segments[..4], inside.x, inside.y);
+        // Same sign as the rect's own winding, so the seed never cancels it out.
+        let seed = SegEntry {
+            entry_type: WINDING_INCREMENT,
+            data: winding,
+            path_idx: 0,
+            ..Default::default()
+        };
+        // The root stays a leaf both at depth 0 and when min_seg is never exceeded.
+        for (max_depth, min_seg) in [(0, 1), (4, 100)] {
+            let tree = QuadTree::with_seed_entries(
+                &scene.abs_segments,
+                &scene.abs_paths,
+                Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap(),
+                &[seed],
+                max_depth,
+                min_seg,
+            )
+            .unwrap();
+            assert_eq!(tree.nodes.len(), 1);
+            let path_order: Vec<u32> = tree.entries.iter().map(|e| e.path_idx).collect();
+            assert!(path_order.is_sorted(), "{path_order:?}");
+
+            let color_at = |x, y| {
+                tree.color_at(
+                    Point { x, y },
+                    &scene.abs_segments,
+                    &scene.abs_paths,
+                    &scene.paints,
+                )
+            };
+            // The blue rect is on top, even where the seeded red path fills too.
+            assert_eq!(color_at(30.0, 30.0), blue);
+            assert_eq!(color_at(inside.x, inside.y), red);
+        }
+    }
+
+    #[test]
+    fn parallel_build_matches_single_threaded() {
+        let parsed = crate::svg_parser::parse_svg(
+            std::path::Path::new(crate::svg_parser::SAMPLE_SVG_PATH),
+            &crate::svg_parser::FlattenOptions::default(),
+        )
+        .unwrap();
+        let root = Rect::from_ltrb(0.0, 0.0, parsed.width as f32, parsed.height as f32).unwrap();
+        let build_on = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                QuadTree::new(&parsed.abs_segments, &parsed.abs_paths, root, 6, 1).unwrap()
+            })
+        };
+        let single = build_on(1);
+        let multi = build_on(4);
+        assert!(single.nodes.len() > 1);
+        assert_eq!(multi.to_bytes(), single.to_bytes());
+    }
+
+    #[test]
+    fn overlap_of_double_loop_fills_only_under_non_zero() {
+        // Two clockwise loops in one path; their overlap, 24..40 on both axes, has
+        // winding 2 and is covered by whole leaves at depth 4.
+        let svg = |rule: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                    <path d="M 8 8 H 40 V 40 H 8 Z M 24 24 H 56 V 56 H 24 Z"
+                          fill="red" fill-rule="{rule}"/>
+                </svg>"#
+            )
+        };
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let pick = |rule: &str, x: f32, y: f32| {
+            let parsed = crate::svg_parser::parse_svg_str(
+                &svg(rule),
+                &crate::svg_parser::FlattenOptions::default(),
+            )
+            .unwrap();
+            let tree = QuadTree::new(&parsed.abs_segments, &parsed.abs_paths, root, 4, 1).unwrap();
+            let leaf = tree.leaf_at(x, y).unwrap();
+            let entries = &tree.entries[leaf.leaf_entry_range.clone().unwrap()];
+            let color = tree.color_at(
+                Point { x, y },
+                &parsed.abs_segments,
+                &parsed.abs_paths,
+                &parsed.paints,
+            );
+            (entries.to_vec(), color)
+        };
+
+        let (entries, nonzero) = pick("nonzero", 34.0, 34.0);
+        assert!(entries.iter().all(|e| e.entry_type & ABSTRACT == 0));
+        assert_eq!(entries.iter().map(|e| e.data.abs()).sum::<i32>(), 2);
+        assert_eq!(nonzero, [255, 0, 0, 255]);
+        assert_eq!(pick("evenodd", 34.0, 34.0).1[3], 0);
+        // Winding 1 fills under both rules.
+        for rule in ["nonzero", "evenodd"] {
+            assert_eq!(pick(rule, 12.0, 12.0).1, [255, 0, 0, 255], "{rule}");
+            assert_eq!(pick(rule, 50.0, 50.0).1, [255, 0, 0, 255], "{rule}");
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_bad_header_and_length() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]));
+        let bytes = build(&scene, 2, 1).to_bytes();
+        let load =
+            |bytes: &[u8]| QuadTree::from_bytes(bytes, &scene.abs_segments, &scene.abs_paths);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(load(&bad_magic).is_err());
+
+        let mut bad_version = bytes.clone();
+        bad_version[4..8].copy_from_slice(&(TREE_BYTES_VERSION + 1).to_ne_bytes());
+        let err = load(&bad_version).unwrap_err();
+        assert!(err.to_string().contains("version"), "{err}");
+
+        assert!(load(&bytes[..bytes.len() - 1]).is_err());
+        assert!(load(&bytes[..8]).is_err());
+    }
+
+    #[test]
+    fn from_bytes_rejects_trees_of_another_drawing() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let tree = build(&scene, 3, 1);
+        let bytes = tree.to_bytes();
+
+        let mut smaller = TestScene::new();
+        smaller.push_rect(4.0, 4.0, 40.0, 30.0, solid([255, 0, 0, 255]));
+        let err =
+            QuadTree::from_bytes(&bytes, &smaller.abs_segments, &smaller.abs_paths).unwrap_err();
+        assert!(err.to_string().contains("path_idx"), "{err}");
+
+        // A leaf range running past the entries, e.g. from a corrupt file.
+        let leaf = tree
+            .nodes
+            .iter()
+            .position(|n| n.leaf_entry_range.is_some())
+            .unwrap();
+        let record = size_of::<TreeBytesHeader>() + leaf * size_of::<CellRecord>();
+        let leaf_end = record + std::mem::offset_of!(CellRecord, leaf_end);
+        let mut corrupt = bytes.clone();
+        corrupt[leaf_end..leaf_end + 4].copy_from_slice(&u32::MAX.to_ne_bytes());
+        let err =
+            QuadTree::from_bytes(&corrupt, &scene.abs_segments, &scene.abs_paths).unwrap_err();
+        assert!(err.to_string().contains("outside"), "{err}");
+    }
+}
@@ -1,11 +1,380 @@
 use crate::abstract_segment::AbstractLineSegment;
+use crate::geometry::rect::Rect;
 use crate::seg_entry::{ABSTRACT, WINDING_INCREMENT};
-use crate::path::{AbstractPath, Paint};
-use crate::quad_tree::QuadTree;
+use crate::path::{resolve_paint, AbstractPath, Paint, PathGroup};
+use crate::quad_tree::{QuadCell, QuadTree};
+use crate::seg_entry::{CellId, SegEntry};
 use std::mem::swap;
+use std::ops::Range;
 
-const DRAW_DEBUG_OVERLAY: bool = true;
+/// Byte order of the pixels written by the CPU renderer.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ChannelOrder {
+    #[default]
+    Rgba,
+    /// Red and blue swapped, for APIs and texture formats that expect BGRA.
+    Bgra,
+}
+
+/// Layout of the sub-pixel samples used for anti-aliasing.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// Regular grid; near-horizontal and near-vertical edges only see
+    /// `samples_per_axis` distinct sample rows or columns.
+    #[default]
+    Grid,
+    /// Grid rotated so that every sample has its own row and column, giving
+    /// `samples_per_axis²` coverage levels on near-axis-aligned edges.
+    RotatedGrid,
+    /// Fixed best-candidate blue-noise points, trading banding for noise.
+    Poisson,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct RenderOptions {
+    /// `samples_per_axis²` sub-pixel samples are taken per pixel; 1 disables
+    /// anti-aliasing.
+    pub samples_per_axis: u32,
+    /// Where in the pixel those samples lie.
+    pub sample_pattern: SamplePattern,
+    /// Draw the debug overlay: leaf cell borders, green shortcut bars and red/blue
+    /// winding-increment bars.
+    pub debug_overlay: bool,
+    /// Colour of the debug overlay's cell borders.
+    pub overlay_color: [u8; 3],
+    /// Opacity in [0, 1] of every debug overlay mark; below 1 the marks are
+    /// blended over the render instead of replacing it.
+    pub overlay_opacity: f32,
+    /// Write premultiplied RGBA (`rgb * a`) to the final buffer, e.g. for texture
+    /// upload. Off by default: `save_png_rgba8` expects straight alpha. Like the
+    /// channel order, it only applies to the pixels the render writes.
+    pub premultiply: bool,
+    /// Colour the whole buffer is cleared to before rendering, with the paths
+    /// composited over it. `None` leaves pixels outside the leaf cells untouched
+    /// (and `render_trees` compositing over the existing content).
+    pub background: Option<[u8; 4]>,
+    /// Byte order of each output pixel; `save_png_rgba8` expects `Rgba`.
+    pub output_channel_order: ChannelOrder,
+    /// Cell whose winding ray is drawn: the horizontal ray from the cell centre
+    /// towards `far_x`, with a tick at each segment crossing: blue for edges running
+    /// down the image, red for edges running up, as in the winding overlay. `None`
+    /// draws no ray.
+    pub debug_ray_cell: Option<CellId>,
+    /// Hairline mode: a path whose edge passes through a pixel but which fills none
+    /// of its samples still covers this fraction of the pixel, in [0, 1], so shapes
+    /// thinner than the sample spacing stay visible. `None` leaves such shapes out,
+    /// as plain point sampling does.
+    pub hairline_coverage: Option<f32>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            samples_per_axis: 1,
+            sample_pattern: SamplePattern::Grid,
+            debug_overlay: false,
+            overlay_color: [255; 3],
+            overlay_opacity: 1.0,
+            premultiply: false,
+            background: None,
+            output_channel_order: ChannelOrder::Rgba,
+            debug_ray_cell: None,
+            hairline_coverage: None,
+        }
+    }
+}
+
+/// Result of evaluating every path of a leaf cell at one sample point.
+struct LeafSample {
+    /// Composited colour, premultiplied: `[r * a, g * a, b * a, a * 255]` with `a` in [0, 1].
+    premul: [f32; 4],
+    has_shortcut: bool,
+}
+
+/// Winding number at `(x, y)` of one path, from its entries in the leaf `node`, and
+/// whether a shortcut contributed to it.
+fn path_winding(
+    layer: &TreeLayer,
+    node: &QuadCell,
+    path_entries: &[SegEntry],
+    x: f32,
+    y: f32,
+) -> (i32, bool) {
+    let mut count = 0;
+    let mut has_shortcut = false;
+    for entry in path_entries {
+        let is_segment = (entry.entry_type & ABSTRACT) != 0;
+        let is_winding_inc = (entry.entry_type & WINDING_INCREMENT) != 0;
+        if is_segment {
+            let seg = &layer.abs_segments[entry.seg_idx as usize];
+            let [_, top, _, bottom] = seg.bbox_ltrb;
+            let shortcut = entry.shortcut();
+
+            // Signed like the winding increments, so non-zero fills see real winding.
+            if seg.crosses_rows() && seg.is_left(x, y) && y >= top && y < bottom {
+                count += if seg.y0 > seg.y1 { 1 } else { -1 };
+            }
+
+            if shortcut != 0 && seg.hit_shortcut(&node.bbox, x, y) {
+                has_shortcut = true;
+                count += shortcut;
+            }
+        }
+
+        if is_winding_inc {
+            count += entry.winding_increment();
+        }
+    }
+    (count, has_shortcut)
+}
+
+/// Evaluate the winding of the paths in a leaf at `(x, y)` front to back (last path
+/// first) and composite the filled ones. Paths marked in `lookup.cullable` are
+/// skipped untested where their bounding box does not hold `(x, y)`. `forced_path` is
+/// composited as if filled, whatever its winding.
+///
+/// Evaluation stops at the first opaque path that is not inside a translucent
+/// group, as nothing below it can show through; `has_shortcut` only reflects the
+/// paths that were evaluated.
+fn sample_leaf(
+    layer: &TreeLayer,
+    lookup: &PathLookup,
+    node: &QuadCell,
+    entry_range: &Range<usize>,
+    x: f32,
+    y: f32,
+    forced_path: Option<u32>,
+) -> LeafSample {
+    let entries = &layer.tree.entries[entry_range.clone()];
+    let mut has_shortcut = false;
+    // Without groups the filled paths are composited under each other as they are
+    // found; with groups they are collected and composited back to front.
+    let mut under = [0f32; 4];
+    let mut filled: Vec<(usize, [u8; 4])> = vec![];
+    // Entries are grouped by path, so walk the groups from the end of the range.
+    let mut end = entries.len();
+    while end > 0 {
+        let path_idx = entries[end - 1].path_idx;
+        let mut start = end - 1;
+        while start > 0 && entries[start - 1].path_idx == path_idx {
+            start -= 1;
+        }
+
+        let path_entries = &entries[start..end];
+        end = start;
+
+        let path = &layer.abs_paths[path_idx as usize];
+        let forced = forced_path == Some(path_idx);
+        if !forced && lookup.cullable[path_idx as usize] && !path.may_fill(x, y) {
+            continue;
+        }
+        let (count, path_shortcut) = path_winding(layer, node, path_entries, x, y);
+        has_shortcut |= path_shortcut;
+        if !path.fills(count) && !forced {
+            continue;
+        }
+        let path_idx = path_idx as usize;
+        let rgba = resolve_paint(layer.paints, path.paint_id).sample(x, y);
+        if layer.groups.is_empty() {
+            let src = premultiply(rgba, 1.0);
+            let remaining = 1.0 - under[3] / 255.0;
+            for (u, s) in under.iter_mut().zip(src) {
+                *u += s * remaining;
+            }
+            if under[3] >= 255.0 {
+                break;
+            }
+        } else {
+            filled.push((path_idx, rgba));
+            let group_opaque = lookup.group_chains[path_idx]
+                .iter()
+                .all(|&g| layer.groups[g].opacity >= 1.0);
+            if rgba[3] == 255 && group_opaque {
+                break;
+            }
+        }
+    }
+
+    let premul = if layer.groups.is_empty() {
+        under
+    } else {
+        composite_groups(&filled, &lookup.group_chains, layer.groups)
+    };
+    LeafSample {
+        premul,
+        has_shortcut,
+    }
+}
+
+/// Straight-alpha colour of `layer` at tree-space `(x, y)`, evaluated in the leaf
+/// containing the point only. Transparent where no leaf holds entries; the debug
+/// overlay is not included.
+pub(crate) fn sample_point(layer: &TreeLayer, x: f32, y: f32) -> [u8; 4] {
+    let Some(node) = layer.tree.leaf_at(x, y) else {
+        return [0; 4];
+    };
+    let Some(entry_range) = node.leaf_entry_range.as_ref() else {
+        return [0; 4];
+    };
+    let lookup = PathLookup::new(layer);
+    let sample = sample_leaf(layer, &lookup, node, entry_range, x, y, None);
+    unpremultiply(sample.premul)
+}
 
+fn premultiply(rgba: [u8; 4], opacity: f32) -> [f32; 4] {
+    let a = rgba[3] as f32 / 255.0 * opacity;
+    [
+        rgba[0] as f32 * a,
+        rgba[1] as f32 * a,
+        rgba[2] as f32 * a,
+        a * 255.0,
+    ]
+}
+
+/// Inverse of `premultiply` with `opacity` 1; fully transparent stays `[0; 4]`.
+fn unpremultiply(premul: [f32; 4]) -> [u8; 4] {
+    let mut out = [0u8; 4];
+    let alpha = premul[3];
+    if alpha > 0.0 {
+        for (o, &c) in out.iter_mut().zip(&premul[..3]) {
+            *o = (c * 255.0 / alpha).round().min(255.0) as u8;
+        }
+        out[3] = alpha.round() as u8;
+    }
+    out
+}
+
+/// Premultiplied source-over.
+fn over(dst: &mut [f32; 4], src: [f32; 4]) {
+    let remaining = 1.0 - src[3] / 255.0;
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = s + *d * remaining;
+    }
+}
+
+/// Composite `filled` (top path first) back to front. Each group opens an isolated
+/// layer (a Push) when its first path is reached and is merged into its parent at
+/// the group's opacity (a Commit) once a path outside it comes up.
+fn composite_groups(
+    filled: &[(usize, [u8; 4])],
+    group_chains: &[Vec<usize>],
+    groups: &[PathGroup],
+) -> [f32; 4] {
+    // Open layers: the root, then one per open group (innermost last).
+    let mut stack: Vec<(Option<usize>, [f32; 4])> = vec![(None, [0.0; 4])];
+    let commit = |stack: &mut Vec<(Option<usize>, [f32; 4])>| {
+        let (group, layer) = stack.pop().unwrap();
+        let opacity = groups[group.unwrap()].opacity.clamp(0.0, 1.0);
+        over(&mut stack.last_mut().unwrap().1, layer.map(|c| c * opacity));
+    };
+    for &(path_idx, rgba) in filled.iter().rev() {
+        let chain = &group_chains[path_idx];
+        let shared = stack[1..]
+            .iter()
+            .zip(chain)
+            .take_while(|((open, _), g)| *open == Some(**g))
+            .count();
+        while stack.len() > shared + 1 {
+            commit(&mut stack);
+        }
+        for &g in &chain[shared..] {
+            stack.push((Some(g), [0.0; 4]));
+        }
+        over(&mut stack.last_mut().unwrap().1, premultiply(rgba, 1.0));
+    }
+    while stack.len() > 1 {
+        commit(&mut stack);
+    }
+    stack[0].1
+}
+
+/// For every path, the indices of the groups containing it, outermost first.
+fn group_chains(groups: &[PathGroup], num_paths: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.sort_by_key(|&g| {
+        (
+            groups[g].paths.start,
+            std::cmp::Reverse(groups[g].paths.end),
+        )
+    });
+    (0..num_paths)
+        .map(|p| {
+            order
+                .iter()
+                .copied()
+                .filter(|&g| groups[g].paths.contains(&p))
+                .collect()
+        })
+        .collect()
+}
+
+/// Per-path data of a layer that `sample_leaf` looks up, computed once per layer.
+struct PathLookup {
+    /// See `group_chains`.
+    group_chains: Vec<Vec<usize>>,
+    /// See `cullable_paths`.
+    cullable: Vec<bool>,
+}
+
+impl PathLookup {
+    fn new(layer: &TreeLayer) -> Self {
+        Self {
+            group_chains: group_chains(layer.groups, layer.abs_paths.len()),
+            cullable: cullable_paths(layer),
+        }
+    }
+}
+
+/// For every path, whether `sample_leaf` may skip it where its bounding box does
+/// not hold the sample. A path's winding is the same everywhere outside its box:
+/// zero, or a seed's baseline (see `QuadTree::with_seed_entries`), so it is probed
+/// once at a corner of the root outside the box, and the path is cullable when that
+/// winding does not fill.
+fn cullable_paths(layer: &TreeLayer) -> Vec<bool> {
+    let tree = layer.tree;
+    let corners = tree.nodes.first().map_or(vec![], |root| {
+        let [l, t, r, b] = root.bbox.to_ltrb();
+        vec![(l, t), (r, t), (l, b), (r, b)]
+    });
+    layer
+        .abs_paths
+        .iter()
+        .enumerate()
+        .map(|(path_idx, path)| {
+            // A box holding every corner holds the root, and nothing is culled.
+            let Some(&(x, y)) = corners.iter().find(|&&(x, y)| !path.may_fill(x, y)) else {
+                return true;
+            };
+            let Some(node) = tree.leaf_at(x, y) else {
+                return true;
+            };
+            let Some(range) = node.leaf_entry_range.clone() else {
+                return true;
+            };
+            let winding = tree.entries[range]
+                .chunk_by(|a, b| a.path_idx == b.path_idx)
+                .find(|path_entries| path_entries[0].path_idx as usize == path_idx)
+                .map_or(0, |entries| path_winding(layer, node, entries, x, y).0);
+            !path.fills(winding)
+        })
+        .collect()
+}
+
+/// One quadtree and the scene data it was built from, placed at `offset` in the image.
+#[derive(Debug, Copy, Clone)]
+pub struct TreeLayer<'a> {
+    pub tree: &'a QuadTree,
+    pub abs_segments: &'a [AbstractLineSegment],
+    pub abs_paths: &'a [AbstractPath],
+    pub paints: &'a [Paint],
+    /// Isolated groups of paths; ranges must be nested or disjoint.
+    pub groups: &'a [PathGroup],
+    /// Image position of the tree's origin.
+    pub offset: [f32; 2],
+}
+
+/// Render into `pixels`, an RGBA8 buffer of at least `img_width * img_height * 4`
+/// bytes; see `render_to_rgba` for a version that allocates it.
 pub fn render(
     tree: &QuadTree,
     abs_segments: &[AbstractLineSegment],
@@ -14,114 +383,689 @@ pub fn render(
     pixels: &mut [u8],
     img_width: u32,
     img_height: u32,
+    options: &RenderOptions,
+) {
+    assert_buffer_size(pixels, img_width, img_height);
+    let layer = TreeLayer {
+        tree,
+        abs_segments,
+        abs_paths,
+        paints,
+        groups: &[],
+        offset: [0.0, 0.0],
+    };
+    if options.background.is_some() {
+        render_trees(&[layer], pixels, img_width, img_height, options);
+        return;
+    }
+    let mut written = vec![false; img_width as usize * img_height as usize];
+    render_layer(
+        &layer,
+        pixels,
+        img_width,
+        img_height,
+        options,
+        Some(&mut written),
+    );
+    finish_output(pixels, &written, options);
+}
+
+/// Like `render`, into a newly allocated `img_width * img_height` RGBA8 buffer.
+pub fn render_to_rgba(
+    tree: &QuadTree,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+    img_width: u32,
+    img_height: u32,
+    options: &RenderOptions,
+) -> Vec<u8> {
+    let mut pixels = vec![0u8; img_width as usize * img_height as usize * 4];
+    render(
+        tree,
+        abs_segments,
+        abs_paths,
+        paints,
+        &mut pixels,
+        img_width,
+        img_height,
+        options,
+    );
+    pixels
+}
+
+fn assert_buffer_size(pixels: &[u8], img_width: u32, img_height: u32) {
+    let needed = img_width as usize * img_height as usize * 4;
+    assert!(
+        pixels.len() >= needed,
+        "pixel buffer holds {} bytes, but {img_width}x{img_height} RGBA8 needs {needed}",
+        pixels.len()
+    );
+}
+
+/// Composite several separately built trees into `pixels`, in order (later layers
+/// on top), over whatever the buffer already holds.
+pub fn render_trees(
+    layers: &[TreeLayer],
+    pixels: &mut [u8],
+    img_width: u32,
+    img_height: u32,
+    options: &RenderOptions,
+) {
+    assert_buffer_size(pixels, img_width, img_height);
+    let len = img_width as usize * img_height as usize * 4;
+    // A background writes every pixel; otherwise only those a layer draws on.
+    let mut written = vec![options.background.is_some(); len / 4];
+    if let Some(background) = options.background {
+        for px in pixels[..len].chunks_exact_mut(4) {
+            px.copy_from_slice(&background);
+        }
+    }
+    let mut scratch = vec![0u8; len];
+    for layer in layers {
+        scratch.fill(0);
+        render_layer(layer, &mut scratch, img_width, img_height, options, None);
+        let layer_pixels = pixels[..len]
+            .chunks_exact_mut(4)
+            .zip(scratch.chunks_exact(4));
+        for ((dst, src), written) in layer_pixels.zip(written.iter_mut()) {
+            if src[3] != 0 {
+                blend_over(dst, &[src[0], src[1], src[2], src[3]]);
+                *written = true;
+            }
+        }
+    }
+    finish_output(pixels, &written, options);
+}
+
+/// Render every path of `layer` on its own, into one newly allocated RGBA8 buffer
+/// per path, e.g. for export as separate layers. Each buffer holds only that
+/// path's fill, transparent elsewhere and not occluded by the paths above it.
+///
+/// Group opacity, the background and the debug overlay are left out of the
+/// layers; the other `options` apply as in `render`. Returned in path order as
+/// `(path_idx, pixels)`.
+pub fn render_layers(
+    layer: &TreeLayer,
+    img_width: u32,
+    img_height: u32,
+    options: &RenderOptions,
+) -> Vec<(usize, Vec<u8>)> {
+    let options = RenderOptions {
+        debug_overlay: false,
+        debug_ray_cell: None,
+        background: None,
+        ..*options
+    };
+    (0..layer.abs_paths.len())
+        .map(|path_idx| {
+            let tree = single_path_tree(layer.tree, path_idx as u32);
+            let path_layer = TreeLayer {
+                tree: &tree,
+                groups: &[],
+                ..*layer
+            };
+            let mut pixels = vec![0u8; img_width as usize * img_height as usize * 4];
+            let mut written = vec![false; img_width as usize * img_height as usize];
+            render_layer(
+                &path_layer,
+                &mut pixels,
+                img_width,
+                img_height,
+                &options,
+                Some(&mut written),
+            );
+            finish_output(&mut pixels, &written, &options);
+            (path_idx, pixels)
+        })
+        .collect()
+}
+
+/// Copy of `tree` whose leaves keep only the entries of `path_idx`; leaves left
+/// without any lose their entry range, so rendering skips them.
+fn single_path_tree(tree: &QuadTree, path_idx: u32) -> QuadTree {
+    let mut entries = Vec::new();
+    let nodes = tree
+        .nodes
+        .iter()
+        .map(|node| {
+            let leaf_entry_range = node.leaf_entry_range.as_ref().and_then(|range| {
+                let start = entries.len();
+                entries.extend(
+                    tree.entries[range.clone()]
+                        .iter()
+                        .filter(|e| e.path_idx == path_idx),
+                );
+                (entries.len() > start).then_some(start..entries.len())
+            });
+            QuadCell {
+                leaf_entry_range,
+                ..node.clone()
+            }
+        })
+        .collect();
+    QuadTree { nodes, entries }
+}
+
+/// Final per-pixel conversions requested by `options`, after all compositing, of
+/// the pixels flagged in `written`. The others still hold what the caller put in
+/// the buffer, which may already be in the output format.
+fn finish_output(pixels: &mut [u8], written: &[bool], options: &RenderOptions) {
+    for (px, _) in pixels
+        .chunks_exact_mut(4)
+        .zip(written)
+        .filter(|(_, written)| **written)
+    {
+        if options.premultiply {
+            premultiply_pixels(px);
+        }
+        if options.output_channel_order == ChannelOrder::Bgra {
+            px.swap(0, 2);
+        }
+    }
+}
+
+/// Convert a straight-alpha RGBA8 buffer to premultiplied alpha in place.
+pub fn premultiply_pixels(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// `samples_per_axis²` sub-pixel offsets in [-0.5, 0.5)² laid out by `pattern`.
+///
+/// Offsets are centred on the integer sample point so that a single sample
+/// reproduces the non-anti-aliased output exactly.
+fn sample_offsets(pattern: SamplePattern, samples_per_axis: u32) -> Vec<[f32; 2]> {
+    let k = samples_per_axis;
+    let n = k * k;
+    match pattern {
+        SamplePattern::Grid => {
+            let at = |i: u32| (i as f32 + 0.5) / k as f32 - 0.5;
+            (0..n).map(|i| [at(i % k), at(i / k)]).collect()
+        }
+        // Column i, row j of the grid is stretched to its own row and column out of
+        // n, as in RGSS for k = 2.
+        SamplePattern::RotatedGrid => {
+            let at = |i: u32| (i as f32 + 0.5) / n as f32 - 0.5;
+            (0..n)
+                .map(|s| {
+                    let (i, j) = (s % k, s / k);
+                    [at(i * k + j), at(j * k + (k - 1 - i))]
+                })
+                .collect()
+        }
+        SamplePattern::Poisson => poisson_offsets(n),
+    }
+}
+
+/// Mitchell's best-candidate sampling from a fixed seed: each point is the one,
+/// out of a handful of random candidates, farthest from the points so far
+/// (wrapping around the pixel).
+fn poisson_offsets(n: u32) -> Vec<[f32; 2]> {
+    const CANDIDATES: u32 = 16;
+    let mut state = 0x9e37_79b9u32;
+    let mut random = || {
+        // xorshift32
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+    };
+    let mut points: Vec<[f32; 2]> = vec![[0.0, 0.0]];
+    while (points.len() as u32) < n {
+        let mut best = [0.0, 0.0];
+        let mut best_dist = -1.0;
+        for _ in 0..CANDIDATES {
+            let candidate = [random(), random()];
+            let dist = points
+                .iter()
+                .map(|p| {
+                    let dx = (p[0] - candidate[0]).abs();
+                    let dy = (p[1] - candidate[1]).abs();
+                    dx.min(1.0 - dx).powi(2) + dy.min(1.0 - dy).powi(2)
+                })
+                .fold(f32::INFINITY, f32::min);
+            if dist > best_dist {
+                best = candidate;
+                best_dist = dist;
+            }
+        }
+        points.push(best);
+    }
+    points
+}
+
+/// Paths of the leaf with a segment through the pixel centred on `(x, y)` that
+/// fill none of its samples, i.e. features too thin for the sample pattern.
+fn missed_paths(
+    layer: &TreeLayer,
+    node: &QuadCell,
+    entry_range: &Range<usize>,
+    offsets: &[[f32; 2]],
+    x: f32,
+    y: f32,
+) -> Vec<u32> {
+    let entries = &layer.tree.entries[entry_range.clone()];
+    let (l, t, r, b) = (x - 0.5, y - 0.5, x + 0.5, y + 0.5);
+    entries
+        .chunk_by(|a, b| a.path_idx == b.path_idx)
+        .filter(|path_entries| {
+            path_entries.iter().any(|e| {
+                if e.entry_type & ABSTRACT == 0 {
+                    return false;
+                }
+                let seg = &layer.abs_segments[e.seg_idx as usize];
+                let [sl, st, sr, sb] = seg.bbox_ltrb;
+                if !seg.crosses_rows() || sl > r || sr < l || st > b || sb < t {
+                    return false;
+                }
+                // The line passes through the pixel unless all corners are on one side.
+                let sides = [(l, t), (r, t), (l, b), (r, b)].map(|(cx, cy)| seg.eval(cx, cy));
+                !(sides.iter().all(|&s| s > 0.0) || sides.iter().all(|&s| s < 0.0))
+            })
+        })
+        .filter(|path_entries| {
+            let path = &layer.abs_paths[path_entries[0].path_idx as usize];
+            offsets.iter().all(|&[dx, dy]| {
+                let (count, _) = path_winding(layer, node, path_entries, x + dx, y + dy);
+                !path.fills(count)
+            })
+        })
+        .map(|path_entries| path_entries[0].path_idx)
+        .collect()
+}
+
+/// Render one layer, overwriting the pixels covered by its leaf cells and flagging
+/// them in `written`, if given.
+fn render_layer(
+    layer: &TreeLayer,
+    pixels: &mut [u8],
+    img_width: u32,
+    img_height: u32,
+    options: &RenderOptions,
+    mut written: Option<&mut [bool]>,
 ) {
+    let tree = layer.tree;
+    let [off_x, off_y] = layer.offset;
+    let lookup = PathLookup::new(layer);
+    let offsets = sample_offsets(options.sample_pattern, options.samples_per_axis.max(1));
+    let overlay_alpha = (options.overlay_opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+
     for node in &tree.nodes {
         let Some(entry_range) = node.leaf_entry_range.as_ref() else {
             continue;
         };
 
-        let left = node.bbox.left().max(0.0) as u32;
-        let right = node.bbox.right().min(img_width as f32) as u32;
-        let top = node.bbox.top().max(0.0) as u32;
-        let bottom = node.bbox.bottom().min(img_height as f32) as u32;
-        let line_paint = Paint::SolidColor { rgba: [255; 4] };
+        let [l, t, r, b] = node.bbox.to_ltrb();
+        let Some(image_bbox) = Rect::from_ltrb(l + off_x, t + off_y, r + off_x, b + off_y) else {
+            continue;
+        };
+        let [left, top, right, bottom] = cell_pixel_bounds(&image_bbox, img_width, img_height);
+        // Off-screen cells clamp to an empty range; drawing their border would
+        // still light up the image edge they were clamped to.
+        if left >= right || top >= bottom {
+            continue;
+        }
+        let [r, g, b] = options.overlay_color;
+        let line_paint = Paint::SolidColor {
+            rgba: [r, g, b, overlay_alpha],
+        };
+        let winc: i32 = tree.entries[entry_range.clone()]
+            .iter()
+            .filter(|e| (e.entry_type & WINDING_INCREMENT) != 0)
+            .map(|e| e.winding_increment())
+            .sum();
+        // Without segments every path's winding is the same all over the leaf, so
+        // only the paint can vary across a pixel: one sample at its centre is enough.
+        let pixel_offsets: &[[f32; 2]] = if has_edges(&tree.entries[entry_range.clone()]) {
+            &offsets
+        } else {
+            &[[0.0, 0.0]]
+        };
+        let sample_count = pixel_offsets.len() as f32;
 
         for y in top..bottom {
             for x in left..right {
-                let mut out = [0u8; 4];
                 let mut has_shortcut = false;
-                let mut winc = 0;
-                let mut count = 0;
-                for i in entry_range.start..entry_range.end {
-                    let entry = &tree.entries[i];
-                    let next_entry = if i == entry_range.end - 1 {
-                        None
-                    } else {
-                        Some(&tree.entries[i + 1])
-                    };
-                    let is_segment = (entry.entry_type & ABSTRACT) != 0;
-                    let is_winding_inc = (entry.entry_type & WINDING_INCREMENT) != 0;
-                    if is_segment {
-                        let seg = &abs_segments[entry.seg_idx as usize];
-                        let [_, top, _, bottom] = seg.bbox_ltrb;
-                        let shortcut = entry.data;
-
-                        if seg.is_left(x as f32, y as f32)
-                            && (y as f32) >= top
-                            && (y as f32) < bottom
-                        {
-                            count += 1;
-                        }
-
-                        if shortcut != 0 && seg.hit_shortcut(&node.bbox, x as f32, y as f32) {
-                            has_shortcut = true;
-                            count += shortcut as i32;
-                        }
+                // Samples are composited individually and then averaged in premultiplied
+                // space, so paths sharing an edge add up to full coverage without seams.
+                let mut acc = [0f32; 4];
+                for &[dx, dy] in pixel_offsets {
+                    let sample = sample_leaf(
+                        layer,
+                        &lookup,
+                        node,
+                        entry_range,
+                        x as f32 + dx - off_x,
+                        y as f32 + dy - off_y,
+                        None,
+                    );
+                    has_shortcut |= sample.has_shortcut;
+                    for (a, c) in acc.iter_mut().zip(sample.premul) {
+                        *a += c;
                     }
-
-                    if is_winding_inc {
-                        count += entry.data;
-                        winc += entry.data;
-                    }
-
-                    let last_entry_in_path = next_entry
-                        .is_some_and(|ne| ne.path_idx != entry.path_idx)
-                        || next_entry.is_none();
-                    if last_entry_in_path {
-                        if count % 2 != 0 {
-                            let path = &abs_paths[entry.path_idx as usize];
-                            if let Paint::SolidColor { rgba } = paints[path.paint_id] {
-                                out[..4].copy_from_slice(&rgba);
-                            }
+                }
+                let mut premul = acc.map(|a| a / sample_count);
+                if let Some(floor) = options.hairline_coverage {
+                    let floor = floor.clamp(0.0, 1.0);
+                    let (px, py) = (x as f32 - off_x, y as f32 - off_y);
+                    for path_idx in missed_paths(layer, node, entry_range, pixel_offsets, px, py) {
+                        // The rest of the pixel as sampled, `floor` of it as if the
+                        // path covered the centre, under whatever paths lie above it.
+                        let forced =
+                            sample_leaf(layer, &lookup, node, entry_range, px, py, Some(path_idx));
+                        for (p, f) in premul.iter_mut().zip(forced.premul) {
+                            *p += (f - *p) * floor;
                         }
-                        count = 0;
                     }
                 }
-                if DRAW_DEBUG_OVERLAY {
+                let mut out = unpremultiply(premul);
+
+                if options.debug_overlay {
                     let debug_line_width = 6;
-                    if has_shortcut && right - debug_line_width <= x && x <= right {
-                        out[..4].copy_from_slice(&[0, 255, 0, 255]);
+                    if has_shortcut && right.saturating_sub(debug_line_width) <= x && x <= right {
+                        blend_over(&mut out, &[0, 255, 0, overlay_alpha]);
                     };
                     let mut curr = 8;
                     for _i in 0..winc.abs() as usize {
-                        if winc != 0 && right - (curr + debug_line_width) <= x && x <= right - curr
+                        if winc != 0
+                            && right.saturating_sub(curr + debug_line_width) <= x
+                            && x <= right.saturating_sub(curr)
                         {
                             if winc < 0 {
-                                out[..4].copy_from_slice(&[255, 0, 0, 255]);
+                                blend_over(&mut out, &[255, 0, 0, overlay_alpha]);
                             } else {
-                                out[..4].copy_from_slice(&[0, 0, 255, 255]);
+                                blend_over(&mut out, &[0, 0, 255, overlay_alpha]);
                             }
                         }
                         curr += debug_line_width + 6;
                     }
                 }
 
-                let base = ((y * img_width + x) * 4) as usize;
-                pixels[base..base + 4].copy_from_slice(&out);
+                let index = (y * img_width + x) as usize;
+                pixels[index * 4..index * 4 + 4].copy_from_slice(&out);
+                if let Some(written) = written.as_deref_mut() {
+                    written[index] = true;
+                }
             }
         }
 
-        if DRAW_DEBUG_OVERLAY {
-            draw_line(left, top, right - 1, top, pixels, img_width, img_height, &line_paint);
-            draw_line(right - 1, top, right - 1, bottom - 1, pixels, img_width, img_height, &line_paint);
-            draw_line(left, bottom - 1, right - 1, bottom - 1, pixels, img_width, img_height, &line_paint);
-            draw_line(left, top, left, bottom - 1, pixels, img_width, img_height, &line_paint);
+        if options.debug_overlay {
+            let (l, t) = (left as i32, top as i32);
+            let (r, b) = (right as i32 - 1, bottom as i32 - 1);
+            draw_line(l, t, r, t, pixels, img_width, img_height, &line_paint);
+            draw_line(r, t, r, b, pixels, img_width, img_height, &line_paint);
+            draw_line(l, b, r, b, pixels, img_width, img_height, &line_paint);
+            draw_line(l, t, l, b, pixels, img_width, img_height, &line_paint);
         }
     }
+
+    if let Some(node) = options.debug_ray_cell.and_then(|id| tree.nodes.get(id as usize)) {
+        draw_winding_ray(layer, node, pixels, img_width, img_height, overlay_alpha);
+    }
 }
 
+/// Draw the winding ray of `node` to the image's right edge (where it is clipped on
+/// its way to `far_x`) and tick every segment of the layer that crosses it.
+fn draw_winding_ray(
+    layer: &TreeLayer,
+    node: &QuadCell,
+    pixels: &mut [u8],
+    img_width: u32,
+    img_height: u32,
+    alpha: u8,
+) {
+    let [off_x, off_y] = layer.offset;
+    let [cx, cy] = node.bbox.mid_point();
+    let (x0, y) = ((cx + off_x).round() as i32, (cy + off_y).round() as i32);
+    let ray_paint = Paint::SolidColor {
+        rgba: [255, 255, 0, alpha],
+    };
+    draw_line(x0, y, img_width as i32, y, pixels, img_width, img_height, &ray_paint);
+
+    for seg in layer.abs_segments {
+        if (seg.y0 <= cy) == (seg.y1 <= cy) {
+            continue;
+        }
+        let Some(x) = seg.x_at_scanline(cy).filter(|&x| x >= cx) else {
+            continue;
+        };
+        let rgba = if seg.y1 > seg.y0 {
+            [0, 0, 255, alpha]
+        } else {
+            [255, 0, 0, alpha]
+        };
+        let x = (x + off_x).round() as i32;
+        let tick = Paint::SolidColor { rgba };
+        draw_line(x, y - 3, x, y + 3, pixels, img_width, img_height, &tick);
+    }
+}
+
+/// Cheap approximate thumbnail of the tree's root bbox at `img_width` x
+/// `img_height`: every pixel takes the dominant colour of the leaf under it,
+/// sampled once per leaf, instead of its own winding. Edges inside a leaf are
+/// lost, so the preview is only as sharp as the subdivision.
+pub fn render_preview(
+    tree: &QuadTree,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+    img_width: u32,
+    img_height: u32,
+) -> Vec<u8> {
+    let mut pixels = vec![0u8; img_width as usize * img_height as usize * 4];
+    let Some(root) = tree.nodes.first() else {
+        return pixels;
+    };
+    let layer = TreeLayer {
+        tree,
+        abs_segments,
+        abs_paths,
+        paints,
+        groups: &[],
+        offset: [0.0, 0.0],
+    };
+    let lookup = PathLookup::new(&layer);
+    let [left, top, right, bottom] = root.bbox.to_ltrb();
+    let scale_x = (right - left) / img_width as f32;
+    let scale_y = (bottom - top) / img_height as f32;
+
+    let mut leaf_colors: Vec<Option<[u8; 4]>> = vec![None; tree.nodes.len()];
+    for (i, px) in pixels.chunks_exact_mut(4).enumerate() {
+        let x = left + ((i as u32 % img_width) as f32 + 0.5) * scale_x;
+        let y = top + ((i as u32 / img_width) as f32 + 0.5) * scale_y;
+        let Some(node) = tree.leaf_at(x, y) else {
+            continue;
+        };
+        let color = *leaf_colors[node.id as usize].get_or_insert_with(|| {
+            let Some(entry_range) = node.leaf_entry_range.as_ref() else {
+                return [0; 4];
+            };
+            dominant_leaf_color(&layer, &lookup, node, entry_range)
+        });
+        px.copy_from_slice(&color);
+    }
+    pixels
+}
+
+/// Most frequent colour over a 3x3 grid of samples in the leaf.
+fn dominant_leaf_color(
+    layer: &TreeLayer,
+    lookup: &PathLookup,
+    node: &QuadCell,
+    entry_range: &Range<usize>,
+) -> [u8; 4] {
+    let [l, t, r, b] = node.bbox.to_ltrb();
+    let mut counts: Vec<([u8; 4], u32)> = Vec::with_capacity(9);
+    for j in 0..3 {
+        for i in 0..3 {
+            let x = l + (r - l) * (i as f32 + 0.5) / 3.0;
+            let y = t + (b - t) * (j as f32 + 0.5) / 3.0;
+            let sample = sample_leaf(layer, lookup, node, entry_range, x, y, None);
+            let color = unpremultiply(sample.premul);
+            match counts.iter_mut().find(|(c, _)| *c == color) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((color, 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, n)| n)
+        .map_or([0; 4], |(c, _)| c)
+}
+
+/// Visualise subdivision: each pixel gets the grey of the deepest cell containing it,
+/// white at depth 0 down to black at the tree's deepest level. Pixels outside the
+/// root are left untouched.
+pub fn render_depth_map(tree: &QuadTree, pixels: &mut [u8], img_width: u32, img_height: u32) {
+    let deepest = tree.nodes.iter().map(|n| n.depth).max().unwrap_or(0);
+    // Nodes are in level order, so deeper cells overwrite their ancestors. Pruned
+    // empty quadrants keep their parent's grey.
+    for node in &tree.nodes {
+        let grey = if deepest == 0 {
+            255
+        } else {
+            255 - (node.depth as u32 * 255 / deepest as u32) as u8
+        };
+        let [left, top, right, bottom] = cell_pixel_bounds(&node.bbox, img_width, img_height);
+        for y in top..bottom {
+            let row = ((y * img_width + left) * 4) as usize;
+            let end = ((y * img_width + right) * 4) as usize;
+            for px in pixels[row..end].chunks_exact_mut(4) {
+                px.copy_from_slice(&[grey, grey, grey, 255]);
+            }
+        }
+    }
+}
+
+/// Segment tests `render` does for one sample per pixel: each leaf's on-screen
+/// pixel count times its ABSTRACT entries, summed. Winding increments are only
+/// added, not tested, so they are left out; anti-aliasing multiplies the total
+/// by the sample count.
+pub fn segment_test_work(tree: &QuadTree, img_width: u32, img_height: u32) -> u64 {
+    tree.nodes
+        .iter()
+        .filter_map(|node| {
+            let range = node.leaf_entry_range.clone()?;
+            let [left, top, right, bottom] = cell_pixel_bounds(&node.bbox, img_width, img_height);
+            let area = right.saturating_sub(left) as u64 * bottom.saturating_sub(top) as u64;
+            let segments = tree.entries[range]
+                .iter()
+                .filter(|e| (e.entry_type & ABSTRACT) != 0)
+                .count() as u64;
+            Some(area * segments)
+        })
+        .sum()
+}
+
+/// Whether a leaf's entries include segments, i.e. edges that can pass through it.
+fn has_edges(entries: &[SegEntry]) -> bool {
+    entries.iter().any(|e| (e.entry_type & ABSTRACT) != 0)
+}
+
+/// On-screen pixels `render` supersamples: those of leaves holding segments. Pixels
+/// of the other leaves take a single sample whatever `samples_per_axis` is.
+pub fn supersampled_pixel_count(tree: &QuadTree, img_width: u32, img_height: u32) -> u64 {
+    tree.nodes
+        .iter()
+        .filter_map(|node| {
+            let range = node.leaf_entry_range.clone()?;
+            if !has_edges(&tree.entries[range]) {
+                return None;
+            }
+            let [left, top, right, bottom] = cell_pixel_bounds(&node.bbox, img_width, img_height);
+            Some(right.saturating_sub(left) as u64 * bottom.saturating_sub(top) as u64)
+        })
+        .sum()
+}
+
+/// Like `segment_test_work`, leaving out the segments of paths `render` culls at
+/// each sample. For the path culling bench.
+#[doc(hidden)]
+pub fn segment_test_work_culled(
+    tree: &QuadTree,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    img_width: u32,
+    img_height: u32,
+) -> u64 {
+    let cullable = cullable_paths(&TreeLayer {
+        tree,
+        abs_segments,
+        abs_paths,
+        paints: &[],
+        groups: &[],
+        offset: [0.0, 0.0],
+    });
+    let mut work = 0;
+    for node in &tree.nodes {
+        let Some(range) = node.leaf_entry_range.clone() else {
+            continue;
+        };
+        let paths: Vec<(&AbstractPath, bool, u64)> = tree.entries[range]
+            .chunk_by(|a, b| a.path_idx == b.path_idx)
+            .map(|path_entries| {
+                let segments = path_entries
+                    .iter()
+                    .filter(|e| (e.entry_type & ABSTRACT) != 0)
+                    .count() as u64;
+                let path_idx = path_entries[0].path_idx as usize;
+                (&abs_paths[path_idx], cullable[path_idx], segments)
+            })
+            .collect();
+        let [left, top, right, bottom] = cell_pixel_bounds(&node.bbox, img_width, img_height);
+        for y in top..bottom {
+            for x in left..right {
+                work += paths
+                    .iter()
+                    .filter(|(path, cullable, _)| !cullable || path.may_fill(x as f32, y as f32))
+                    .map(|(_, _, segments)| segments)
+                    .sum::<u64>();
+            }
+        }
+    }
+    work
+}
+
+/// Pixel range `[left, top, right, bottom)` owned by a cell, clamped to the image.
+/// Integer-aligned cells take an exact integer path; others truncate their edges.
+fn cell_pixel_bounds(bbox: &Rect, img_width: u32, img_height: u32) -> [u32; 4] {
+    if let Some([l, t, r, b]) = bbox.to_integer_ltrb() {
+        let (w, h) = (img_width as i64, img_height as i64);
+        return [
+            (l as i64).clamp(0, w) as u32,
+            (t as i64).clamp(0, h) as u32,
+            (r as i64).clamp(0, w) as u32,
+            (b as i64).clamp(0, h) as u32,
+        ];
+    }
+    let (w, h) = (img_width as f32, img_height as f32);
+    [
+        bbox.left().clamp(0.0, w) as u32,
+        bbox.top().clamp(0.0, h) as u32,
+        bbox.right().clamp(0.0, w) as u32,
+        bbox.bottom().clamp(0.0, h) as u32,
+    ]
+}
+
+/// Draw a 1px line; endpoints may lie outside the image, only the on-screen part is drawn.
 pub fn draw_line(
-    x1: u32,
-    y1: u32,
-    x2: u32,
-    y2: u32,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
     pixels: &mut [u8],
     img_width: u32,
     img_height: u32,
     paint: &Paint,
 ) {
-    let w = (x1 as i32 - x2 as i32).abs();
-    let h = (y1 as i32 - y2 as i32).abs();
+    let w = (x1 - x2).abs();
+    let h = (y1 - y2).abs();
     let is_steep = w < h;
     let mut x1 = x1;
     let mut x2 = x2;
@@ -138,17 +1082,25 @@ pub fn draw_line(
     if x1 == x2 {
         return;
     }
-    let mut y = y1 as f32;
-    let step = (y2 as i32 - y1 as i32) as f32 / (x2 as i32 - x1 as i32) as f32;
+    let (major_dim, minor_dim) = if is_steep {
+        (img_height as i32, img_width as i32)
+    } else {
+        (img_width as i32, img_height as i32)
+    };
+    let step = (y2 - y1) as f32 / (x2 - x1) as f32;
     if let Paint::SolidColor { rgba } = paint {
-        for x in x1..=x2 {
-            let py = y.round() as u32;
+        // Clamp the major axis to [0, dim) and skip minor coordinates outside it,
+        // so nothing negative is ever cast to u32.
+        for x in x1.max(0)..=x2.min(major_dim - 1) {
+            let y = (y1 as f32 + step * (x - x1) as f32).round() as i32;
+            if !(0..minor_dim).contains(&y) {
+                continue;
+            }
             if is_steep {
-                set_pixel(py, x, img_width, img_height, rgba, pixels);
+                set_pixel(y as u32, x as u32, img_width, img_height, rgba, pixels);
             } else {
-                set_pixel(x, py, img_width, img_height, rgba, pixels);
+                set_pixel(x as u32, y as u32, img_width, img_height, rgba, pixels);
             }
-            y += step;
         }
     }
 }
@@ -158,5 +1110,1002 @@ fn set_pixel(x: u32, y: u32, width: u32, height: u32, rgba: &[u8; 4], pixels: &m
         return;
     }
     let base = ((y * width + x) * 4) as usize;
-    pixels[base..base + 4].copy_from_slice(rgba);
+    blend_over(&mut pixels[base..base + 4], rgba);
+}
+
+/// Source-over composite of straight-alpha `src` onto straight-alpha `dst`;
+/// an opaque `src` replaces `dst` exactly.
+fn blend_over(dst: &mut [u8], src: &[u8; 4]) {
+    let sa = src[3] as f32 / 255.0;
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    if out_a <= 0.0 {
+        dst[..4].fill(0);
+        return;
+    }
+    for c in 0..3 {
+        let v = (src[c] as f32 * sa + dst[c] as f32 * da * (1.0 - sa)) / out_a;
+        dst[c] = v.round().min(255.0) as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, pixel, solid};
+
+    #[test]
+    fn depth_map_is_darker_where_subdivided_deeper() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        // Detail only in the top-left corner; the rest of the canvas stays shallow.
+        scene.push_polygon(
+            &[(2.0, 2.0), (14.0, 3.0), (6.0, 13.0)],
+            solid([255, 0, 0, 255]),
+        );
+        scene.push_polygon(
+            &[(3.0, 9.0), (12.0, 6.0), (10.0, 14.0)],
+            solid([0, 0, 255, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 4, 1).unwrap();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        render_depth_map(&tree, &mut pixels, w, h);
+
+        let depth_at = |x: u32, y: u32| tree.leaf_at(x as f32 + 0.5, y as f32 + 0.5).unwrap().depth;
+        let (deep, shallow) = ((5, 5), (50, 50));
+        assert!(depth_at(deep.0, deep.1) > 1);
+        // The bottom-right quadrant is empty and pruned, so it shows the root's depth.
+        assert_eq!(tree.nodes[0].children.unwrap()[3], None);
+        let deep_px = pixel(&pixels, w, deep.0, deep.1);
+        let shallow_px = pixel(&pixels, w, shallow.0, shallow.1);
+        assert_eq!(shallow_px, [255, 255, 255, 255]);
+        assert!(deep_px[0] < shallow_px[0], "{deep_px:?} vs {shallow_px:?}");
+        assert_eq!(deep_px[0], deep_px[2]);
+    }
+
+    #[test]
+    fn shared_edge_has_no_seam_with_anti_aliasing() {
+        let (w, h) = (16u32, 16u32);
+        let color = [200, 50, 50, 255];
+        let tl = (2.0, 2.0);
+        let br = (14.0, 14.0);
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(&[tl, (14.0, 2.0), br], solid(color))
+            .push_polygon(&[tl, br, (2.0, 14.0)], solid(color));
+
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        let options = RenderOptions {
+            samples_per_axis: 4,
+            ..Default::default()
+        };
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut pixels,
+            w,
+            h,
+            &options,
+        );
+
+        // The diagonal shared by both triangles must be fully covered.
+        for y in 3..13 {
+            for x in 3..13 {
+                assert_eq!(pixel(&pixels, w, x, y), color, "seam at ({x}, {y})");
+            }
+        }
+        // The outer edge is still anti-aliased.
+        let edge = pixel(&pixels, w, 2, 8);
+        assert!(edge[3] > 0 && edge[3] < 255, "edge alpha {}", edge[3]);
+        assert_eq!(edge[..3], color[..3]);
+    }
+
+    #[test]
+    fn only_leaves_with_edges_are_supersampled() {
+        let (w, h) = (64u32, 64u32);
+        let color = [30, 120, 220, 255];
+        let circle: Vec<_> = (0..48)
+            .map(|i| {
+                let a = i as f32 / 48.0 * std::f32::consts::TAU;
+                (32.0 + 28.0 * a.cos(), 32.0 + 28.0 * a.sin())
+            })
+            .collect();
+        let mut scene = TestScene::new();
+        scene.push_polygon(&circle, solid(color));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 5, 0).unwrap();
+        let render_with = |samples_per_axis| {
+            let options = RenderOptions {
+                samples_per_axis,
+                ..Default::default()
+            };
+            render_to_rgba(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                w,
+                h,
+                &options,
+            )
+        };
+        let (aliased, smooth) = (render_with(1), render_with(4));
+
+        let supersampled = supersampled_pixel_count(&tree, w, h);
+        assert!(supersampled * 2 < (w * h) as u64, "{supersampled} pixels supersampled");
+        for node in &tree.nodes {
+            let Some(range) = node.leaf_entry_range.clone() else {
+                continue;
+            };
+            if has_edges(&tree.entries[range]) {
+                continue;
+            }
+            let [left, top, right, bottom] = cell_pixel_bounds(&node.bbox, w, h);
+            for y in top..bottom {
+                for x in left..right {
+                    assert_eq!(pixel(&smooth, w, x, y), pixel(&aliased, w, x, y));
+                }
+            }
+        }
+        assert_eq!(pixel(&smooth, w, 32, 32), color);
+        let edge = pixel(&smooth, w, 4, 32);
+        assert!(edge[3] > 0 && edge[3] < 255, "edge alpha {}", edge[3]);
+    }
+
+    #[test]
+    fn rotated_grid_smooths_near_horizontal_edges() {
+        let (w, h) = (64u32, 16u32);
+        let mut scene = TestScene::new();
+        // The top edge drops one pixel over the image width, crossing the sample
+        // rows of row 4 along the way.
+        scene.push_polygon(
+            &[(0.0, 4.0), (64.0, 5.0), (64.0, 12.0), (0.0, 12.0)],
+            solid([0, 0, 0, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let edge_alphas = |sample_pattern: SamplePattern| {
+            let options = RenderOptions {
+                samples_per_axis: 4,
+                sample_pattern,
+                ..Default::default()
+            };
+            let pixels = render_to_rgba(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                w,
+                h,
+                &options,
+            );
+            // Column 0 is also cut by the left edge.
+            (1..w).map(|x| pixel(&pixels, w, x, 4)[3]).collect::<Vec<_>>()
+        };
+        let levels = |alphas: &[u8]| {
+            let mut alphas = alphas.to_vec();
+            alphas.sort();
+            alphas.dedup();
+            alphas.len()
+        };
+        let max_step = |alphas: &[u8]| {
+            alphas
+                .windows(2)
+                .map(|p| p[0].abs_diff(p[1]))
+                .max()
+                .unwrap()
+        };
+
+        let grid = edge_alphas(SamplePattern::Grid);
+        let rotated = edge_alphas(SamplePattern::RotatedGrid);
+        assert!(levels(&rotated) > levels(&grid), "{rotated:?} vs {grid:?}");
+        assert!(max_step(&rotated) < max_step(&grid), "{rotated:?} vs {grid:?}");
+        assert_eq!((rotated[0], rotated[62]), (grid[0], grid[62]));
+    }
+
+    #[test]
+    fn sample_patterns_stay_inside_the_pixel() {
+        for pattern in [
+            SamplePattern::Grid,
+            SamplePattern::RotatedGrid,
+            SamplePattern::Poisson,
+        ] {
+            assert_eq!(sample_offsets(pattern, 1), [[0.0, 0.0]], "{pattern:?}");
+            let offsets = sample_offsets(pattern, 3);
+            assert_eq!(offsets.len(), 9);
+            for [dx, dy] in offsets {
+                assert!((-0.5..0.5).contains(&dx) && (-0.5..0.5).contains(&dy));
+            }
+        }
+    }
+
+    #[test]
+    fn segment_test_work_matches_per_pixel_count() {
+        let (w, h) = (48u32, 40u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_polygon(
+                &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+                solid([255, 0, 0, 255]),
+            )
+            .push_rect(10.0, 10.0, 30.0, 30.0, solid([0, 0, 255, 255]));
+        // The root overhangs the image, so clipped leaves count only their visible part.
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 4, 1).unwrap();
+
+        let mut brute_force = 0u64;
+        for y in 0..h {
+            for x in 0..w {
+                let Some(leaf) = tree.leaf_at(x as f32 + 0.5, y as f32 + 0.5) else {
+                    continue;
+                };
+                let range = leaf.leaf_entry_range.clone().unwrap();
+                brute_force += tree.entries[range]
+                    .iter()
+                    .filter(|e| e.entry_type & ABSTRACT != 0)
+                    .count() as u64;
+            }
+        }
+        assert!(brute_force > 0);
+        assert_eq!(segment_test_work(&tree, w, h), brute_force);
+    }
+
+    #[test]
+    fn path_bbox_culling_cuts_work_not_pixels() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        for i in 0..4 {
+            let (x, y) = (4.0 + 15.0 * i as f32, 6.0 + 13.0 * i as f32);
+            scene
+                .push_polygon(
+                    &[(x, y), (x + 11.0, y + 2.5), (x + 3.0, y + 9.5)],
+                    solid([255, 60 * i as u8, 0, 255]),
+                )
+                .push_rect(x + 4.5, y + 1.0, x + 8.0, y + 12.0, solid([0, 0, 255, 160]));
+        }
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        // Shallow, so leaves hold several paths each.
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap();
+        let unculled: Vec<AbstractPath> = scene
+            .abs_paths
+            .iter()
+            .map(|p| AbstractPath {
+                bounding_box: root,
+                ..*p
+            })
+            .collect();
+        let draw = |abs_paths: &[AbstractPath]| {
+            let options = RenderOptions {
+                samples_per_axis: 4,
+                ..RenderOptions::default()
+            };
+            render_to_rgba(&tree, &scene.abs_segments, abs_paths, &scene.paints, w, h, &options)
+        };
+
+        assert_eq!(draw(&scene.abs_paths), draw(&unculled));
+        let work = segment_test_work(&tree, w, h);
+        let culled_work =
+            |abs_paths| segment_test_work_culled(&tree, &scene.abs_segments, abs_paths, w, h);
+        assert_eq!(culled_work(&unculled), work);
+        let culled = culled_work(&scene.abs_paths);
+        assert!(culled * 4 < work, "{culled} of {work}");
+    }
+
+    #[test]
+    fn seeded_path_fills_outside_its_bounding_box() {
+        let (w, h) = (32u32, 32u32);
+        let blue = [0, 0, 255, 255];
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 12.0, 12.0, solid(blue));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let seed = SegEntry {
+            entry_type: WINDING_INCREMENT,
+            data: 1,
+            path_idx: 0,
+            ..Default::default()
+        };
+        let tree =
+            QuadTree::with_seed_entries(&scene.abs_segments, &scene.abs_paths, root, &[seed], 2, 1)
+                .unwrap();
+        let rgba = render_to_rgba(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+        // Outside the rect's bounding box the seed alone fills, both in leaves the
+        // rect's edges pass through and in those it never reaches.
+        for (x, y) in [(1, 1), (14, 14), (20, 2), (28, 28)] {
+            assert_eq!(pixel(&rgba, w, x, y), blue, "({x}, {y})");
+        }
+        let work = segment_test_work(&tree, w, h);
+        let culled = segment_test_work_culled(&tree, &scene.abs_segments, &scene.abs_paths, w, h);
+        assert_eq!(culled, work);
+    }
+
+    #[test]
+    fn hairline_coverage_keeps_thin_rect_visible() {
+        let (w, h) = (16u32, 16u32);
+        let mut scene = TestScene::new();
+        // 0.3px wide, between the sample columns of 1 and 2x2 samples per pixel.
+        scene.push_rect(10.35, 4.0, 10.65, 12.0, solid([0, 0, 255, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+        let draw = |samples_per_axis, hairline_coverage| {
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &RenderOptions {
+                    samples_per_axis,
+                    hairline_coverage,
+                    ..RenderOptions::default()
+                },
+            );
+            pixels
+        };
+
+        for samples_per_axis in [1, 2] {
+            let plain = draw(samples_per_axis, None);
+            assert!(plain.chunks(4).all(|p| p[3] == 0));
+
+            let hairline = draw(samples_per_axis, Some(0.25));
+            for x in [10, 11] {
+                assert_eq!(pixel(&hairline, w, x, 8), [0, 0, 255, 64]);
+            }
+            assert_eq!(pixel(&hairline, w, 9, 8)[3], 0);
+            assert_eq!(pixel(&hairline, w, 10, 14)[3], 0);
+        }
+
+        // Coverage the samples already see is left alone.
+        let full = draw(4, None);
+        assert_eq!(draw(4, Some(0.25)), full);
+        assert!(pixel(&full, w, 10, 8)[3] > 0);
+    }
+
+    #[test]
+    fn missing_paint_renders_opaque_black() {
+        let (w, h) = (16u32, 16u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(0.0, 0.0, 16.0, 16.0, solid([0, 255, 0, 255]))
+            .push_rect(4.0, 4.0, 12.0, 12.0, solid([255, 0, 0, 128]));
+        // The second path's paint_id now points past the end.
+        scene.paints.truncate(1);
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut pixels,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+        assert_eq!(pixel(&pixels, w, 8, 8), [0, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, w, 1, 1), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn only_horizontal_segments_render_nothing() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        // Degenerate polygons folded onto one row; some lie on cell split lines.
+        for (x0, x1, y) in [
+            (2.0, 60.0, 10.0),
+            (0.0, 64.0, 32.0),
+            (5.5, 40.25, 47.5),
+            (16.0, 48.0, 16.0),
+        ] {
+            scene.push_polygon(&[(x0, y), (x1, y)], solid([255, 0, 0, 255]));
+        }
+        scene.push_polygon(
+            &[(4.0, 20.0), (30.0, 20.0), (50.0, 20.0)],
+            solid([0, 0, 255, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 4, 1).unwrap();
+        assert!(
+            tree.entries
+                .iter()
+                .all(|e| e.entry_type & WINDING_INCREMENT == 0 || e.data == 0)
+        );
+
+        for samples_per_axis in [1, 4] {
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &RenderOptions {
+                    samples_per_axis,
+                    background: Some([0, 0, 0, 0]),
+                    ..RenderOptions::default()
+                },
+            );
+            assert!(
+                pixels.iter().all(|&c| c == 0),
+                "samples_per_axis {samples_per_axis}"
+            );
+        }
+    }
+
+    #[test]
+    fn premultiply_option_scales_rgb_by_alpha() {
+        let (w, h) = (8u32, 8u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(0.0, 0.0, 8.0, 8.0, solid([255, 255, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        for (premultiply, expected) in [(false, [255, 255, 255, 128]), (true, [128; 4])] {
+            let options = RenderOptions {
+                premultiply,
+                ..Default::default()
+            };
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &options,
+            );
+            assert_eq!(pixel(&pixels, w, 4, 4), expected);
+        }
+    }
+
+    #[test]
+    fn debug_ray_runs_along_cell_mid_y() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 60.0, 60.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap();
+        let cell = tree.leaf_at(10.0, 10.0).unwrap();
+        assert_eq!(cell.bbox.to_ltrb(), [0.0, 0.0, 16.0, 16.0]);
+
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        let options = RenderOptions {
+            debug_ray_cell: Some(cell.id),
+            ..Default::default()
+        };
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut pixels,
+            w,
+            h,
+            &options,
+        );
+
+        let yellow = [255, 255, 0, 255];
+        for x in [10, 24, 40, 56] {
+            assert_eq!(pixel(&pixels, w, x, 8), yellow, "ray at x = {x}");
+            assert_eq!(pixel(&pixels, w, x, 9), [255, 0, 0, 255]);
+        }
+        assert_ne!(pixel(&pixels, w, 4, 8), yellow);
+        // The rect's right edge goes from top to bottom: a blue tick. The left edge
+        // is behind the ray's start and gets none.
+        assert_eq!(pixel(&pixels, w, 60, 6), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, w, 60, 11), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, w, 4, 6), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn render_to_rgba_sizes_buffer_for_any_resolution() {
+        let (w, h) = (48u32, 20u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 40.0, 16.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap();
+        let options = RenderOptions::default();
+
+        let owned = render_to_rgba(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            w,
+            h,
+            &options,
+        );
+        assert_eq!(owned.len(), (w * h * 4) as usize);
+        let mut in_place = vec![0u8; owned.len()];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut in_place,
+            w,
+            h,
+            &options,
+        );
+        assert_eq!(owned, in_place);
+    }
+
+    #[test]
+    #[should_panic(expected = "needs 6400")]
+    fn render_rejects_short_buffer() {
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 36.0, 36.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, 40.0, 40.0).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let mut pixels = vec![0u8; 1000];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut pixels,
+            40,
+            40,
+            &RenderOptions::default(),
+        );
+    }
+
+    #[test]
+    fn fills_composite_over_background() {
+        let (w, h) = (16u32, 16u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(4.0, 4.0, 12.0, 12.0, solid([255, 0, 0, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap();
+        let options = RenderOptions {
+            background: Some([255; 4]),
+            ..Default::default()
+        };
+        let pixels = render_to_rgba(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            w,
+            h,
+            &options,
+        );
+        assert_eq!(pixel(&pixels, w, 8, 8), [255, 127, 127, 255]);
+        // Outside the rect, including pruned empty quadrants.
+        assert_eq!(pixel(&pixels, w, 1, 1), [255; 4]);
+        assert_eq!(pixel(&pixels, w, 14, 14), [255; 4]);
+    }
+
+    #[test]
+    fn bgra_output_swaps_red_and_blue() {
+        let (w, h) = (8u32, 8u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(0.0, 0.0, 8.0, 8.0, solid([200, 100, 20, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let render_in = |output_channel_order| {
+            let options = RenderOptions {
+                output_channel_order,
+                ..Default::default()
+            };
+            render_to_rgba(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                w,
+                h,
+                &options,
+            )
+        };
+        let rgba = render_in(ChannelOrder::Rgba);
+        let bgra = render_in(ChannelOrder::Bgra);
+        assert_eq!(pixel(&rgba, w, 4, 4), [200, 100, 20, 255]);
+        for (c, b) in rgba.chunks_exact(4).zip(bgra.chunks_exact(4)) {
+            assert_eq!([c[2], c[1], c[0], c[3]], b);
+        }
+    }
+
+    #[test]
+    fn output_conversions_leave_unwritten_pixels_alone() {
+        let (w, h) = (32u32, 32u32);
+        let mut scene = TestScene::new();
+        // The bottom-right quadrant stays empty, so the tree has no leaf there.
+        scene.push_rect(2.0, 2.0, 14.0, 14.0, solid([200, 100, 20, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 2, 1).unwrap();
+        let options = RenderOptions {
+            premultiply: true,
+            output_channel_order: ChannelOrder::Bgra,
+            ..Default::default()
+        };
+        let existing = [10, 20, 30, 40];
+        let layer = TreeLayer {
+            tree: &tree,
+            abs_segments: &scene.abs_segments,
+            abs_paths: &scene.abs_paths,
+            paints: &scene.paints,
+            groups: &[],
+            offset: [0.0, 0.0],
+        };
+        for use_layers in [false, true] {
+            let mut pixels = existing.repeat((w * h) as usize);
+            if use_layers {
+                render_trees(&[layer], &mut pixels, w, h, &options);
+            } else {
+                render(
+                    &tree,
+                    &scene.abs_segments,
+                    &scene.abs_paths,
+                    &scene.paints,
+                    &mut pixels,
+                    w,
+                    h,
+                    &options,
+                );
+            }
+            assert_eq!(
+                pixel(&pixels, w, 24, 24),
+                existing,
+                "render_trees: {use_layers}"
+            );
+            if !use_layers {
+                assert_eq!(pixel(&pixels, w, 8, 8), [10, 50, 100, 128]);
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_translucent_rects_blend_source_over() {
+        let (w, h) = (32u32, 32u32);
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(2.0, 2.0, 20.0, 20.0, solid([255, 0, 0, 128]))
+            .push_rect(12.0, 12.0, 30.0, 30.0, solid([0, 0, 255, 128]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+        let pixels = render_to_rgba(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+
+        assert_eq!(pixel(&pixels, w, 5, 5), [255, 0, 0, 128]);
+        assert_eq!(pixel(&pixels, w, 25, 25), [0, 0, 255, 128]);
+        // Blue over red: a = 0.5 + 0.5 * 0.5, rgb = (0.5 * 0.5 * red + 0.5 * blue) / a.
+        let a = 128.0f32 / 255.0;
+        let out_a = a + a * (1.0 - a);
+        let expected = [
+            (255.0 * a * (1.0 - a) / out_a).round() as u8,
+            0,
+            (255.0 * a / out_a).round() as u8,
+            (out_a * 255.0).round() as u8,
+        ];
+        assert_eq!(pixel(&pixels, w, 16, 16), expected);
+    }
+
+    #[test]
+    fn preview_of_filled_square_is_mostly_its_colour() {
+        let red = [255, 0, 0, 255];
+        let mut scene = TestScene::new();
+        scene.push_rect(8.0, 8.0, 56.0, 56.0, solid(red));
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 4, 1).unwrap();
+        let (w, h) = (16u32, 16u32);
+        let preview =
+            render_preview(&tree, &scene.abs_segments, &scene.abs_paths, &scene.paints, w, h);
+        assert_eq!(preview.len(), (w * h * 4) as usize);
+
+        // The square covers preview pixels 2..14 on each axis; the 16px leaves along
+        // its edges take one colour for both sides, so allow a quarter to miss.
+        let inside: Vec<_> = (2..14)
+            .flat_map(|y| (2..14).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(&preview, w, x, y))
+            .collect();
+        let red_count = inside.iter().filter(|&&px| px == red).count();
+        assert!(red_count * 4 >= inside.len() * 3, "{red_count} of {}", inside.len());
+        assert_eq!(pixel(&preview, w, 0, 0)[3], 0);
+    }
+
+    #[test]
+    fn draw_line_clips_part_above_top_edge() {
+        let (w, h) = (8u32, 8u32);
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        let white = [255; 4];
+        // Steep line from (2, -6) to (5, 5): only y in 0..=5 is on screen.
+        draw_line(2, -6, 5, 5, &mut pixels, w, h, &solid(white));
+
+        let lit: Vec<(u32, u32)> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&pixels, w, x, y) == white)
+            .collect();
+        let rows: Vec<u32> = lit.iter().map(|&(_, y)| y).collect();
+        assert_eq!(rows, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(lit.first(), Some(&(4, 0)));
+        assert_eq!(lit.last(), Some(&(5, 5)));
+    }
+
+    #[test]
+    fn overlay_off_leaves_cell_borders_clean() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        scene.push_polygon(
+            &[(4.0, 6.0), (58.0, 4.0), (60.0, 44.0), (20.0, 60.0)],
+            solid([255, 0, 0, 255]),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+        let white_on_borders = |debug_overlay: bool| {
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            let options = RenderOptions {
+                debug_overlay,
+                ..Default::default()
+            };
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &options,
+            );
+            let mut count = 0;
+            for node in tree.nodes.iter().filter(|n| n.leaf_entry_range.is_some()) {
+                let [l, t, r, b] = cell_pixel_bounds(&node.bbox, w, h);
+                for x in l..r {
+                    for y in [t, b - 1] {
+                        count += (pixel(&pixels, w, x, y) == [255; 4]) as usize;
+                    }
+                }
+            }
+            count
+        };
+        assert!(white_on_borders(true) > 0);
+        assert_eq!(white_on_borders(false), 0);
+    }
+
+    #[test]
+    fn overlay_skips_cells_right_of_image() {
+        let (w, h) = (64u32, 64u32);
+        let mut scene = TestScene::new();
+        // Only the right half of the root has content, and it is all off-screen.
+        scene.push_rect(80.0, 8.0, 120.0, 56.0, solid([255, 0, 0, 255]));
+        for root in [
+            Rect::from_ltrb(0.0, 0.0, 128.0, 64.0).unwrap(),
+            Rect::from_ltrb(0.0, 0.0, 128.5, 64.0).unwrap(),
+        ] {
+            let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            let options = RenderOptions {
+                debug_overlay: true,
+                ..Default::default()
+            };
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &options,
+            );
+            assert!(pixels.iter().all(|&c| c == 0), "{root:?}");
+        }
+    }
+
+    #[test]
+    fn overlay_blends_at_half_opacity() {
+        let (w, h) = (16u32, 16u32);
+        let mut scene = TestScene::new();
+        scene.push_rect(0.0, 0.0, 16.0, 16.0, solid([255, 0, 0, 255]));
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+
+        let render_with = |overlay_opacity: f32| {
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            let options = RenderOptions {
+                debug_overlay: true,
+                overlay_color: [0, 0, 255],
+                overlay_opacity,
+                ..Default::default()
+            };
+            render(
+                &tree,
+                &scene.abs_segments,
+                &scene.abs_paths,
+                &scene.paints,
+                &mut pixels,
+                w,
+                h,
+                &options,
+            );
+            pixels
+        };
+
+        // Cell border on the top row, away from the corners.
+        assert_eq!(pixel(&render_with(1.0), w, 8, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&render_with(0.5), w, 8, 0), [127, 0, 128, 255]);
+        // Interior pixels are untouched.
+        assert_eq!(pixel(&render_with(0.5), w, 8, 8), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn render_trees_composites_layers_in_order() {
+        let (w, h) = (16u32, 8u32);
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let root = Rect::from_ltrb(0.0, 0.0, 8.0, 8.0).unwrap();
+        let mut bottom = TestScene::new();
+        bottom.push_rect(0.0, 0.0, 8.0, 8.0, solid(red));
+        let mut top = TestScene::new();
+        top.push_rect(0.0, 0.0, 8.0, 8.0, solid(blue));
+        let bottom_tree =
+            QuadTree::new(&bottom.abs_segments, &bottom.abs_paths, root, 0, 1).unwrap();
+        let top_tree = QuadTree::new(&top.abs_segments, &top.abs_paths, root, 0, 1).unwrap();
+
+        let layers = [
+            TreeLayer {
+                tree: &bottom_tree,
+                abs_segments: &bottom.abs_segments,
+                abs_paths: &bottom.abs_paths,
+                paints: &bottom.paints,
+                groups: &[],
+                offset: [0.0, 0.0],
+            },
+            TreeLayer {
+                tree: &top_tree,
+                abs_segments: &top.abs_segments,
+                abs_paths: &top.abs_paths,
+                paints: &top.paints,
+                groups: &[],
+                offset: [4.0, 0.0],
+            },
+        ];
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        render_trees(&layers, &mut pixels, w, h, &RenderOptions::default());
+
+        assert_eq!(pixel(&pixels, w, 2, 4), red);
+        // Overlap: the later layer wins.
+        assert_eq!(pixel(&pixels, w, 6, 4), blue);
+        assert_eq!(pixel(&pixels, w, 10, 4), blue);
+        assert_eq!(pixel(&pixels, w, 14, 4), [0; 4]);
+    }
+
+    #[test]
+    fn render_layers_gives_each_path_only_its_own_fill() {
+        let (w, h) = (16u32, 16u32);
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let mut scene = TestScene::new();
+        scene.push_rect(1.0, 1.0, 11.0, 11.0, solid(red)).push_rect(
+            5.0,
+            5.0,
+            15.0,
+            15.0,
+            solid(blue),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 3, 1).unwrap();
+        let layer = TreeLayer {
+            tree: &tree,
+            abs_segments: &scene.abs_segments,
+            abs_paths: &scene.abs_paths,
+            paints: &scene.paints,
+            groups: &[],
+            offset: [0.0, 0.0],
+        };
+        let options = RenderOptions {
+            debug_overlay: true,
+            ..RenderOptions::default()
+        };
+        let layers = render_layers(&layer, w, h, &options);
+
+        assert_eq!(layers.len(), scene.abs_paths.len());
+        for (i, (path_idx, pixels)) in layers.iter().enumerate() {
+            assert_eq!(*path_idx, i);
+            let [l, t, r, b] = scene.abs_paths[i].bounding_box.to_ltrb();
+            let colour = [red, blue][i];
+            for y in 0..h {
+                for x in 0..w {
+                    let inside = (l..r).contains(&(x as f32)) && (t..b).contains(&(y as f32));
+                    let expected = if inside { colour } else { [0; 4] };
+                    assert_eq!(pixel(pixels, w, x, y), expected, "path {i} at ({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn topmost_filled_path_wins() {
+        let (w, h) = (16u32, 16u32);
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let mut scene = TestScene::new();
+        scene.push_rect(1.0, 1.0, 11.0, 11.0, solid(red)).push_rect(
+            5.0,
+            5.0,
+            15.0,
+            15.0,
+            solid(blue),
+        );
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &scene.abs_segments,
+            &scene.abs_paths,
+            &scene.paints,
+            &mut pixels,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+
+        assert_eq!(pixel(&pixels, w, 3, 3), red);
+        assert_eq!(pixel(&pixels, w, 8, 8), blue);
+        assert_eq!(pixel(&pixels, w, 12, 12), blue);
+    }
+
+    #[test]
+    fn isolated_group_composites_at_group_opacity() {
+        let (w, h) = (16u32, 16u32);
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let render_scene = |alpha: u8, groups: &[PathGroup]| {
+            let mut scene = TestScene::new();
+            scene
+                .push_rect(1.0, 1.0, 11.0, 11.0, solid([255, 0, 0, alpha]))
+                .push_rect(5.0, 5.0, 15.0, 15.0, solid([0, 0, 255, alpha]));
+            let tree = QuadTree::new(&scene.abs_segments, &scene.abs_paths, root, 0, 1).unwrap();
+            let layer = TreeLayer {
+                tree: &tree,
+                abs_segments: &scene.abs_segments,
+                abs_paths: &scene.abs_paths,
+                paints: &scene.paints,
+                groups,
+                offset: [0.0, 0.0],
+            };
+            let mut pixels = vec![0u8; (w * h * 4) as usize];
+            render_trees(&[layer], &mut pixels, w, h, &RenderOptions::default());
+            pixels
+        };
+
+        // Two half-transparent shapes: the overlap shows both.
+        let ungrouped = render_scene(128, &[]);
+        assert_eq!(pixel(&ungrouped, w, 8, 8), [85, 0, 170, 192]);
+        // Two opaque shapes in a half-opaque group: only the top one shows.
+        let group = PathGroup {
+            paths: 0..2,
+            opacity: 128.0 / 255.0,
+        };
+        let grouped = render_scene(255, &[group]);
+        assert_eq!(pixel(&grouped, w, 8, 8), [0, 0, 255, 128]);
+        assert_eq!(pixel(&grouped, w, 3, 3), [255, 0, 0, 128]);
+    }
 }
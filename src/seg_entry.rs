@@ -20,6 +20,10 @@ static NEXT_CELL_UNIQUE_ID: AtomicU32 = AtomicU32::new(0);
 pub type CellId = u32;
 
 fn half_open_eval(seg: &AbstractLineSegment, sample: &Point) -> i32 {
+    // A degenerate segment has no side, so no cell edge can cross it.
+    if seg.is_point() {
+        return 0;
+    }
     let [left, top, right, bottom] = seg.bbox_ltrb;
 
     // Outside the segment's vertical bbox: use a clipped constant sign.
@@ -288,7 +292,8 @@ impl SplitData {
         let contains_in_parent = |x: f32, y: f32| -> bool {
             x >= bound.left() && x <= bound.right() && y >= bound.top() && y <= bound.bottom()
         };
-        // Endpoints inside the parent quad mark their child cell as occupied.
+        // Endpoints inside the parent quad mark their child cell as occupied; unlike
+        // `quad_tree::classify_child`, one on a midline counts for the top/left child.
         if contains_in_parent(seg.x0, seg.y0) {
             split_info |= fill(classify_child(seg.x0, seg.y0));
         }
@@ -425,10 +430,10 @@ impl SplitData {
 
 /// Per-entry record stored in a quad cell.
 #[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
 pub struct SegEntry {
     pub entry_type: u32,
-    pub data: i32,    // WINDING_INCREMENT: increment value; ABSTRACT: shortcut flag
+    pub data: i32,    // Read through `shortcut()` or `winding_increment()`
     pub seg_idx: u32, // Index into abs_segments; only valid for ABSTRACT entries
     pub path_idx: u32,
     pub cell_pos: u32,
@@ -436,6 +441,34 @@ pub struct SegEntry {
     pub _pad: [u32; 2],
 }
 
+impl SegEntry {
+    /// Shortcut flag of an `ABSTRACT` entry: +1 or -1 when the segment's shortcut
+    /// edge adds that winding in the cell, 0 when it has none.
+    ///
+    /// `data` means something else for other entry types; debug builds panic if
+    /// this is not an `ABSTRACT` entry.
+    pub fn shortcut(&self) -> i32 {
+        debug_assert!(
+            self.entry_type & ABSTRACT != 0,
+            "shortcut() of a non-ABSTRACT entry (type {:#x})",
+            self.entry_type
+        );
+        self.data
+    }
+
+    /// Winding added to every point of the cell by a `WINDING_INCREMENT` entry.
+    ///
+    /// Debug builds panic if this is not a `WINDING_INCREMENT` entry.
+    pub fn winding_increment(&self) -> i32 {
+        debug_assert!(
+            self.entry_type & WINDING_INCREMENT != 0,
+            "winding_increment() of a non-WINDING_INCREMENT entry (type {:#x})",
+            self.entry_type
+        );
+        self.data
+    }
+}
+
 impl Default for SegEntry {
     fn default() -> Self {
         SegEntry {
@@ -481,6 +514,22 @@ pub fn init_root_seg_entries(abs_segments: &[AbstractLineSegment]) -> Vec<SegEnt
     entries
 }
 
+/// Canonicalize entry order to what the subdivision kernels assume.
+/// - Each cell's entries must be contiguous; cells keep their place.
+/// - Within a cell, entries are ordered by path, with ABSTRACT entries (by `seg_idx`)
+///   before the WINDING_INCREMENT of the same path.
+pub fn sort_cell_entries(entries: &mut [SegEntry]) {
+    for cell in entries.chunk_by_mut(|a, b| a.cell_id == b.cell_id) {
+        cell.sort_by_key(|e| {
+            (
+                e.path_idx,
+                (e.entry_type & WINDING_INCREMENT) != 0,
+                e.seg_idx,
+            )
+        });
+    }
+}
+
 /// Kernel 1 of 4.2 Parallel subdivision
 /// Assuming parent_entries already ordered SEGMENTs - WINDING for each cell.
 pub fn build_split_entries(
@@ -490,6 +539,26 @@ pub fn build_split_entries(
     abs_segments: &[AbstractLineSegment],
 ) -> Vec<SplitEntry> {
     let mut split_entries: Vec<SplitEntry> = vec![];
+    build_split_entries_into(
+        parent_bound,
+        mid_point,
+        seg_entries,
+        abs_segments,
+        &mut split_entries,
+    );
+    split_entries
+}
+
+/// `build_split_entries` writing into `split_entries`, which is cleared first so its
+/// allocation can be reused across cells.
+pub fn build_split_entries_into(
+    parent_bound: &Rect,
+    mid_point: &Point,
+    seg_entries: &mut [SegEntry],
+    abs_segments: &[AbstractLineSegment],
+    split_entries: &mut Vec<SplitEntry>,
+) {
+    split_entries.clear();
     let unique_id = NEXT_CELL_UNIQUE_ID.fetch_add(1, Ordering::Relaxed);
 
     for entry in &mut *seg_entries {
@@ -501,7 +570,7 @@ pub fn build_split_entries(
             let seg = &abs_segments[seg_idx as usize];
             let edge_info = EdgeIntersectionInfo::new(&seg, &parent_bound, &mid_point);
             let split_data =
-                SplitData::new(&seg, entry.data, &edge_info, &parent_bound, &mid_point);
+                SplitData::new(&seg, entry.shortcut(), &edge_info, &parent_bound, &mid_point);
             split_entries.push(SplitEntry {
                 split_data,
                 offsets: [0u32; 4],
@@ -513,7 +582,7 @@ pub fn build_split_entries(
         }
 
         if is_winding_inc_entry {
-            let parent_winding = entry.data;
+            let parent_winding = entry.winding_increment();
             split_entries.push(SplitEntry {
                 split_data: SplitData {
                     winding: [parent_winding; 4],
@@ -528,13 +597,16 @@ pub fn build_split_entries(
             });
         }
     }
-    split_entries
 }
 
 /// Kernel 2 of 4.2 Parallel subdivision
 /// - Mutate split_entries.
 /// - Assuming the Vec<SegmentEntry> and Vec<AbstractLineSegment> is ordered by path already.
 /// - Executes inclusive scan to generate the last winding per path, per cell.
+/// - Coincident segments of one path are not deduplicated: each is a crossing, as in
+///   SVG. An edge traversed back and forth cancels out, and a contour repeated in the
+///   same direction doubles the winding (which even-odd then leaves unfilled) unless
+///   `FlattenOptions::dedup_coincident_edges` dropped the repeats when parsing.
 pub fn consolidate_winding_inc(split_entries: &mut Vec<SplitEntry>) {
     assert!(split_entries.len() > 0);
 
@@ -591,8 +663,21 @@ pub fn update_to_global_offset(entries: &mut [SplitEntry]) -> u32 {
 
 /// Kernel 4, scatter split entries into child `SegEntry` records.
 pub fn split_to_seg_entry(split_entries: &mut [SplitEntry], out_vec_size: u32) -> Vec<SegEntry> {
+    let mut seg_entries: Vec<SegEntry> = vec![];
+    split_to_seg_entry_into(split_entries, out_vec_size, &mut seg_entries);
+    seg_entries
+}
+
+/// `split_to_seg_entry` writing into `seg_entries`, which is cleared and resized to
+/// `out_vec_size` first so its allocation can be reused across cells.
+pub fn split_to_seg_entry_into(
+    split_entries: &mut [SplitEntry],
+    out_vec_size: u32,
+    seg_entries: &mut Vec<SegEntry>,
+) {
     assert!(split_entries.last().is_some());
-    let mut seg_entries: Vec<SegEntry> = vec![SegEntry::default(); out_vec_size as usize];
+    seg_entries.clear();
+    seg_entries.resize(out_vec_size as usize, SegEntry::default());
 
     for &cell in &[TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT, BOTTOM_RIGHT] {
         let ci = cell as usize;
@@ -656,7 +741,13 @@ pub fn split_to_seg_entry(split_entries: &mut [SplitEntry], out_vec_size: u32) -
             start = end;
         }
     }
-    seg_entries
+}
+
+/// Buffers reused by `subdivide_seg_entry_with_scratch` from one cell to the next.
+#[derive(Debug, Default)]
+pub struct SubdivideScratch {
+    split_entries: Vec<SplitEntry>,
+    child_entries: Vec<SegEntry>,
 }
 
 /// Execute Kernel 1 ~ 4 of 4.2 Parallel Subdivision on CPU.
@@ -666,12 +757,38 @@ pub fn subdivide_seg_entry(
     parent_mid_point: &Point,
     abs_segments: &[AbstractLineSegment],
 ) -> anyhow::Result<Vec<SegEntry>> {
-    let mut split_entries =
-        build_split_entries(parent_bound, parent_mid_point, seg_entries, abs_segments);
-    consolidate_winding_inc(&mut split_entries);
-    let out_vec_size = update_to_global_offset(&mut split_entries);
-    let next_seg_entries = split_to_seg_entry(&mut split_entries, out_vec_size);
-    Ok(next_seg_entries)
+    let mut scratch = SubdivideScratch::default();
+    subdivide_seg_entry_with_scratch(
+        seg_entries,
+        parent_bound,
+        parent_mid_point,
+        abs_segments,
+        &mut scratch,
+    )?;
+    Ok(scratch.child_entries)
+}
+
+/// `subdivide_seg_entry` without per-cell allocation once `scratch` has grown to the
+/// largest cell; the child entries borrow from `scratch` until its next use.
+pub fn subdivide_seg_entry_with_scratch<'s>(
+    seg_entries: &mut [SegEntry],
+    parent_bound: &Rect,
+    parent_mid_point: &Point,
+    abs_segments: &[AbstractLineSegment],
+    scratch: &'s mut SubdivideScratch,
+) -> anyhow::Result<&'s [SegEntry]> {
+    let split_entries = &mut scratch.split_entries;
+    build_split_entries_into(
+        parent_bound,
+        parent_mid_point,
+        seg_entries,
+        abs_segments,
+        split_entries,
+    );
+    consolidate_winding_inc(split_entries);
+    let out_vec_size = update_to_global_offset(split_entries);
+    split_to_seg_entry_into(split_entries, out_vec_size, &mut scratch.child_entries);
+    Ok(&scratch.child_entries)
 }
 
 fn print_entries<T: Debug>(entries: &[T], mut cell_pos: impl FnMut(&T) -> u32) {
@@ -735,3 +852,209 @@ fn print_split_data(split_data: &SplitData) {
         print!("] ");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, reference_winding, solid};
+
+    fn is_winding(e: &SegEntry) -> bool {
+        (e.entry_type & WINDING_INCREMENT) != 0
+    }
+
+    /// Winding of `(x, y)` in `cell` from one path's entries, counted as the
+    /// renderer does.
+    fn cell_winding(
+        entries: &[SegEntry],
+        cell: &Rect,
+        abs_segments: &[AbstractLineSegment],
+        x: f32,
+        y: f32,
+    ) -> i32 {
+        let mut count = 0;
+        for e in entries {
+            if is_winding(e) {
+                count += e.winding_increment();
+                continue;
+            }
+            let seg = &abs_segments[e.seg_idx as usize];
+            let [_, top, _, bottom] = seg.bbox_ltrb;
+            if seg.crosses_rows() && seg.is_left(x, y) && y >= top && y < bottom {
+                count += if seg.y0 > seg.y1 { 1 } else { -1 };
+            }
+            if e.shortcut() != 0 && seg.hit_shortcut(cell, x, y) {
+                count += e.shortcut();
+            }
+        }
+        count
+    }
+
+    fn entry(entry_type: EntryFlags, data: i32) -> SegEntry {
+        SegEntry {
+            entry_type,
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn data_accessors_read_their_entry_type() {
+        assert_eq!(entry(ABSTRACT, -1).shortcut(), -1);
+        assert_eq!(entry(ABSTRACT, 0).shortcut(), 0);
+        assert_eq!(entry(WINDING_INCREMENT, 2).winding_increment(), 2);
+        assert_eq!(entry(WINDING_INCREMENT, -1).winding_increment(), -1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "shortcut() of a non-ABSTRACT entry")]
+    fn shortcut_of_winding_increment_panics() {
+        entry(WINDING_INCREMENT, 1).shortcut();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "winding_increment() of a non-WINDING_INCREMENT entry")]
+    fn winding_increment_of_abstract_entry_panics() {
+        entry(ABSTRACT, 1).winding_increment();
+    }
+
+    #[test]
+    fn sort_cell_entries_restores_kernel_order() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 60.0, 60.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let mut root_entries = init_root_seg_entries(&scene.abs_segments);
+        let [mx, my] = root.mid_point();
+        let children = subdivide_seg_entry(
+            &mut root_entries,
+            &root,
+            &Point { x: mx, y: my },
+            &scene.abs_segments,
+        )
+        .unwrap();
+
+        // Pick a child cell that carries at least one winding increment.
+        let pos = (0..4)
+            .find(|&p| children.iter().any(|e| e.cell_pos == p && is_winding(e)))
+            .expect("no child received a winding increment");
+        let child: Vec<SegEntry> = children
+            .iter()
+            .copied()
+            .filter(|e| e.cell_pos == pos)
+            .collect();
+        let child_bbox = [
+            Rect::from_ltrb(root.left(), root.top(), mx, my),
+            Rect::from_ltrb(mx, root.top(), root.right(), my),
+            Rect::from_ltrb(root.left(), my, mx, root.bottom()),
+            Rect::from_ltrb(mx, my, root.right(), root.bottom()),
+        ][pos as usize]
+            .unwrap();
+
+        let mut shuffled = child.clone();
+        shuffled.reverse();
+        assert_ne!(shuffled, child);
+        sort_cell_entries(&mut shuffled);
+        assert_eq!(shuffled, child);
+
+        // Subdividing the restored order gives each grandchild the real winding of
+        // every path, as the renderer computes it from the cell's entries.
+        let [cx, cy] = child_bbox.mid_point();
+        let mid = Point { x: cx, y: cy };
+        let grandchildren =
+            subdivide_seg_entry(&mut shuffled, &child_bbox, &mid, &scene.abs_segments).unwrap();
+        let quadrants = [
+            Rect::from_ltrb(child_bbox.left(), child_bbox.top(), cx, cy),
+            Rect::from_ltrb(cx, child_bbox.top(), child_bbox.right(), cy),
+            Rect::from_ltrb(child_bbox.left(), cy, cx, child_bbox.bottom()),
+            Rect::from_ltrb(cx, cy, child_bbox.right(), child_bbox.bottom()),
+        ];
+        for (pos, quadrant) in quadrants.into_iter().enumerate() {
+            let quadrant = quadrant.unwrap();
+            let x = quadrant.left() + 0.3137 * quadrant.width();
+            let y = quadrant.top() + 0.7291 * quadrant.height();
+            for (path_idx, path) in scene.abs_paths.iter().enumerate() {
+                let entries: Vec<SegEntry> = grandchildren
+                    .iter()
+                    .copied()
+                    .filter(|e| e.cell_pos == pos as u32 && e.path_idx == path_idx as u32)
+                    .collect();
+                let segs = &scene.abs_segments[path.seg_start_idx..path.seg_end_idx];
+                assert_eq!(
+                    cell_winding(&entries, &quadrant, &scene.abs_segments, x, y),
+                    reference_winding(segs, x, y),
+                    "path {path_idx} in quadrant {pos} at ({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sort_cell_entries_keeps_cell_order() {
+        let entry = |cell_id, path_idx, entry_type| SegEntry {
+            entry_type,
+            path_idx,
+            cell_id,
+            ..SegEntry::default()
+        };
+        let mut entries = vec![
+            entry(7, 1, WINDING_INCREMENT),
+            entry(7, 0, ABSTRACT),
+            entry(7, 1, ABSTRACT),
+            entry(3, 0, WINDING_INCREMENT),
+            entry(3, 0, ABSTRACT),
+        ];
+        sort_cell_entries(&mut entries);
+        let order: Vec<_> = entries
+            .iter()
+            .map(|e| (e.cell_id, e.path_idx, is_winding(e)))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (7, 0, false),
+                (7, 1, false),
+                (7, 1, true),
+                (3, 0, false),
+                (3, 0, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn scratch_subdivision_matches_fresh_buffers() {
+        let mut scene = TestScene::new();
+        scene
+            .push_rect(4.0, 4.0, 60.0, 60.0, solid([255, 0, 0, 255]))
+            .push_polygon(
+                &[(10.0, 60.0), (60.0, 8.0), (50.0, 50.0)],
+                solid([0, 0, 255, 255]),
+            );
+        let root = Rect::from_ltrb(0.0, 0.0, 64.0, 64.0).unwrap();
+        let small = Rect::from_ltrb(0.0, 0.0, 32.0, 32.0).unwrap();
+        let mut scratch = SubdivideScratch::default();
+        // A large cell then a smaller one: stale data from the first must not leak.
+        for bbox in [root, small, root] {
+            let [mx, my] = bbox.mid_point();
+            let mid = Point { x: mx, y: my };
+            let mut entries = init_root_seg_entries(&scene.abs_segments);
+            let expected =
+                subdivide_seg_entry(&mut entries.clone(), &bbox, &mid, &scene.abs_segments)
+                    .unwrap();
+            let actual = subdivide_seg_entry_with_scratch(
+                &mut entries,
+                &bbox,
+                &mid,
+                &scene.abs_segments,
+                &mut scratch,
+            )
+            .unwrap();
+            assert_eq!(actual, expected.as_slice());
+        }
+    }
+}
@@ -1,24 +1,82 @@
 use crate::abstract_segment::{AbstractLineSegment, SegType};
-use crate::create_paint_array;
-use crate::path::{AbstractPath, Paint};
+use crate::path::{AbstractPath, Affine, GradientStop, Paint, PathGroup};
 use anyhow::Context;
 use crate::geometry::rect::Rect;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use usvg::tiny_skia_path::{PathSegment, Point};
-use usvg::{Group, Node, Path};
+use usvg::{FillRule, Group, LineCap, LineJoin, Node, PaintOrder, Path, Stroke, Transform};
 
+/// Default maximum distance, in pixels, between a curve and its flattened lines.
+pub const DEFAULT_FLATTEN_TOLERANCE: f32 = 0.1;
+
+/// Default limit on the lines a single curve may flatten into.
+pub const DEFAULT_MAX_SEGMENTS_PER_CURVE: usize = 4096;
+
+/// Default limit on how many times a curve is split in each branch.
+pub const DEFAULT_MAX_FLATTEN_DEPTH: u32 = 16;
+
+/// How `parse_svg` reads an SVG and turns its paths into line segments.
+#[derive(Debug, Copy, Clone)]
+pub struct FlattenOptions {
+    /// Maximum distance in pixels between a curve and its flattened lines.
+    pub tolerance: f32,
+    /// A curve needing more lines than this to meet `tolerance` is an error, which
+    /// guards against near-zero tolerances and pathological inputs.
+    pub max_segments_per_curve: usize,
+    /// Curves are split at most this many times in each branch, whatever the
+    /// tolerance, which bounds the recursion depth; a piece still too curved at
+    /// the cap is emitted as its chord.
+    pub max_flatten_depth: u32,
+    /// Take each `AbstractPath::bounding_box` from the emitted line segments instead
+    /// of usvg's curve bounds, so it matches the flattened geometry exactly.
+    pub bbox_from_segments: bool,
+    /// Colour `currentColor` resolves to where the document sets no `color`, as the
+    /// embedding context would; `None` leaves usvg's black. It is injected as a
+    /// style sheet rule on `svg` elements, so it also beats a `color` attribute on
+    /// them, but not one on any other element or from the document's own CSS.
+    pub current_color: Option<[u8; 4]>,
+    /// Letterbox the drawing into a `[width, height]` canvas: its content bounds are
+    /// scaled uniformly to fit and centred, and `ParsedSvg::width`/`height` become
+    /// this size. `None` keeps the SVG's own canvas.
+    pub fit_to: Option<[u32; 2]>,
+    /// Drop a fill segment that repeats an earlier one of the same path, endpoint
+    /// for endpoint and in the same direction, so a contour traced twice winds once.
+    /// Off by default: each coincident edge is then a crossing of its own, as in SVG.
+    pub dedup_coincident_edges: bool,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_FLATTEN_TOLERANCE,
+            max_segments_per_curve: DEFAULT_MAX_SEGMENTS_PER_CURVE,
+            max_flatten_depth: DEFAULT_MAX_FLATTEN_DEPTH,
+            bbox_from_segments: false,
+            current_color: None,
+            fit_to: None,
+            dedup_coincident_edges: false,
+        }
+    }
+}
+
+/// Flatten `path` into `abs_segments`, with `transform` taking its points to the
+/// canvas (normally `path.abs_transform()`).
 pub fn create_abstract_segment_array(
     abs_segments: &mut Vec<AbstractLineSegment>,
     path: &Path,
+    transform: Transform,
     path_idx: u32,
-) -> usize {
+    options: &FlattenOptions,
+) -> anyhow::Result<usize> {
     let mut start: Option<Point> = None;
     let mut curr: Option<Point> = None;
     let mut seg_count = 0usize;
 
     for segment in path.data().segments() {
-        match segment {
+        match map_segment(segment, &transform) {
             PathSegment::MoveTo(point) => {
+                seg_count += close_open_subpath(abs_segments, start, curr, path_idx);
                 start = Some(point);
                 curr = Some(point);
             }
@@ -33,81 +91,1368 @@ pub fn create_abstract_segment_array(
                 ));
                 seg_count += 1;
             }
-            PathSegment::QuadTo(_, _) => todo!(),
-            PathSegment::CubicTo(_, _, _) => todo!(),
+            PathSegment::QuadTo(ctrl, point) => {
+                let a = curr.expect("There should be a point before");
+                let curve_start = abs_segments.len();
+                let mut prev = a;
+                flatten_quad(a, ctrl, point, options, 0, &mut |p| {
+                    abs_segments.push(AbstractLineSegment::new(
+                        prev,
+                        p,
+                        SegType::Quadratic,
+                        path_idx,
+                    ));
+                    prev = p;
+                });
+                seg_count +=
+                    check_curve_segments(abs_segments.len() - curve_start, path_idx, options)?;
+                curr = Some(point);
+            }
+            PathSegment::CubicTo(ctrl0, ctrl1, point) => {
+                let a = curr.expect("There should be a point before");
+                let curve_start = abs_segments.len();
+                let mut prev = a;
+                flatten_cubic(a, ctrl0, ctrl1, point, options, 0, &mut |p| {
+                    abs_segments.push(AbstractLineSegment::new(prev, p, SegType::Cubic, path_idx));
+                    prev = p;
+                });
+                seg_count +=
+                    check_curve_segments(abs_segments.len() - curve_start, path_idx, options)?;
+                curr = Some(point);
+            }
             PathSegment::Close => {
                 let a = curr.expect("There should be at least one point");
                 let b = start.expect("There should be at least one point");
                 abs_segments.push(AbstractLineSegment::new(a, b, SegType::Linear, path_idx));
                 seg_count += 1;
+                curr = start;
+            }
+        }
+    }
+    seg_count += close_open_subpath(abs_segments, start, curr, path_idx);
+    Ok(seg_count)
+}
+
+/// Remove from `abs_segments[start..]` every segment with the same endpoints, in
+/// the same order, as an earlier one there; see
+/// `FlattenOptions::dedup_coincident_edges`. Reversed duplicates are kept, since
+/// they cancel out instead of doubling the winding.
+fn dedup_coincident_segments(abs_segments: &mut Vec<AbstractLineSegment>, start: usize) {
+    let mut seen = HashSet::new();
+    let tail = abs_segments.split_off(start);
+    abs_segments.extend(
+        tail.into_iter()
+            .filter(|seg| seen.insert([seg.x0, seg.y0, seg.x1, seg.y1].map(f32::to_bits))),
+    );
+}
+
+/// Which of a path's two paints an `AbstractPath` draws.
+#[derive(Debug, Copy, Clone)]
+enum PathPart {
+    Fill,
+    Stroke,
+}
+
+/// Outline the stroke of `path` into `abs_segments`, as a region to fill with the
+/// non-zero rule; `transform` is as for `create_abstract_segment_array`. Returns
+/// the number of segments pushed.
+///
+/// Every flattened line becomes a rectangle `stroke.width()` wide and every corner
+/// a miter wedge, or a bevel triangle beyond `stroke.miterlimit()` and for
+/// `LineJoin::Bevel`. All of them wind the same way, so their overlaps stay filled.
+/// Ends get butt caps. The outline is built in the path's own space and then
+/// transformed, so the width follows any scale or skew.
+pub fn create_stroke_segment_array(
+    abs_segments: &mut Vec<AbstractLineSegment>,
+    path: &Path,
+    stroke: &Stroke,
+    transform: Transform,
+    path_idx: u32,
+    options: &FlattenOptions,
+) -> anyhow::Result<usize> {
+    // Nothing to offset in a space collapsed to a line.
+    let Some(to_local) = transform.invert() else {
+        return Ok(0);
+    };
+    let map = |ts: &Transform, mut p: Point| {
+        ts.map_point(&mut p);
+        p
+    };
+    let half_width = stroke.width().get() / 2.0;
+    let miter_limit = match stroke.linejoin() {
+        LineJoin::Bevel => 1.0,
+        _ => stroke.miterlimit().get(),
+    };
+    let seg_start = abs_segments.len();
+    // Flattened in canvas space so that the tolerance is in pixels.
+    for polyline in flatten_polylines(path, transform, path_idx, options)? {
+        let local: Vec<Point> = polyline
+            .points
+            .into_iter()
+            .map(|p| map(&to_local, p))
+            .collect();
+        let mut push = |polygon: &[Point]| {
+            for (i, &a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                abs_segments.push(AbstractLineSegment::new(
+                    map(&transform, a),
+                    map(&transform, b),
+                    SegType::Linear,
+                    path_idx,
+                ));
+            }
+        };
+        stroke_polyline(&local, polyline.closed, half_width, miter_limit, &mut push);
+    }
+    Ok(abs_segments.len() - seg_start)
+}
+
+/// Whether `create_stroke_segment_array` draws `stroke` differently from the SVG.
+fn stroke_is_approximated(stroke: &Stroke) -> bool {
+    stroke.dasharray().is_some()
+        || stroke.linecap() != LineCap::Butt
+        || stroke.linejoin() == LineJoin::Round
+}
+
+/// One flattened subpath, open or closed.
+struct Polyline {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+/// Flatten `path` through `transform` into its subpaths. Unlike
+/// `create_abstract_segment_array`, which must close every subpath to fill it,
+/// subpaths without a `Close` are left open.
+fn flatten_polylines(
+    path: &Path,
+    transform: Transform,
+    path_idx: u32,
+    options: &FlattenOptions,
+) -> anyhow::Result<Vec<Polyline>> {
+    let mut polylines: Vec<Polyline> = vec![];
+    for segment in path.data().segments() {
+        let segment = map_segment(segment, &transform);
+        if let PathSegment::MoveTo(point) = segment {
+            polylines.push(Polyline {
+                points: vec![point],
+                closed: false,
+            });
+            continue;
+        }
+        let Some(last) = polylines.last() else {
+            anyhow::bail!("path {path_idx} draws before its first move");
+        };
+        // Drawing on after a `Close` starts a new subpath at the closed one's start.
+        if last.closed {
+            let start = last.points[0];
+            polylines.push(Polyline {
+                points: vec![start],
+                closed: false,
+            });
+        }
+        let polyline = polylines.last_mut().unwrap();
+        let points = &mut polyline.points;
+        let curr = *points.last().unwrap();
+        match segment {
+            PathSegment::MoveTo(_) => unreachable!(),
+            PathSegment::LineTo(point) => points.push(point),
+            PathSegment::QuadTo(ctrl, point) => {
+                let curve_start = points.len();
+                flatten_quad(curr, ctrl, point, options, 0, &mut |p| points.push(p));
+                check_curve_segments(points.len() - curve_start, path_idx, options)?;
+            }
+            PathSegment::CubicTo(ctrl0, ctrl1, point) => {
+                let curve_start = points.len();
+                flatten_cubic(curr, ctrl0, ctrl1, point, options, 0, &mut |p| {
+                    points.push(p)
+                });
+                check_curve_segments(points.len() - curve_start, path_idx, options)?;
+            }
+            PathSegment::Close => polyline.closed = true,
+        }
+    }
+    Ok(polylines)
+}
+
+/// Call `emit` with the convex polygons, all wound the same way, whose union is
+/// the stroke of `points`: a rectangle per line and a join at every corner, the
+/// start and end included when `closed`. A join is a miter unless the miter would
+/// reach more than `miter_limit` half widths from the corner, then a bevel.
+fn stroke_polyline(
+    points: &[Point],
+    closed: bool,
+    half_width: f32,
+    miter_limit: f32,
+    emit: &mut impl FnMut(&[Point]),
+) {
+    let mut points = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let n = points.len();
+    if n < 2 || half_width <= 0.0 {
+        return;
+    }
+    let mut emit_wound = |polygon: &mut [Point]| {
+        if signed_area(polygon) < 0.0 {
+            polygon.reverse();
+        }
+        emit(polygon);
+    };
+    let direction = |i: usize| {
+        let mut d = points[(i + 1) % n] - points[i];
+        d.normalize();
+        d
+    };
+    // Left-hand normal of the line from `points[i]`, half a stroke long.
+    let offset = |i: usize| {
+        let d = direction(i);
+        Point::from_xy(-d.y * half_width, d.x * half_width)
+    };
+
+    let lines = if closed { n } else { n - 1 };
+    for i in 0..lines {
+        let (a, b, off) = (points[i], points[(i + 1) % n], offset(i));
+        emit_wound(&mut [a + off, b + off, b - off, a - off]);
+    }
+
+    let corners = if closed { 0..n } else { 1..n - 1 };
+    for i in corners {
+        let prev = (i + n - 1) % n;
+        let (d0, d1) = (direction(prev), direction(i));
+        let turn = d0.cross(d1);
+        if turn == 0.0 && d0.dot(d1) > 0.0 {
+            continue;
+        }
+        // The gap to fill is on the outside of the turn.
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let p = points[i];
+        let (mut off0, mut off1) = (offset(prev), offset(i));
+        off0.scale(side);
+        off1.scale(side);
+        // The miter tip lies along off0 + off1, 1 / sin(angle between the lines / 2)
+        // half widths from the corner; a reversing corner has none.
+        let cos = d0.dot(d1);
+        let miter = (1.0 + cos > 0.0)
+            .then(|| {
+                let mut tip = off0 + off1;
+                tip.scale(1.0 / (1.0 + cos));
+                tip
+            })
+            .filter(|tip| tip.length() <= miter_limit * half_width);
+        match miter {
+            Some(tip) => emit_wound(&mut [p, p + off0, p + tip, p + off1]),
+            None => emit_wound(&mut [p, p + off0, p + off1]),
+        }
+    }
+}
+
+/// Shoelace area of `polygon`, positive for one winding direction and negative
+/// for the other.
+fn signed_area(polygon: &[Point]) -> f32 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| polygon[i].cross(polygon[(i + 1) % n]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Union of the segments' bounding boxes; `None` for no segments.
+fn segments_bounding_box(segments: &[AbstractLineSegment]) -> Option<Rect> {
+    let [l, t, r, b] = segments.iter().fold(
+        [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY],
+        |[l, t, r, b], seg| {
+            let [sl, st, sr, sb] = seg.bbox_ltrb;
+            [l.min(sl), t.min(st), r.max(sr), b.max(sb)]
+        },
+    );
+    Rect::from_ltrb(l, t, r, b)
+}
+
+/// Map the points of `segment` from the path's local space through `transform`,
+/// before flattening so that the tolerance applies in canvas pixels.
+fn map_segment(segment: PathSegment, transform: &Transform) -> PathSegment {
+    let map = |mut p: Point| {
+        transform.map_point(&mut p);
+        p
+    };
+    match segment {
+        PathSegment::MoveTo(p) => PathSegment::MoveTo(map(p)),
+        PathSegment::LineTo(p) => PathSegment::LineTo(map(p)),
+        PathSegment::QuadTo(c, p) => PathSegment::QuadTo(map(c), map(p)),
+        PathSegment::CubicTo(c0, c1, p) => PathSegment::CubicTo(map(c0), map(c1), map(p)),
+        PathSegment::Close => PathSegment::Close,
+    }
+}
+
+/// `count`, the number of lines a curve flattened into, or an error if that
+/// exceeds `options.max_segments_per_curve`. `options.max_flatten_depth` bounds the
+/// work done before the check.
+fn check_curve_segments(
+    count: usize,
+    path_idx: u32,
+    options: &FlattenOptions,
+) -> anyhow::Result<usize> {
+    if count > options.max_segments_per_curve {
+        anyhow::bail!(
+            "curve in path {path_idx} flattened into {count} segments, more than the limit of {} \
+             (tolerance {})",
+            options.max_segments_per_curve,
+            options.tolerance
+        );
+    }
+    Ok(count)
+}
+
+/// Adaptive de Casteljau flattening of the quadratic `p0, ctrl, p1`: calls `emit` with
+/// the end point of each line, ending with `p1`. A half is split again while the
+/// curve strays more than `options.tolerance` from its chord, which for a quadratic
+/// is `|p0 - 2 ctrl + p1| / 4`, and `depth` is below `options.max_flatten_depth`.
+fn flatten_quad(
+    p0: Point,
+    ctrl: Point,
+    p1: Point,
+    options: &FlattenOptions,
+    depth: u32,
+    emit: &mut impl FnMut(Point),
+) {
+    let dx = p0.x - 2.0 * ctrl.x + p1.x;
+    let dy = p0.y - 2.0 * ctrl.y + p1.y;
+    let deviation = (dx * dx + dy * dy).sqrt() / 4.0;
+    if deviation <= options.tolerance
+        || deviation.is_nan()
+        || depth >= options.max_flatten_depth
+    {
+        emit(p1);
+        return;
+    }
+    let c0 = midpoint(p0, ctrl);
+    let c1 = midpoint(ctrl, p1);
+    let split = midpoint(c0, c1);
+    flatten_quad(p0, c0, split, options, depth + 1, emit);
+    flatten_quad(split, c1, p1, options, depth + 1, emit);
+}
+
+/// Cubic counterpart of `flatten_quad`. The curve stays within
+/// `3/4 * max(|p0 - 2 c0 + c1|, |c0 - 2 c1 + p1|)` of its chord.
+fn flatten_cubic(
+    p0: Point,
+    c0: Point,
+    c1: Point,
+    p1: Point,
+    options: &FlattenOptions,
+    depth: u32,
+    emit: &mut impl FnMut(Point),
+) {
+    let second_diff = |a: Point, b: Point, c: Point| {
+        let dx = a.x - 2.0 * b.x + c.x;
+        let dy = a.y - 2.0 * b.y + c.y;
+        (dx * dx + dy * dy).sqrt()
+    };
+    let deviation = 0.75 * second_diff(p0, c0, c1).max(second_diff(c0, c1, p1));
+    if deviation <= options.tolerance
+        || deviation.is_nan()
+        || depth >= options.max_flatten_depth
+    {
+        emit(p1);
+        return;
+    }
+    let a = midpoint(p0, c0);
+    let b = midpoint(c0, c1);
+    let c = midpoint(c1, p1);
+    let ab = midpoint(a, b);
+    let bc = midpoint(b, c);
+    let split = midpoint(ab, bc);
+    flatten_cubic(p0, a, ab, split, options, depth + 1, emit);
+    flatten_cubic(split, bc, c, p1, options, depth + 1, emit);
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point {
+        x: (a.x + b.x) * 0.5,
+        y: (a.y + b.y) * 0.5,
+    }
+}
+
+/// Filled subpaths are implicitly closed: if the subpath ended away from its start
+/// without a `Close`, add the closing segment. Returns the number of segments pushed.
+fn close_open_subpath(
+    abs_segments: &mut Vec<AbstractLineSegment>,
+    start: Option<Point>,
+    curr: Option<Point>,
+    path_idx: u32,
+) -> usize {
+    match (curr, start) {
+        (Some(a), Some(b)) if a != b => {
+            abs_segments.push(AbstractLineSegment::new(a, b, SegType::Linear, path_idx));
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// Paints of the parsed paths, each solid colour stored once however many paths
+/// use it. Gradients are not compared and always get an entry of their own.
+#[derive(Debug, Default)]
+pub struct PaintTable {
+    paints: Vec<Paint>,
+    solid_ids: HashMap<[u8; 4], usize>,
+}
+
+impl PaintTable {
+    /// Index of `paint` in the table, added unless it is a colour already there.
+    pub fn insert(&mut self, paint: Paint) -> usize {
+        if let Paint::SolidColor { rgba } = paint {
+            if let Some(&id) = self.solid_ids.get(&rgba) {
+                return id;
             }
+            self.solid_ids.insert(rgba, self.paints.len());
         }
+        self.paints.push(paint);
+        self.paints.len() - 1
+    }
+
+    pub fn into_paints(self) -> Vec<Paint> {
+        self.paints
     }
-    seg_count
 }
 
-pub fn visit_group(g: &Group, paths: &mut Vec<Path>) {
+/// A fill or stroke paint with `opacity` folded into its alpha; colours keep straight
+/// alpha. Group `opacity` is not folded in here; it is applied when the `PathGroup`
+/// is composited. `transform` is the path's transform to the canvas, as given to
+/// `create_abstract_segment_array`. A pattern, which is not supported, becomes a
+/// transparent colour.
+fn convert_paint(paint: &usvg::Paint, opacity: f32, transform: Transform) -> Paint {
+    match paint {
+        usvg::Paint::Color(c) => Paint::SolidColor {
+            rgba: [c.red, c.green, c.blue, unit_to_u8(opacity)],
+        },
+        usvg::Paint::LinearGradient(lg) => Paint::LinearGradient {
+            start: [lg.x1(), lg.y1()],
+            end: [lg.x2(), lg.y2()],
+            stops: gradient_stops(lg.stops(), opacity),
+            inv_transform: inverse_affine(transform.pre_concat(lg.transform())),
+        },
+        usvg::Paint::RadialGradient(rg) => Paint::RadialGradient {
+            center: [rg.cx(), rg.cy()],
+            radius: rg.r().get(),
+            stops: gradient_stops(rg.stops(), opacity),
+            inv_transform: inverse_affine(transform.pre_concat(rg.transform())),
+        },
+        usvg::Paint::Pattern(_) => Paint::SolidColor { rgba: [0; 4] },
+    }
+}
+
+fn gradient_stops(stops: &[usvg::Stop], opacity: f32) -> Vec<GradientStop> {
+    stops
+        .iter()
+        .map(|s| {
+            let c = s.color();
+            GradientStop {
+                offset: s.offset().get(),
+                rgba: [
+                    c.red,
+                    c.green,
+                    c.blue,
+                    unit_to_u8(s.opacity().get() * opacity),
+                ],
+            }
+        })
+        .collect()
+}
+
+fn unit_to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Convert a usvg gradient-to-canvas transform into the inverse `Affine` used by
+/// gradient sampling.
+fn inverse_affine(ts: usvg::Transform) -> Affine {
+    let inv = ts.invert().unwrap_or_default();
+    [inv.sx, inv.kx, inv.tx, inv.ky, inv.sy, inv.ty]
+}
+
+/// Collect the filled or stroked paths under `g` in paint order. Child groups that are
+/// translucent or isolated are recorded in `groups` as ranges over the collected
+/// paths. Content that is not rendered is reported in `warnings`.
+pub fn visit_group(
+    g: &Group,
+    paths: &mut Vec<Path>,
+    groups: &mut Vec<PathGroup>,
+    warnings: &mut Vec<ParseWarning>,
+) {
     for node in g.children() {
         match node {
             Node::Path(p) => {
-                paths.push(*p.clone());
+                if p.stroke().is_some_and(stroke_is_approximated) {
+                    warnings.push(ParseWarning::StrokeApproximated {
+                        id: p.id().to_string(),
+                    });
+                }
+                if p.fill().is_some() || p.stroke().is_some() {
+                    paths.push(*p.clone());
+                }
+            }
+            Node::Group(child) => {
+                let start = paths.len();
+                visit_group(child, paths, groups, warnings);
+                let opacity = child.opacity().get();
+                if (opacity < 1.0 || child.isolate()) && paths.len() > start {
+                    groups.push(PathGroup {
+                        paths: start..paths.len(),
+                        opacity,
+                    });
+                }
             }
-            Node::Group(child) => visit_group(child, paths),
-            Node::Image(_) => {}
-            Node::Text(_) => {}
+            Node::Image(image) => {
+                // usvg moves an image's `id` onto the group it wraps the image in.
+                let id = if image.id().is_empty() {
+                    g.id()
+                } else {
+                    image.id()
+                };
+                warnings.push(ParseWarning::ImageSkipped { id: id.to_string() });
+            }
+            Node::Text(text) => warnings.push(ParseWarning::TextSkipped {
+                id: text.id().to_string(),
+            }),
         }
     }
 }
 
+/// Content `parse_svg_with_warnings` leaves out of the parsed scene. `id` is the
+/// element's `id` attribute, empty when it has none.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    /// An `<image>` element; embedded images are not rendered.
+    ImageSkipped { id: String },
+    /// A text node usvg did not convert to paths, e.g. for lack of fonts.
+    TextSkipped { id: String },
+    /// A stroke with dashes, round or square caps or round joins; it is drawn solid,
+    /// with butt caps and miter joins.
+    StrokeApproximated { id: String },
+    /// A fill paint other than a colour or gradient, such as a pattern; the path at
+    /// `path_idx` is left transparent.
+    UnsupportedPaint { path_idx: usize },
+}
+
 pub struct ParsedSvg {
     pub abs_paths: Vec<AbstractPath>,
     pub abs_segments: Vec<AbstractLineSegment>,
     pub paints: Vec<Paint>,
+    pub groups: Vec<PathGroup>,
     pub width: u32,
     pub height: u32,
 }
 
-pub fn parse_svg() -> anyhow::Result<ParsedSvg> {
+impl ParsedSvg {
+    /// Union of every `AbstractPath::bounding_box`, a tight root bbox for the
+    /// quad tree; `None` when there are no paths.
+    pub fn paths_bounding_box(&self) -> Option<Rect> {
+        self.abs_paths
+            .iter()
+            .map(|path| path.bounding_box)
+            .reduce(|acc, bbox| acc.union(&bbox))
+    }
+
+    /// The canvas together with every path, grown by `padding` on each side: a
+    /// root bbox that no segment falls outside of, even where the drawing spills
+    /// off the canvas.
+    pub fn root_bounds(&self, padding: f32) -> anyhow::Result<Rect> {
+        let canvas = Rect::from_ltrb(0.0, 0.0, self.width as f32, self.height as f32)
+            .ok_or_else(|| anyhow::anyhow!("invalid canvas {}x{}", self.width, self.height))?;
+        let bounds = self
+            .paths_bounding_box()
+            .map_or(canvas, |bbox| canvas.union(&bbox));
+        bounds
+            .outset(padding)
+            .ok_or_else(|| anyhow::anyhow!("cannot pad {:?} by {padding}", bounds.to_ltrb()))
+    }
+
+    /// Stretch the drawing from `self.width` x `self.height` to `width` x `height`,
+    /// scaling each axis on its own. Gradients are stretched with the geometry.
+    pub fn scale_to(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        anyhow::ensure!(width > 0 && height > 0, "cannot scale to {width}x{height}");
+        let sx = width as f32 / self.width as f32;
+        let sy = height as f32 / self.height as f32;
+        for path in &mut self.abs_paths {
+            let [l, t, r, b] = path.bounding_box.to_ltrb();
+            path.bounding_box = Rect::from_ltrb(l * sx, t * sy, r * sx, b * sy)
+                .context("Invalid path bounding box after scaling")?;
+        }
+        for seg in &mut self.abs_segments {
+            let p0 = Point {
+                x: seg.x0 * sx,
+                y: seg.y0 * sy,
+            };
+            let p1 = Point {
+                x: seg.x1 * sx,
+                y: seg.y1 * sy,
+            };
+            let seg_type = SegType::from_u32(seg.seg_type);
+            *seg = AbstractLineSegment::new(p0, p1, seg_type, seg.path_idx);
+        }
+        for paint in &mut self.paints {
+            if let Paint::LinearGradient { inv_transform, .. }
+            | Paint::RadialGradient { inv_transform, .. } = paint
+            {
+                // Undo the scale before mapping into gradient space.
+                for row in inv_transform.chunks_exact_mut(3) {
+                    row[0] /= sx;
+                    row[1] /= sy;
+                }
+            }
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+}
+
+/// SVG rendered when no input file is given.
+pub const SAMPLE_SVG_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/sample_svg/simple_polygons.svg"
+);
+
+/// Uniform scale and translation that fit `content` into a `width` x `height`
+/// canvas, centred, with the spare space split evenly on both sides. A
+/// zero-size axis does not constrain the scale; empty content is left in place.
+pub fn letterbox_transform(content: usvg::Rect, width: u32, height: u32) -> Transform {
+    let scale_for = |canvas: u32, extent: f32| (extent > 0.0).then(|| canvas as f32 / extent);
+    let scale = match (
+        scale_for(width, content.width()),
+        scale_for(height, content.height()),
+    ) {
+        (Some(sx), Some(sy)) => sx.min(sy),
+        (Some(s), None) | (None, Some(s)) => s,
+        (None, None) => return Transform::identity(),
+    };
+    let tx = (width as f32 - content.width() * scale) / 2.0 - content.left() * scale;
+    let ty = (height as f32 - content.height() * scale) / 2.0 - content.top() * scale;
+    Transform::from_row(scale, 0.0, 0.0, scale, tx, ty)
+}
+
+fn current_color_style_sheet([r, g, b, a]: [u8; 4]) -> String {
+    format!("svg {{ color: rgba({r}, {g}, {b}, {}) }}", a as f32 / 255.0)
+}
+
+/// Parse the SVG file at `svg_path`, flattening curves as set by `options`.
+pub fn parse_svg(
+    svg_path: &std::path::Path,
+    options: &FlattenOptions,
+) -> anyhow::Result<ParsedSvg> {
+    parse_svg_with_warnings(svg_path, options).map(|(parsed, _)| parsed)
+}
+
+/// `parse_svg`, also returning what was left out of the scene.
+pub fn parse_svg_with_warnings(
+    svg_path: &std::path::Path,
+    options: &FlattenOptions,
+) -> anyhow::Result<(ParsedSvg, Vec<ParseWarning>)> {
+    let svg = fs::read_to_string(svg_path)
+        .with_context(|| format!("failed to read SVG file {}", svg_path.display()))?;
+    parse_svg_str_with_warnings(&svg, options)
+        .with_context(|| format!("failed to parse SVG file {}", svg_path.display()))
+}
+
+pub fn parse_svg_str(svg: &str, options: &FlattenOptions) -> anyhow::Result<ParsedSvg> {
+    parse_svg_str_with_warnings(svg, options).map(|(parsed, _)| parsed)
+}
+
+/// `parse_svg_str`, also returning what was left out of the scene.
+pub fn parse_svg_str_with_warnings(
+    svg: &str,
+    options: &FlattenOptions,
+) -> anyhow::Result<(ParsedSvg, Vec<ParseWarning>)> {
     let mut paths: Vec<Path> = vec![];
     let mut abs_paths: Vec<AbstractPath> = vec![];
     let mut abs_segments: Vec<AbstractLineSegment> = vec![];
-    let mut paints: Vec<Paint> = vec![];
+    let mut paints = PaintTable::default();
+    let mut groups: Vec<PathGroup> = vec![];
+    let mut warnings: Vec<ParseWarning> = vec![];
 
-    let svg_path = format!(
-        "{}/sample_svg/simple_polygons.svg",
-        env!("CARGO_MANIFEST_DIR")
-    );
-    let svg: String = fs::read_to_string(svg_path)?;
-    let opt = usvg::Options::default();
-    let svg_tree = usvg::Tree::from_str(&svg, &opt)?;
-    visit_group(svg_tree.root(), &mut paths);
-
-    let mut seg_start_idx = 0usize;
-    for (i, path) in paths.iter().enumerate() {
-        let seg_count = create_abstract_segment_array(&mut abs_segments, path, i as u32);
-        let seg_end_idx = seg_start_idx + seg_count;
-        let bb = path.bounding_box();
-        abs_paths.push(AbstractPath {
-            seg_start_idx,
-            seg_end_idx,
-            fill_rule: usvg::FillRule::EvenOdd,
-            paint_id: i,
-            bounding_box: Rect::from_ltrb(bb.left(), bb.top(), bb.right(), bb.bottom()).unwrap(),
-        });
-        seg_start_idx = seg_end_idx;
-        create_paint_array(&mut paints, path);
-    }
-    let svg_size = svg_tree.size();
-    let width = svg_size.width().ceil().max(1.0) as u32;
-    let height = svg_size.height().ceil().max(1.0) as u32;
+    let opt = usvg::Options {
+        style_sheet: options.current_color.map(current_color_style_sheet),
+        ..Default::default()
+    };
+    let svg_tree = usvg::Tree::from_str(svg, &opt)?;
+    visit_group(svg_tree.root(), &mut paths, &mut groups, &mut warnings);
+    let fit = match options.fit_to {
+        Some([w, h]) => letterbox_transform(svg_tree.root().abs_bounding_box(), w, h),
+        None => Transform::identity(),
+    };
+
+    // A usvg path gives a fill path, a stroke outline path, or both, in paint order;
+    // `first_abs_path[i]` is the first of those for `paths[i]`.
+    let mut first_abs_path = Vec::with_capacity(paths.len() + 1);
+    for path in &paths {
+        first_abs_path.push(abs_paths.len());
+        let transform = fit.pre_concat(path.abs_transform());
+        let parts = match path.paint_order() {
+            PaintOrder::FillAndStroke => [PathPart::Fill, PathPart::Stroke],
+            PaintOrder::StrokeAndFill => [PathPart::Stroke, PathPart::Fill],
+        };
+        for part in parts {
+            let path_idx = abs_paths.len();
+            let seg_start_idx = abs_segments.len();
+            let (paint, opacity, fill_rule, stroke_width) = match part {
+                PathPart::Fill => {
+                    let Some(fill) = path.fill() else {
+                        continue;
+                    };
+                    create_abstract_segment_array(
+                        &mut abs_segments,
+                        path,
+                        transform,
+                        path_idx as u32,
+                        options,
+                    )?;
+                    if options.dedup_coincident_edges {
+                        dedup_coincident_segments(&mut abs_segments, seg_start_idx);
+                    }
+                    (fill.paint(), fill.opacity(), fill.rule(), None)
+                }
+                PathPart::Stroke => {
+                    let Some(stroke) = path.stroke() else {
+                        continue;
+                    };
+                    let seg_count = create_stroke_segment_array(
+                        &mut abs_segments,
+                        path,
+                        stroke,
+                        transform,
+                        path_idx as u32,
+                        options,
+                    )?;
+                    if seg_count == 0 {
+                        continue;
+                    }
+                    (
+                        stroke.paint(),
+                        stroke.opacity(),
+                        FillRule::NonZero,
+                        Some(stroke.width().get()),
+                    )
+                }
+            };
+            let seg_end_idx = abs_segments.len();
+            // Outlines always take their bounds from their segments: usvg's stroke
+            // bounds include caps and joins drawn differently here.
+            let segment_bbox = (options.bbox_from_segments || stroke_width.is_some())
+                .then(|| segments_bounding_box(&abs_segments[seg_start_idx..seg_end_idx]))
+                .flatten();
+            let bounding_box = segment_bbox.unwrap_or_else(|| {
+                let bb = path.abs_bounding_box();
+                let bb = bb.transform(fit).unwrap_or(bb);
+                Rect::from_usvg(bb).unwrap()
+            });
+            abs_paths.push(AbstractPath {
+                seg_start_idx,
+                seg_end_idx,
+                fill_rule,
+                paint_id: paints.insert(convert_paint(paint, opacity.get(), transform)),
+                bounding_box,
+                stroke_width,
+            });
+            if matches!(paint, usvg::Paint::Pattern(_)) {
+                warnings.push(ParseWarning::UnsupportedPaint { path_idx });
+            }
+        }
+    }
+    first_abs_path.push(abs_paths.len());
+    for group in &mut groups {
+        group.paths = first_abs_path[group.paths.start]..first_abs_path[group.paths.end];
+    }
+    let [width, height] = options.fit_to.unwrap_or_else(|| {
+        let svg_size = svg_tree.size();
+        [svg_size.width(), svg_size.height()].map(|v| v.ceil().max(1.0) as u32)
+    });
+    let [width, height] = [width.max(1), height.max(1)];
     let _ = Rect::from_ltrb(0.0, 0.0, width as f32, height as f32)
         .context("Invalid parsed SVG size")?;
 
-    Ok(ParsedSvg {
+    let parsed = ParsedSvg {
         abs_paths,
         abs_segments,
-        paints,
+        paints: paints.into_paints(),
+        groups,
         width,
         height,
-    })
+    };
+    Ok((parsed, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad_tree::QuadTree;
+    use crate::render::{render, render_to_rgba, RenderOptions};
+    use crate::png_writer::to_data_uri;
+    use crate::test_utils::pixel;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    fn render_svg(svg: &str) -> (ParsedSvg, Vec<u8>) {
+        render_svg_with(svg, &FlattenOptions::default())
+    }
+
+    fn render_svg_with(svg: &str, options: &FlattenOptions) -> (ParsedSvg, Vec<u8>) {
+        let parsed = parse_svg_str(svg, options).unwrap();
+        let (w, h) = (parsed.width, parsed.height);
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&parsed.abs_segments, &parsed.abs_paths, root, 0, 1).unwrap();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        render(
+            &tree,
+            &parsed.abs_segments,
+            &parsed.abs_paths,
+            &parsed.paints,
+            &mut pixels,
+            w,
+            h,
+            &RenderOptions::default(),
+        );
+        (parsed, pixels)
+    }
+
+    #[test]
+    fn missing_svg_file_error_names_the_path() {
+        let missing = std::path::Path::new("does/not/exist.svg");
+        let Err(err) = parse_svg(missing, &FlattenOptions::default()) else {
+            panic!("expected an error for a missing file");
+        };
+        assert!(err.to_string().contains("does/not/exist.svg"), "{err}");
+    }
+
+    #[test]
+    fn fill_opacity_sets_paint_alpha() {
+        let parsed = parse_svg_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+                <rect width="16" height="16" fill="red" fill-opacity="0.5"/>
+            </svg>"#,
+            &FlattenOptions::default(),
+        )
+        .unwrap();
+        let Paint::SolidColor { rgba } = parsed.paints[0] else {
+            panic!("expected a solid paint");
+        };
+        assert_eq!(rgba, [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn stroke_only_paths_are_outlined_and_paint_less_paths_dropped() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <path d="M0 0 L32 32" stroke="black" fill="none"/>
+            <path d="M0 32 L32 0" fill="none"/>
+            <rect x="4" y="4" width="8" height="8" fill="blue"/>
+        </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        assert_eq!(parsed.abs_paths.len(), 2);
+        assert_eq!(parsed.abs_paths[0].stroke_width, Some(1.0));
+        assert_eq!(parsed.abs_paths[1].stroke_width, None);
+        assert_eq!(parsed.paints.len(), 2);
+    }
+
+    #[test]
+    fn pattern_fill_keeps_its_paint_slot_transparent() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <defs>
+                <pattern id="dots" width="4" height="4" patternUnits="userSpaceOnUse">
+                    <rect width="2" height="2" fill="red"/>
+                </pattern>
+            </defs>
+            <rect x="0" y="0" width="8" height="8" fill="blue"/>
+            <rect x="8" y="8" width="8" height="8" fill="url(#dots)"/>
+            <rect x="16" y="16" width="8" height="8" fill="red"/>
+        </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        assert_eq!(parsed.paints.len(), 3);
+        assert!(matches!(
+            parsed.paints[1],
+            Paint::SolidColor { rgba: [0, 0, 0, 0] }
+        ));
+        // The path after the pattern still gets its own paint.
+        assert!(matches!(
+            parsed.paints[parsed.abs_paths[2].paint_id],
+            Paint::SolidColor {
+                rgba: [255, 0, 0, 255]
+            }
+        ));
+    }
+
+    #[test]
+    fn unclosed_subpath_fills_as_if_closed() {
+        let (parsed, pixels) = render_svg(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <path d="M 8 8 L 56 8 L 32 56" fill="red"/>
+            </svg>"#,
+        );
+        assert_eq!(parsed.abs_segments.len(), 3);
+        let last = &parsed.abs_segments[2];
+        assert_eq!((last.x1, last.y1), (8.0, 8.0));
+        assert_eq!(pixel(&pixels, 64, 32, 20), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 64, 32, 60)[3], 0);
+    }
+
+    #[test]
+    fn inner_contour_cuts_hole_under_even_odd() {
+        let (parsed, pixels) = render_svg(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <path d="M 4 4 H 60 V 60 H 4 Z M 20 20 H 44 V 44 H 20 Z"
+                      fill="blue" fill-rule="evenodd"/>
+            </svg>"#,
+        );
+        assert_eq!(parsed.abs_paths.len(), 1);
+        assert_eq!(parsed.abs_segments.len(), 8);
+        assert!(parsed.abs_segments.iter().all(|s| s.path_idx == 0));
+        assert_eq!(pixel(&pixels, 64, 10, 32), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 64, 32, 10), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 64, 32, 32)[3], 0);
+    }
+
+    #[test]
+    fn quadratic_is_flattened_into_connected_lines() {
+        let parsed = parse_svg_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <path d="M 4 60 Q 32 -20 60 60 Z" fill="red"/>
+            </svg>"#,
+            &FlattenOptions::default(),
+        )
+        .unwrap();
+        let segs = &parsed.abs_segments;
+        let quad_type = SegType::Quadratic.to_u32();
+        let curve: Vec<_> = segs.iter().filter(|s| s.seg_type == quad_type).collect();
+        // The whole curve deviates 160 / 4 = 40px from its chord and every split
+        // quarters that, so five levels get it under 0.1px.
+        assert_eq!(curve.len(), 32);
+        assert_eq!(segs.len(), curve.len() + 1);
+        assert_eq!(parsed.abs_paths[0].seg_end_idx, segs.len());
+
+        assert_eq!((segs[0].x0, segs[0].y0), (4.0, 60.0));
+        for pair in segs.windows(2) {
+            assert_eq!((pair[0].x1, pair[0].y1), (pair[1].x0, pair[1].y0));
+        }
+        let last_curve = curve.last().unwrap();
+        assert_eq!((last_curve.x1, last_curve.y1), (60.0, 60.0));
+        assert!(segs.iter().all(|s| s.path_idx == 0));
+    }
+
+    #[test]
+    fn scale_to_stretches_gradients_and_keeps_segment_types() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="32">
+            <defs>
+                <linearGradient id="g" x1="0" y1="0" x2="64" y2="0"
+                                gradientUnits="userSpaceOnUse">
+                    <stop offset="0" stop-color="red"/>
+                    <stop offset="1" stop-color="blue"/>
+                </linearGradient>
+            </defs>
+            <path d="M 4 28 Q 32 -20 60 28 Z" fill="url(#g)"/>
+        </svg>"#;
+        let original = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        let mut scaled = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        scaled.scale_to(128, 96).unwrap();
+
+        for (x, y) in [(8.0, 4.0), (32.0, 16.0), (56.0, 30.0)] {
+            assert_eq!(
+                scaled.paints[0].sample(x * 2.0, y * 3.0),
+                original.paints[0].sample(x, y),
+                "({x}, {y})"
+            );
+        }
+        let seg_types = |parsed: &ParsedSvg| -> Vec<u32> {
+            parsed.abs_segments.iter().map(|s| s.seg_type).collect()
+        };
+        assert_eq!(seg_types(&scaled), seg_types(&original));
+        assert!(scaled.scale_to(0, 96).is_err());
+    }
+
+    #[test]
+    fn rounded_rect_cubics_flatten_and_close() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <rect x="4" y="4" width="56" height="40" rx="10" fill="red"/>
+            </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        let segs = &parsed.abs_segments;
+        let cubic_type = SegType::Cubic.to_u32();
+        let corners = segs.iter().filter(|s| s.seg_type == cubic_type).count();
+        // Four corners of a few lines each, plus at most the straight sides and close.
+        assert!((16..=160).contains(&corners), "{corners} cubic lines");
+        assert!(segs.len() - corners <= 5);
+
+        for pair in segs.windows(2) {
+            assert_eq!((pair[0].x1, pair[0].y1), (pair[1].x0, pair[1].y0));
+        }
+        let (first, last) = (segs.first().unwrap(), segs.last().unwrap());
+        assert_eq!((last.x1, last.y1), (first.x0, first.y0));
+
+        let coarse = parse_svg_str(
+            svg,
+            &FlattenOptions {
+                tolerance: 2.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(coarse.abs_segments.len() < segs.len());
+    }
+
+    #[test]
+    fn flatten_depth_cap_stops_recursion_with_connected_chords() {
+        // A zero tolerance is never met, so only the depth cap ends the recursion.
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <path d="M 4 60 Q 32 -20 60 60 C 60 0 4 120 4 60 Z" fill="red"/>
+            </svg>"#;
+        let options = FlattenOptions {
+            tolerance: 0.0,
+            max_flatten_depth: 3,
+            ..Default::default()
+        };
+        let segs = parse_svg_str(svg, &options).unwrap().abs_segments;
+        let count = |seg_type: SegType| {
+            let t = seg_type.to_u32();
+            segs.iter().filter(|s| s.seg_type == t).count()
+        };
+        assert_eq!(count(SegType::Quadratic), 8);
+        assert_eq!(count(SegType::Cubic), 8);
+        assert_eq!((segs[0].x0, segs[0].y0), (4.0, 60.0));
+        for pair in segs.windows(2) {
+            assert_eq!((pair[0].x1, pair[0].y1), (pair[1].x0, pair[1].y0));
+        }
+        let last = segs.last().unwrap();
+        assert_eq!((last.x1, last.y1), (4.0, 60.0));
+    }
+
+    #[test]
+    fn tiny_tolerance_on_long_curve_hits_segment_cap() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="4000" height="4000">
+                <path d="M 0 0 C 4000 0 0 4000 4000 4000 Z" fill="red"/>
+            </svg>"#;
+        let options = FlattenOptions {
+            tolerance: 1e-6,
+            max_segments_per_curve: 1000,
+            ..Default::default()
+        };
+        let Err(err) = parse_svg_str(svg, &options) else {
+            panic!("expected the segment cap to trigger");
+        };
+        assert!(err.to_string().contains("limit of 1000"), "{err}");
+
+        let relaxed = FlattenOptions {
+            max_segments_per_curve: usize::MAX,
+            ..options
+        };
+        assert!(parse_svg_str(svg, &relaxed).unwrap().abs_segments.len() > 1000);
+    }
+
+    #[test]
+    fn group_and_path_transforms_move_segments() {
+        let parsed = parse_svg_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <g transform="translate(20 10)">
+                    <rect x="2" y="4" width="10" height="6" fill="red"
+                          transform="scale(2)"/>
+                </g>
+            </svg>"#,
+            &FlattenOptions::default(),
+        )
+        .unwrap();
+        let xs: Vec<f32> = parsed.abs_segments.iter().map(|s| s.x0).collect();
+        let ys: Vec<f32> = parsed.abs_segments.iter().map(|s| s.y0).collect();
+        let min = |v: &[f32]| v.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = |v: &[f32]| v.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        assert_eq!((min(&xs), max(&xs)), (24.0, 44.0));
+        assert_eq!((min(&ys), max(&ys)), (18.0, 30.0));
+        assert_eq!(
+            parsed.abs_paths[0].bounding_box.to_ltrb(),
+            [24.0, 18.0, 44.0, 30.0]
+        );
+    }
+
+    #[test]
+    fn non_square_view_box_sets_canvas_size() {
+        let (parsed, pixels) = render_svg(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 120 40">
+                <rect x="100" y="0" width="20" height="40" fill="red"/>
+            </svg>"#,
+        );
+        assert_eq!((parsed.width, parsed.height), (120, 40));
+        assert_eq!(pixel(&pixels, 120, 110, 20), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 120, 50, 20), [0; 4]);
+
+        let uri = to_data_uri(parsed.width, parsed.height, &pixels).unwrap();
+        let png_bytes = STANDARD
+            .decode(uri.trim_start_matches("data:image/png;base64,"))
+            .unwrap();
+        let reader = png::Decoder::new(std::io::Cursor::new(png_bytes))
+            .read_info()
+            .unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (120, 40));
+    }
+
+    #[test]
+    fn segment_bbox_follows_transformed_flattened_shape() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <g transform="rotate(45 32 32)">
+                    <ellipse cx="32" cy="32" rx="20" ry="4" fill="red"/>
+                </g>
+            </svg>"#;
+        let options = FlattenOptions {
+            bbox_from_segments: true,
+            ..Default::default()
+        };
+        let parsed = parse_svg_str(svg, &options).unwrap();
+        let bbox = parsed.abs_paths[0].bounding_box.to_ltrb();
+        for seg in &parsed.abs_segments {
+            for (x, y) in [(seg.x0, seg.y0), (seg.x1, seg.y1)] {
+                assert!(bbox[0] <= x && x <= bbox[2] && bbox[1] <= y && y <= bbox[3]);
+            }
+        }
+        // The rotated ellipse reaches sqrt((20^2 + 4^2) / 2) ~= 14.42 from its centre.
+        let reach = (208.0f32).sqrt();
+        let expected = [32.0 - reach, 32.0 - reach, 32.0 + reach, 32.0 + reach];
+        for (got, want) in bbox.iter().zip(expected) {
+            assert!((got - want).abs() <= options.tolerance, "{bbox:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn current_color_fill_uses_supplied_color() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="8">
+                <rect x="0" y="0" width="8" height="8" fill="currentColor"/>
+                <g color="red">
+                    <rect x="8" y="0" width="8" height="8" fill="currentColor"/>
+                </g>
+            </svg>"#;
+        let options = FlattenOptions {
+            current_color: Some([0, 128, 255, 255]),
+            ..Default::default()
+        };
+        let (_, pixels) = render_svg_with(svg, &options);
+        assert_eq!(pixel(&pixels, 16, 4, 4), [0, 128, 255, 255]);
+        // A `color` set inside the document still takes precedence.
+        assert_eq!(pixel(&pixels, 16, 12, 4), [255, 0, 0, 255]);
+
+        let (_, pixels) = render_svg(svg);
+        assert_eq!(pixel(&pixels, 16, 4, 4), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn fit_to_letterboxes_tall_drawing_centred() {
+        // 10 x 40 content at (30, 20) on a 100 x 100 canvas.
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                <rect x="30" y="20" width="10" height="40" fill="red"/>
+            </svg>"#;
+        let options = FlattenOptions {
+            fit_to: Some([64, 64]),
+            ..Default::default()
+        };
+        let (parsed, pixels) = render_svg_with(svg, &options);
+        assert_eq!((parsed.width, parsed.height), (64, 64));
+        // Scaled by 64 / 40 to 16 x 64, then centred horizontally.
+        assert_eq!(
+            parsed.paths_bounding_box().unwrap().to_ltrb(),
+            [24.0, 0.0, 40.0, 64.0]
+        );
+        let red = [255, 0, 0, 255];
+        for (x, y) in [(24, 0), (39, 63), (32, 32)] {
+            assert_eq!(pixel(&pixels, 64, x, y), red, "({x}, {y})");
+        }
+        for (x, y) in [(23, 32), (40, 32), (2, 2), (61, 61)] {
+            assert_eq!(pixel(&pixels, 64, x, y)[3], 0, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn paths_sharing_a_colour_share_a_paint() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+                <rect x="0" y="0" width="8" height="8" fill="red"/>
+                <rect x="10" y="0" width="8" height="8" fill="none"/>
+                <rect x="0" y="10" width="8" height="8" fill="blue"/>
+                <rect x="10" y="10" width="8" height="8" fill="red"/>
+            </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        assert_eq!(parsed.abs_paths.len(), 3);
+        assert_eq!(parsed.paints.len(), 2);
+        let colours: Vec<_> = parsed
+            .abs_paths
+            .iter()
+            .map(|path| parsed.paints[path.paint_id].sample(0.0, 0.0))
+            .collect();
+        assert_eq!(
+            colours,
+            [[255, 0, 0, 255], [0, 0, 255, 255], [255, 0, 0, 255]]
+        );
+    }
+
+    #[test]
+    fn paths_bounding_box_unions_every_path() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                <rect x="4" y="10" width="8" height="6" fill="red"/>
+                <rect x="40" y="2" width="10" height="4" fill="blue"/>
+            </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        let bbox = parsed.paths_bounding_box().unwrap();
+        assert_eq!(bbox.to_ltrb(), [4.0, 2.0, 50.0, 16.0]);
+
+        let empty = r#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8"/>"#;
+        let parsed = parse_svg_str(empty, &FlattenOptions::default()).unwrap();
+        assert!(parsed.paths_bounding_box().is_none());
+    }
+
+    #[test]
+    fn empty_svg_renders_background_only() {
+        let parsed = parse_svg_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="16"/>"#,
+            &FlattenOptions::default(),
+        )
+        .unwrap();
+        let (w, h) = (parsed.width, parsed.height);
+        let root = Rect::from_ltrb(0.0, 0.0, w as f32, h as f32).unwrap();
+        let tree = QuadTree::new(&parsed.abs_segments, &parsed.abs_paths, root, 4, 1).unwrap();
+        let red = [255, 0, 0, 255];
+        let options = RenderOptions {
+            background: Some(red),
+            ..Default::default()
+        };
+        let pixels = render_to_rgba(
+            &tree,
+            &parsed.abs_segments,
+            &parsed.abs_paths,
+            &parsed.paints,
+            w,
+            h,
+            &options,
+        );
+        assert!(pixels.chunks_exact(4).all(|px| px == red));
+    }
+
+    #[test]
+    fn fill_rule_decides_star_centre() {
+        let star = |rule: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                    <path d="M 32 4 L 49 58 L 4 24 L 60 24 L 15 58 Z"
+                          fill="red" fill-rule="{rule}"/>
+                </svg>"#
+            )
+        };
+        let (nonzero, nonzero_pixels) = render_svg(&star("nonzero"));
+        let (evenodd, evenodd_pixels) = render_svg(&star("evenodd"));
+        assert_eq!(nonzero.abs_paths[0].fill_rule, FillRule::NonZero);
+        assert_eq!(evenodd.abs_paths[0].fill_rule, FillRule::EvenOdd);
+
+        // The inner pentagon has winding 2; the tips have winding 1.
+        assert_eq!(pixel(&nonzero_pixels, 64, 32, 34), [255, 0, 0, 255]);
+        assert_eq!(pixel(&evenodd_pixels, 64, 32, 34)[3], 0);
+        for pixels in [&nonzero_pixels, &evenodd_pixels] {
+            assert_eq!(pixel(pixels, 64, 32, 12), [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn stroked_open_polyline_fills_along_the_line() {
+        let corner = |join: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+                    <path d="M8 8 L56 8 L56 56" fill="none" stroke="red" stroke-width="6"
+                          stroke-linejoin="{join}"/>
+                </svg>"#
+            )
+        };
+        let (parsed, miter) = render_svg(&corner("miter"));
+        assert_eq!(parsed.abs_paths.len(), 1);
+        assert_eq!(parsed.abs_paths[0].fill_rule, FillRule::NonZero);
+        assert_eq!(parsed.abs_paths[0].stroke_width, Some(6.0));
+        let red = [255, 0, 0, 255];
+        // Along both lines, on the centre line and near the outer edges.
+        for (x, y) in [(32, 8), (32, 6), (32, 10), (56, 32), (54, 32), (58, 32)] {
+            assert_eq!(pixel(&miter, 64, x, y), red, "({x}, {y})");
+        }
+        // Off the line, past the butt ends and on the closing line a fill would add.
+        for (x, y) in [(32, 13), (5, 8), (56, 59), (32, 32)] {
+            assert_eq!(pixel(&miter, 64, x, y)[3], 0, "({x}, {y})");
+        }
+        // The miter squares off the outer corner; a bevel cuts it.
+        assert_eq!(pixel(&miter, 64, 58, 6), red);
+        let (_, bevel) = render_svg(&corner("bevel"));
+        assert_eq!(pixel(&bevel, 64, 58, 6)[3], 0);
+        assert_eq!(pixel(&bevel, 64, 56, 6), red);
+    }
+
+    #[test]
+    fn stroke_outlines_follow_paint_order_within_groups() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <g opacity="0.5">
+                <rect x="4" y="4" width="8" height="8" fill="blue" stroke="red"/>
+                <rect x="16" y="4" width="8" height="8" fill="blue" stroke="red"
+                      paint-order="stroke"/>
+            </g>
+            <rect x="4" y="20" width="8" height="8" fill="lime"/>
+        </svg>"#;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        let strokes: Vec<_> = parsed
+            .abs_paths
+            .iter()
+            .map(|path| path.stroke_width.is_some())
+            .collect();
+        assert_eq!(strokes, [false, true, true, false, false]);
+        assert_eq!(parsed.groups.len(), 1);
+        assert_eq!(parsed.groups[0].paths, 0..4);
+        let paint_ids: Vec<_> = parsed.abs_paths.iter().map(|p| p.paint_id).collect();
+        assert_eq!(paint_ids, [0, 1, 1, 0, 2]);
+        for (path_idx, path) in parsed.abs_paths.iter().enumerate() {
+            let segments = &parsed.abs_segments[path.seg_start_idx..path.seg_end_idx];
+            assert!(segments.iter().all(|s| s.path_idx == path_idx as u32));
+        }
+        let red = Paint::SolidColor {
+            rgba: [255, 0, 0, 255],
+        };
+        assert_eq!(parsed.paints[1].sample(0.0, 0.0), red.sample(0.0, 0.0));
+    }
+
+    #[test]
+    fn warnings_report_image_pattern_and_stroke() {
+        let image = to_data_uri(2, 2, &[255; 16]).unwrap();
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"
+                    xmlns:xlink="http://www.w3.org/1999/xlink" width="32" height="32">
+                <defs>
+                    <pattern id="dots" width="4" height="4" patternUnits="userSpaceOnUse">
+                        <rect width="2" height="2" fill="red"/>
+                    </pattern>
+                </defs>
+                <image id="photo" x="0" y="0" width="8" height="8" xlink:href="{image}"/>
+                <rect x="0" y="0" width="16" height="16" fill="blue"/>
+                <rect x="16" y="16" width="16" height="16" fill="url(#dots)"/>
+                <path id="line" d="M0 0 L32 32" stroke="black" stroke-dasharray="4 2"
+                      fill="none"/>
+            </svg>"#
+        );
+        let (parsed, warnings) =
+            parse_svg_str_with_warnings(&svg, &FlattenOptions::default()).unwrap();
+
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning::ImageSkipped {
+                    id: "photo".to_string()
+                },
+                ParseWarning::StrokeApproximated {
+                    id: "line".to_string()
+                },
+                ParseWarning::UnsupportedPaint { path_idx: 1 },
+            ]
+        );
+        // The dashed line is still outlined, solid; the pattern-filled path gets a
+        // transparent paint of its own.
+        assert_eq!(parsed.abs_paths.len(), 3);
+        assert_eq!(parsed.paints.len(), 3);
+        assert_eq!(parsed.abs_paths[2].stroke_width, Some(1.0));
+    }
 }
@@ -0,0 +1,216 @@
+use crate::abstract_segment::AbstractLineSegment;
+use crate::path::{resolve_paint, AbstractPath, Affine, GradientStop, Paint};
+use std::fmt::Write as _;
+use std::fs;
+use usvg::{FillRule, Transform};
+
+/// Export the filled paths as an SVG of `width` x `height` pixels.
+///
+/// Each path is written as its original line segments, with its fill rule and
+/// paint, so parsing the result gives back the same segments. Gradients become
+/// `userSpaceOnUse` gradients in `<defs>`; group opacity is not written.
+pub fn to_svg_string(
+    width: u32,
+    height: u32,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> String {
+    let mut defs = String::new();
+    let mut body = String::new();
+    for (i, path) in abs_paths.iter().enumerate() {
+        let segments = &abs_segments[path.seg_start_idx..path.seg_end_idx];
+        if segments.is_empty() {
+            continue;
+        }
+        let fill = match resolve_paint(paints, path.paint_id) {
+            Paint::SolidColor { rgba: [r, g, b, a] } => {
+                let mut fill = format!("fill=\"rgb({r},{g},{b})\"");
+                if *a != 255 {
+                    let _ = write!(fill, " fill-opacity=\"{}\"", *a as f32 / 255.0);
+                }
+                fill
+            }
+            Paint::LinearGradient {
+                start,
+                end,
+                stops,
+                inv_transform,
+            } => {
+                let _ = write!(
+                    defs,
+                    "<linearGradient id=\"paint{i}\" gradientUnits=\"userSpaceOnUse\" \
+                     x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"{}>",
+                    start[0],
+                    start[1],
+                    end[0],
+                    end[1],
+                    gradient_transform(inv_transform)
+                );
+                write_stops(&mut defs, stops);
+                defs.push_str("</linearGradient>\n");
+                format!("fill=\"url(#paint{i})\"")
+            }
+            Paint::RadialGradient {
+                center,
+                radius,
+                stops,
+                inv_transform,
+            } => {
+                let _ = write!(
+                    defs,
+                    "<radialGradient id=\"paint{i}\" gradientUnits=\"userSpaceOnUse\" \
+                     cx=\"{}\" cy=\"{}\" r=\"{radius}\"{}>",
+                    center[0],
+                    center[1],
+                    gradient_transform(inv_transform)
+                );
+                write_stops(&mut defs, stops);
+                defs.push_str("</radialGradient>\n");
+                format!("fill=\"url(#paint{i})\"")
+            }
+        };
+        let fill_rule = match path.fill_rule {
+            FillRule::EvenOdd => "evenodd",
+            FillRule::NonZero => "nonzero",
+        };
+        let _ = writeln!(
+            body,
+            "<path d=\"{}\" fill-rule=\"{fill_rule}\" {fill}/>",
+            path_data(segments)
+        );
+    }
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    if !defs.is_empty() {
+        let _ = write!(out, "<defs>\n{defs}</defs>\n");
+    }
+    out.push_str(&body);
+    out.push_str("</svg>\n");
+    out
+}
+
+pub fn save_svg(
+    path: &str,
+    width: u32,
+    height: u32,
+    abs_segments: &[AbstractLineSegment],
+    abs_paths: &[AbstractPath],
+    paints: &[Paint],
+) -> anyhow::Result<()> {
+    fs::write(
+        path,
+        to_svg_string(width, height, abs_segments, abs_paths, paints),
+    )?;
+    Ok(())
+}
+
+/// `M`/`L` commands for the segments, starting a new subpath at each break in the
+/// chain. No `Z` is written: the parser only adds a closing line when a subpath
+/// ends away from its start, which the segments never do.
+fn path_data(segments: &[AbstractLineSegment]) -> String {
+    let mut d = String::new();
+    let mut prev_end: Option<(f32, f32)> = None;
+    for seg in segments {
+        if prev_end != Some((seg.x0, seg.y0)) {
+            let _ = write!(d, "M{} {} ", seg.x0, seg.y0);
+        }
+        let _ = write!(d, "L{} {} ", seg.x1, seg.y1);
+        prev_end = Some((seg.x1, seg.y1));
+    }
+    d.pop();
+    d
+}
+
+/// ` gradientTransform="..."` undoing `inv_transform`, or nothing for the identity.
+fn gradient_transform(inv_transform: &Affine) -> String {
+    let [sx, kx, tx, ky, sy, ty] = *inv_transform;
+    let ts = Transform::from_row(sx, ky, kx, sy, tx, ty)
+        .invert()
+        .unwrap_or_default();
+    if ts.is_identity() {
+        return String::new();
+    }
+    format!(
+        " gradientTransform=\"matrix({} {} {} {} {} {})\"",
+        ts.sx, ts.ky, ts.kx, ts.sy, ts.tx, ts.ty
+    )
+}
+
+fn write_stops(out: &mut String, stops: &[GradientStop]) {
+    for stop in stops {
+        let [r, g, b, a] = stop.rgba;
+        let _ = write!(
+            out,
+            "<stop offset=\"{}\" stop-color=\"rgb({r},{g},{b})\" stop-opacity=\"{}\"/>",
+            stop.offset,
+            a as f32 / 255.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg_parser::{FlattenOptions, parse_svg_str};
+
+    #[test]
+    fn exported_svg_reparses_to_the_same_segments() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="80">
+            <defs>
+                <linearGradient id="g" x1="0" y1="0" x2="1" y2="0">
+                    <stop offset="0" stop-color="#f00"/>
+                    <stop offset="1" stop-color="#00f" stop-opacity="0.5"/>
+                </linearGradient>
+            </defs>
+            <path d="M10 10 H60 V40 H10 Z M20 20 V30 H50 V20 Z" fill="#0a0"
+                  fill-rule="evenodd" fill-opacity="0.5"/>
+            <path d="M70 10 Q95 10 95 40 C95 70 70 70 60 60" fill="url(#g)"
+                  transform="rotate(10 80 40)"/>
+        </svg>"##;
+        let parsed = parse_svg_str(svg, &FlattenOptions::default()).unwrap();
+        let exported = to_svg_string(
+            parsed.width,
+            parsed.height,
+            &parsed.abs_segments,
+            &parsed.abs_paths,
+            &parsed.paints,
+        );
+        let reparsed = parse_svg_str(&exported, &FlattenOptions::default()).unwrap();
+
+        assert_eq!((reparsed.width, reparsed.height), (100, 80));
+        let endpoints = |segments: &[AbstractLineSegment]| -> Vec<_> {
+            segments
+                .iter()
+                .map(|s| (s.path_idx, [s.x0, s.y0, s.x1, s.y1]))
+                .collect()
+        };
+        assert_eq!(
+            endpoints(&reparsed.abs_segments),
+            endpoints(&parsed.abs_segments)
+        );
+        for (a, b) in reparsed.abs_paths.iter().zip(&parsed.abs_paths) {
+            assert_eq!(
+                (a.seg_start_idx, a.seg_end_idx),
+                (b.seg_start_idx, b.seg_end_idx)
+            );
+            assert_eq!(a.fill_rule, b.fill_rule);
+        }
+        for (x, y) in [(15.0, 15.0), (80.0, 30.0), (90.0, 45.0)] {
+            assert_eq!(
+                reparsed.paints[0].sample(x, y),
+                parsed.paints[0].sample(x, y)
+            );
+            let (a, b) = (
+                reparsed.paints[1].sample(x, y),
+                parsed.paints[1].sample(x, y),
+            );
+            for c in 0..4 {
+                assert!(a[c].abs_diff(b[c]) <= 1, "({x}, {y}): {a:?} vs {b:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Scene fixtures shared by unit tests.
+
+use crate::abstract_segment::{AbstractLineSegment, SegType};
+use crate::geometry::rect::Rect;
+use crate::path::{AbstractPath, Paint};
+use usvg::tiny_skia_path::Point;
+use usvg::FillRule;
+
+#[derive(Default)]
+pub struct TestScene {
+    pub abs_segments: Vec<AbstractLineSegment>,
+    pub abs_paths: Vec<AbstractPath>,
+    pub paints: Vec<Paint>,
+}
+
+impl TestScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a closed polygon as a new path (on top of the existing ones).
+    pub fn push_polygon(&mut self, points: &[(f32, f32)], paint: Paint) -> &mut Self {
+        let path_idx = self.abs_paths.len() as u32;
+        let seg_start_idx = self.abs_segments.len();
+        for i in 0..points.len() {
+            let (x0, y0) = points[i];
+            let (x1, y1) = points[(i + 1) % points.len()];
+            self.abs_segments.push(AbstractLineSegment::new(
+                Point { x: x0, y: y0 },
+                Point { x: x1, y: y1 },
+                SegType::Linear,
+                path_idx,
+            ));
+        }
+        let xs = points.iter().map(|p| p.0);
+        let ys = points.iter().map(|p| p.1);
+        self.abs_paths.push(AbstractPath {
+            seg_start_idx,
+            seg_end_idx: self.abs_segments.len(),
+            fill_rule: FillRule::EvenOdd,
+            paint_id: self.paints.len(),
+            bounding_box: Rect::from_ltrb(
+                xs.clone().fold(f32::MAX, f32::min),
+                ys.clone().fold(f32::MAX, f32::min),
+                xs.fold(f32::MIN, f32::max),
+                ys.fold(f32::MIN, f32::max),
+            )
+            .unwrap(),
+            stroke_width: None,
+        });
+        self.paints.push(paint);
+        self
+    }
+
+    pub fn push_rect(&mut self, l: f32, t: f32, r: f32, b: f32, paint: Paint) -> &mut Self {
+        self.push_polygon(&[(l, t), (r, t), (r, b), (l, b)], paint)
+    }
+}
+
+pub fn solid(rgba: [u8; 4]) -> Paint {
+    Paint::SolidColor { rgba }
+}
+
+/// Winding of `(x, y)` by brute force over every segment, for comparison.
+pub fn reference_winding(segs: &[AbstractLineSegment], x: f32, y: f32) -> i32 {
+    segs.iter()
+        .filter(|s| (s.y0 <= y) != (s.y1 <= y))
+        .filter(|s| s.x0 + (y - s.y0) / (s.y1 - s.y0) * (s.x1 - s.x0) < x)
+        .map(|s| if s.y1 > s.y0 { 1 } else { -1 })
+        .sum()
+}
+
+pub fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+    let base = ((y * width + x) * 4) as usize;
+    [pixels[base], pixels[base + 1], pixels[base + 2], pixels[base + 3]]
+}